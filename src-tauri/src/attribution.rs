@@ -0,0 +1,336 @@
+use crate::database::Database;
+use crate::watch::{self, WatchedTree};
+use notify::event::{EventKind, ModifyKind};
+use notify::RecommendedWatcher;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+/// Quiet period before buffered file events are written, so an editor's
+/// burst of writes to one file becomes a single entry
+const FLUSH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Longest buffered events wait while more keep arriving, e.g. during a build
+const FLUSH_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// A terminal counts as active, and gets credit for file changes, while it
+/// has printed output this recently. claude animates a status line while it
+/// works, so a session editing files is never quiet for long.
+const ACTIVITY_WINDOW: Duration = Duration::from_secs(10);
+
+/// Directories whose contents are build output or git internals rather
+/// than edits, left unwatched even where git doesn't ignore them
+const IGNORED_DIRS: &[&str] = &[".git", "node_modules", "target", "__pycache__"];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileChangeKind {
+    Created,
+    Modified,
+    Renamed,
+    Removed,
+}
+
+impl FileChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileChangeKind::Created => "created",
+            FileChangeKind::Modified => "modified",
+            FileChangeKind::Renamed => "renamed",
+            FileChangeKind::Removed => "removed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "created" => FileChangeKind::Created,
+            "renamed" => FileChangeKind::Renamed,
+            "removed" => FileChangeKind::Removed,
+            _ => FileChangeKind::Modified,
+        }
+    }
+
+    fn from_event(kind: &EventKind) -> Option<Self> {
+        match kind {
+            EventKind::Create(_) => Some(FileChangeKind::Created),
+            EventKind::Modify(ModifyKind::Name(_)) => Some(FileChangeKind::Renamed),
+            EventKind::Modify(ModifyKind::Metadata(_)) => None,
+            EventKind::Modify(_) => Some(FileChangeKind::Modified),
+            EventKind::Remove(_) => Some(FileChangeKind::Removed),
+            _ => None,
+        }
+    }
+}
+
+/// A file change credited to a terminal, waiting to be stored
+#[derive(Debug, Clone)]
+pub struct AttributedChange {
+    pub terminal_id: String,
+    pub path: String,
+    pub change: FileChangeKind,
+    pub changed_at: String,
+    /// Other terminals watching the file were active at the same time
+    pub shared: bool,
+}
+
+struct WatchedTerminal {
+    root: PathBuf,
+    last_output: Option<Instant>,
+    /// Files changed while this was the only active terminal watching them
+    sole_changes: HashSet<PathBuf>,
+}
+
+/// A watched working directory. Locked on its own, so walking new
+/// directories doesn't hold up the rest of the tracker.
+struct WatchedRoot {
+    watcher: RecommendedWatcher,
+    tree: WatchedTree,
+}
+
+/// A filesystem event and the root whose watcher saw it
+type RootEvent = (PathBuf, notify::Result<notify::Event>);
+
+struct TrackerState {
+    events: mpsc::Sender<RootEvent>,
+    roots: HashMap<PathBuf, Arc<std::sync::Mutex<WatchedRoot>>>,
+    /// Roots whose initial walk is still running
+    walking: HashSet<PathBuf>,
+    terminals: HashMap<String, WatchedTerminal>,
+}
+
+/// Terminals a change to `path` is credited to: those watching it that were
+/// recently active
+fn active_for(terminals: &HashMap<String, WatchedTerminal>, path: &Path, now: Instant) -> Vec<String> {
+    terminals
+        .iter()
+        .filter(|(_, t)| path.starts_with(&t.root))
+        .filter(|(_, t)| t.last_output.is_some_and(|at| now.duration_since(at) <= ACTIVITY_WINDOW))
+        .map(|(id, _)| id.clone())
+        .collect()
+}
+
+/// Watches running terminals' working directories and records which
+/// session changed which file
+#[derive(Clone)]
+pub struct ChangeTracker {
+    state: Arc<std::sync::Mutex<TrackerState>>,
+}
+
+impl ChangeTracker {
+    /// Starts the watcher thread that writes attributed changes to `db`
+    pub fn start(db: Arc<tokio::sync::Mutex<Database>>) -> Self {
+        let (events, rx) = mpsc::channel();
+        let state = Arc::new(std::sync::Mutex::new(TrackerState {
+            events,
+            roots: HashMap::new(),
+            walking: HashSet::new(),
+            terminals: HashMap::new(),
+        }));
+
+        let thread_state = state.clone();
+        std::thread::spawn(move || loop {
+            let mut pending: HashMap<(String, String), AttributedChange> = HashMap::new();
+            let open = watch::recv_burst(&rx, FLUSH_DEBOUNCE, FLUSH_MAX_DELAY, |event| {
+                attribute(&thread_state, event, &mut pending)
+            });
+            if !pending.is_empty() {
+                let changes: Vec<AttributedChange> = pending.into_values().collect();
+                if let Err(e) = db.blocking_lock().insert_file_changes(&changes) {
+                    eprintln!("Failed to record file changes: {}", e);
+                }
+            }
+            if !open {
+                break;
+            }
+        });
+
+        ChangeTracker { state }
+    }
+
+    /// Starts watching a terminal's working directory, leaving out what git
+    /// ignores there. A new directory is walked on a thread of its own, and
+    /// its changes are recorded once that is done.
+    pub fn watch(&self, terminal_id: &str, working_directory: &str) {
+        let root = Path::new(working_directory);
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let events = {
+            let mut state = self.state.lock().unwrap();
+            state.terminals.insert(
+                terminal_id.to_string(),
+                WatchedTerminal { root: root.clone(), last_output: None, sole_changes: HashSet::new() },
+            );
+            if state.roots.contains_key(&root) || !state.walking.insert(root.clone()) {
+                return;
+            }
+            state.events.clone()
+        };
+
+        let state = self.state.clone();
+        std::thread::spawn(move || {
+            let event_root = root.clone();
+            let watched = notify::recommended_watcher(move |event| {
+                let _ = events.send((event_root.clone(), event));
+            })
+            .map(|mut watcher| {
+                let mut tree = WatchedTree::new(&root, IGNORED_DIRS);
+                tree.watch(&mut watcher, &root);
+                WatchedRoot { watcher, tree }
+            });
+
+            let mut state = state.lock().unwrap();
+            state.walking.remove(&root);
+            let watched = match watched {
+                Ok(watched) => watched,
+                Err(e) => {
+                    eprintln!("Failed to start file change watcher: {}", e);
+                    return;
+                }
+            };
+            // Every terminal using it may have closed during the walk
+            if state.terminals.values().any(|t| t.root == root) {
+                state.roots.insert(root, Arc::new(std::sync::Mutex::new(watched)));
+            }
+        });
+    }
+
+    /// Stops watching a terminal's working directory once no other terminal
+    /// uses it
+    pub fn unwatch(&self, terminal_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        let Some(terminal) = state.terminals.remove(terminal_id) else {
+            return;
+        };
+        // Dropping the watcher removes its watches
+        if !state.terminals.values().any(|t| t.root == terminal.root) {
+            state.roots.remove(&terminal.root);
+        }
+    }
+
+    /// Files that changed while the terminal was the only active one
+    /// watching them, so the change was certainly its own
+    pub fn sole_changes(&self, terminal_id: &str) -> HashSet<PathBuf> {
+        self.state
+            .lock()
+            .unwrap()
+            .terminals
+            .get(terminal_id)
+            .map(|t| t.sole_changes.clone())
+            .unwrap_or_default()
+    }
+
+    /// Forgets sole changes to files no longer in the terminal's change set,
+    /// e.g. once they were committed or reverted
+    pub fn retain_sole_changes(&self, terminal_id: &str, changed: &HashSet<PathBuf>) {
+        if let Some(terminal) = self.state.lock().unwrap().terminals.get_mut(terminal_id) {
+            terminal.sole_changes.retain(|path| changed.contains(path));
+        }
+    }
+
+    /// Notes that a terminal just printed output
+    pub fn mark_active(&self, terminal_id: &str) {
+        if let Some(terminal) = self.state.lock().unwrap().terminals.get_mut(terminal_id) {
+            terminal.last_output = Some(Instant::now());
+        }
+    }
+}
+
+/// Credits an event's paths to the terminals active right now
+fn attribute(
+    state: &std::sync::Mutex<TrackerState>,
+    (root, event): RootEvent,
+    pending: &mut HashMap<(String, String), AttributedChange>,
+) {
+    let Ok(event) = event else {
+        return;
+    };
+    let Some(change) = FileChangeKind::from_event(&event.kind) else {
+        return;
+    };
+    // The root may have been unwatched since
+    let Some(watched) = state.lock().unwrap().roots.get(&root).cloned() else {
+        return;
+    };
+    let paths: Vec<&PathBuf> = {
+        let mut watched = watched.lock().unwrap();
+        let WatchedRoot { watcher, tree } = &mut *watched;
+        // Also watches new directories
+        event
+            .paths
+            .iter()
+            .filter(|path| tree.handle_path(watcher, &event.kind, path) && !path.is_dir())
+            .collect()
+    };
+
+    let now = Instant::now();
+    let changed_at = chrono::Utc::now().to_rfc3339();
+    let mut state = state.lock().unwrap();
+    let watching = &mut state.terminals;
+    for path in paths {
+        let terminals = active_for(watching, path, now);
+        let shared = terminals.len() > 1;
+        if let [terminal_id] = terminals.as_slice() {
+            if let Some(terminal) = watching.get_mut(terminal_id) {
+                terminal.sole_changes.insert(path.clone());
+            }
+        }
+        let path = path.to_string_lossy().to_string();
+        for terminal_id in terminals {
+            let key = (terminal_id.clone(), path.clone());
+            // A file created and then written in one burst stays "created"
+            let change = match pending.get(&key) {
+                Some(earlier) if earlier.change == FileChangeKind::Created && change != FileChangeKind::Removed => {
+                    FileChangeKind::Created
+                }
+                _ => change,
+            };
+            pending.insert(
+                key,
+                AttributedChange {
+                    terminal_id,
+                    path: path.clone(),
+                    change,
+                    changed_at: changed_at.clone(),
+                    shared,
+                },
+            );
+        }
+    }
+}
+
+/// One stored file change in a session's timeline
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileChangeEntry {
+    pub id: i64,
+    pub session_id: i64,
+    pub path: String,
+    pub change: FileChangeKind,
+    pub changed_at: String,
+    pub shared: bool,
+}
+
+/// A file touched by a session
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionFile {
+    pub path: String,
+    pub change_count: u32,
+    pub first_changed_at: String,
+    pub last_changed_at: String,
+    pub last_change: FileChangeKind,
+    /// Some of the changes happened while other terminals were active
+    pub shared: bool,
+}
+
+/// A session that touched a file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileSession {
+    pub session_id: i64,
+    pub terminal_id: String,
+    pub label: String,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+    pub change_count: u32,
+    pub last_changed_at: String,
+    pub shared: bool,
+}
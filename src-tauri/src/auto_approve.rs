@@ -0,0 +1,410 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Stripped terminal output kept for prompt detection (16 KB)
+const MAX_DETECTOR_BUFFER: usize = 16 * 1024;
+
+/// claude redraws a prompt while it is on screen; the same prompt isn't
+/// answered again within this window
+const ANSWER_COOLDOWN: Duration = Duration::from_secs(3);
+
+/// Keys sent to claude's permission menu: Enter picks the highlighted "Yes",
+/// Escape picks "No, and tell Claude what to do differently"
+const APPROVE_KEYS: &[u8] = b"\r";
+const DENY_KEYS: &[u8] = b"\x1b";
+
+/// Title of the prompt box claude shows before running a shell command
+const BASH_PROMPT_TITLE: &str = "Bash command";
+
+/// Compiled rule patterns by rule id, with the pattern and decision they
+/// were compiled from so edited rules are recompiled; None for patterns that
+/// don't compile
+type PatternCache = HashMap<String, (String, Decision, Option<Regex>)>;
+
+static COMPILED: LazyLock<Mutex<PatternCache>> = LazyLock::new(Default::default);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Approve,
+    Deny,
+}
+
+impl Decision {
+    pub fn keys(self) -> &'static [u8] {
+        match self {
+            Decision::Approve => APPROVE_KEYS,
+            Decision::Deny => DENY_KEYS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoApproveRule {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Case-insensitive regex. Approve rules must match the whole command of a
+    /// Bash prompt, or the whole question of other prompts; deny rules match
+    /// anywhere in the prompt text.
+    pub pattern: String,
+    pub decision: Decision,
+    /// Working directories (and their subdirectories) the rule applies in;
+    /// empty means every terminal
+    #[serde(default)]
+    pub directories: Vec<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RuleMatch {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub decision: Decision,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AutoApproveAuditEntry {
+    pub id: i64,
+    pub terminal_id: String,
+    pub prompt_text: String,
+    pub rule_id: String,
+    pub rule_name: String,
+    pub decision: Decision,
+    pub decided_at: String,
+}
+
+impl AutoApproveRule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() || self.name.len() > 255 {
+            return Err("Rule name must be 1-255 characters".to_string());
+        }
+        if self.pattern.trim().is_empty() {
+            return Err("Rule pattern can't be empty".to_string());
+        }
+        RegexBuilder::new(&self.pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| format!("Invalid pattern: {}", e))?;
+        Ok(())
+    }
+
+    fn applies_in(&self, working_directory: &str) -> bool {
+        if self.directories.is_empty() {
+            return true;
+        }
+        let cwd = Path::new(working_directory);
+        let cwd = cwd.canonicalize().unwrap_or_else(|_| cwd.to_path_buf());
+        self.directories.iter().any(|dir| {
+            let dir = Path::new(dir);
+            cwd.starts_with(dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf()))
+        })
+    }
+
+    fn matches(&self, prompt: &PermissionPrompt) -> bool {
+        let Some(re) = self.compiled() else {
+            return false;
+        };
+        match self.decision {
+            Decision::Deny => re.is_match(&prompt.text),
+            Decision::Approve => prompt.subject.as_deref().is_some_and(|subject| re.is_match(subject)),
+        }
+    }
+
+    fn compiled(&self) -> Option<Regex> {
+        let mut cache = COMPILED.lock().unwrap();
+        if let Some((pattern, decision, re)) = cache.get(&self.id) {
+            if *pattern == self.pattern && *decision == self.decision {
+                return re.clone();
+            }
+        }
+        let source = match self.decision {
+            Decision::Approve => format!("^(?:{})$", self.pattern),
+            Decision::Deny => self.pattern.clone(),
+        };
+        let re = RegexBuilder::new(&source).case_insensitive(true).build().ok();
+        cache.insert(self.id.clone(), (self.pattern.clone(), self.decision, re.clone()));
+        re
+    }
+}
+
+/// Drops a deleted rule's compiled pattern
+pub fn forget_rule(id: &str) {
+    COMPILED.lock().unwrap().remove(id);
+}
+
+/// A permission prompt as claude drew it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PermissionPrompt {
+    /// Everything from the box title to the question, whitespace collapsed
+    pub text: String,
+    /// What approve rules are matched against: the command of a Bash prompt,
+    /// otherwise the question. None when the command can't be told apart
+    /// from its description, so only deny rules apply.
+    pub subject: Option<String>,
+}
+
+impl PermissionPrompt {
+    /// Reads a prompt from stripped output starting at the box's top border,
+    /// which must end with the complete Yes/No menu
+    fn parse(drawn: &str) -> Result<Self, Incomplete> {
+        let lines: Vec<&str> = drawn.lines().map(clean).collect();
+        let question = lines
+            .iter()
+            .rposition(|l| l.starts_with("Do you want to"))
+            .ok_or(Incomplete::Superseded)?;
+        let menu: Vec<&str> = lines[question + 1..].iter().copied().filter(|l| !l.is_empty()).collect();
+        // Anything but menu options after the question means something else
+        // was drawn since
+        if !menu.iter().all(|l| is_option(l)) || menu.first().is_some_and(|l| !l.starts_with("1. Yes")) {
+            return Err(Incomplete::Superseded);
+        }
+        if !menu.last().is_some_and(|l| l.contains("No")) {
+            return Err(Incomplete::Drawing);
+        }
+
+        let mut body = lines[1..question].iter().copied().filter(|l| !l.is_empty());
+        let title = body.next().ok_or(Incomplete::Superseded)?;
+        let body: Vec<&str> = body.collect();
+        let subject = if title == BASH_PROMPT_TITLE {
+            // The command, optionally followed by its one-line description
+            match body.as_slice() {
+                [command] | [command, _] => Some(command.to_string()),
+                _ => None,
+            }
+        } else {
+            Some(lines[question].to_string())
+        };
+        let text = std::iter::once(title)
+            .chain(body)
+            .chain(std::iter::once(lines[question]))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(PermissionPrompt { text: normalize(&text), subject })
+    }
+
+    /// A prompt typed in to test rules: a whole prompt box, or just the
+    /// command or question
+    pub fn from_text(text: &str) -> Self {
+        let plain = || PermissionPrompt {
+            text: normalize(text),
+            subject: Some(normalize(text)),
+        };
+        let Some(start) = text.find('╭') else {
+            return plain();
+        };
+        PermissionPrompt::parse(&format!("{}\n1. Yes\n2. No", &text[start..])).unwrap_or_else(|_| plain())
+    }
+}
+
+enum Incomplete {
+    /// The menu is still being drawn
+    Drawing,
+    /// Something other than a prompt is on screen now
+    Superseded,
+}
+
+/// Drops the box borders, padding and selection marker around a line
+fn clean(line: &str) -> &str {
+    line.trim_matches(|c: char| c.is_whitespace() || ('\u{2500}'..='\u{257f}').contains(&c))
+        .trim_start_matches('❯')
+        .trim()
+}
+
+/// Whether a line is a numbered menu option such as `2. No`
+fn is_option(line: &str) -> bool {
+    line.split_once(". ")
+        .is_some_and(|(n, _)| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Picks the rule that decides a prompt. Deny rules win over approve rules so
+/// a broad "yes" can't override a specific "no".
+pub fn decide(
+    rules: &[AutoApproveRule],
+    working_directory: &str,
+    prompt: &PermissionPrompt,
+) -> Option<RuleMatch> {
+    let applicable: Vec<&AutoApproveRule> = rules
+        .iter()
+        .filter(|r| r.enabled && r.applies_in(working_directory) && r.matches(prompt))
+        .collect();
+    applicable
+        .iter()
+        .find(|r| r.decision == Decision::Deny)
+        .or_else(|| applicable.first())
+        .map(|r| RuleMatch {
+            rule_id: r.id.clone(),
+            rule_name: r.name.clone(),
+            decision: r.decision,
+        })
+}
+
+/// Watches a terminal's output for claude's permission prompts
+#[derive(Default)]
+pub struct PromptDetector {
+    buffer: String,
+    last_prompt: Option<(String, Instant)>,
+}
+
+impl PromptDetector {
+    /// Feeds raw PTY output. Returns the prompt once a complete permission
+    /// prompt (question plus its Yes/No options) is the last thing drawn.
+    pub fn feed(&mut self, data: &[u8]) -> Option<PermissionPrompt> {
+        let stripped = strip_ansi_escapes::strip(data);
+        self.buffer.push_str(&String::from_utf8_lossy(&stripped));
+        if self.buffer.len() > MAX_DETECTOR_BUFFER {
+            let mut cut = self.buffer.len() - MAX_DETECTOR_BUFFER;
+            while !self.buffer.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.buffer.drain(..cut);
+        }
+
+        let question = self.buffer.rfind("Do you want to")?;
+        // The prompt box starts at the last top-left corner before the question
+        let Some(start) = self.buffer[..question].rfind('╭') else {
+            self.buffer.clear();
+            return None;
+        };
+        let prompt = match PermissionPrompt::parse(&self.buffer[start..]) {
+            Ok(prompt) => prompt,
+            Err(Incomplete::Drawing) => return None,
+            // A menu followed by other output is no longer waiting for an
+            // answer, and may not have been a real prompt
+            Err(Incomplete::Superseded) => {
+                self.buffer.clear();
+                return None;
+            }
+        };
+        self.buffer.clear();
+
+        if let Some((last, at)) = &self.last_prompt {
+            if *last == prompt.text && at.elapsed() < ANSWER_COOLDOWN {
+                return None;
+            }
+        }
+        self.last_prompt = Some((prompt.text.clone(), Instant::now()));
+        Some(prompt)
+    }
+}
+
+/// Drops box-drawing characters and collapses whitespace so rules see plain text
+fn normalize(text: &str) -> String {
+    text.chars()
+        .map(|c| if ('\u{2500}'..='\u{257f}').contains(&c) { ' ' } else { c })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A prompt box as claude draws it, with the menu below the question
+    fn prompt_box(body: &[&str], question: &str) -> String {
+        let mut drawn = format!("╭{}╮\r\n", "─".repeat(60));
+        for line in body.iter().chain([&"", &question]) {
+            drawn.push_str(&format!("│ {:<58} │\r\n", line));
+        }
+        drawn.push_str("│ ❯ 1. Yes                                                   │\r\n");
+        drawn.push_str("│   2. Yes, and don't ask again this session                 │\r\n");
+        drawn.push_str("│   3. No, and tell Claude what to do differently (esc)      │\r\n");
+        drawn.push_str(&format!("╰{}╯\r\n", "─".repeat(60)));
+        drawn
+    }
+
+    fn rule(id: &str, pattern: &str, decision: Decision) -> AutoApproveRule {
+        AutoApproveRule {
+            id: id.to_string(),
+            name: id.to_string(),
+            enabled: true,
+            pattern: pattern.to_string(),
+            decision,
+            directories: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn detects_bash_prompt_with_its_command() {
+        let drawn = prompt_box(&["Bash command", "", "  npm test", "  Run the test suite"], "Do you want to proceed?");
+        let prompt = PromptDetector::default().feed(drawn.as_bytes()).unwrap();
+        assert_eq!(prompt.subject.as_deref(), Some("npm test"));
+        assert_eq!(prompt.text, "Bash command npm test Run the test suite Do you want to proceed?");
+    }
+
+    #[test]
+    fn detects_other_prompts_by_their_question() {
+        let drawn = prompt_box(&["Edit file", "", "  src/main.rs"], "Do you want to make this edit to main.rs?");
+        let prompt = PromptDetector::default().feed(drawn.as_bytes()).unwrap();
+        assert_eq!(prompt.subject.as_deref(), Some("Do you want to make this edit to main.rs?"));
+        assert!(prompt.text.starts_with("Edit file src/main.rs"));
+    }
+
+    #[test]
+    fn waits_for_a_prompt_split_across_chunks() {
+        let drawn = prompt_box(&["Bash command", "", "  cargo build"], "Do you want to proceed?");
+        let split = drawn.find("2. Yes").unwrap();
+        let mut detector = PromptDetector::default();
+        assert_eq!(detector.feed(&drawn.as_bytes()[..split]), None);
+        let prompt = detector.feed(&drawn.as_bytes()[split..]).unwrap();
+        assert_eq!(prompt.subject.as_deref(), Some("cargo build"));
+    }
+
+    #[test]
+    fn reads_through_ansi_and_ignores_redraws() {
+        let drawn = prompt_box(&["Bash command", "", "  ls -la"], "Do you want to proceed?")
+            .replace("❯ 1. Yes", "\x1b[38;5;153m❯\x1b[39m \x1b[1m1. Yes\x1b[22m")
+            .replace("\r\n", "\x1b[K\r\n");
+        let mut detector = PromptDetector::default();
+        let redraw = format!("\x1b[2J\x1b[H{}", drawn);
+        assert_eq!(detector.feed(redraw.as_bytes()).unwrap().subject.as_deref(), Some("ls -la"));
+        // The same prompt drawn again isn't answered twice
+        assert_eq!(detector.feed(redraw.as_bytes()), None);
+    }
+
+    #[test]
+    fn ignores_a_menu_followed_by_other_output() {
+        let drawn = prompt_box(&["Bash command", "", "  ls"], "Do you want to proceed?");
+        let mut detector = PromptDetector::default();
+        assert_eq!(detector.feed(format!("{}⏺ Bash(ls)\r\n", drawn).as_bytes()), None);
+        assert_eq!(detector.feed(b"Do you want to see more? (y/n)\r\n"), None);
+    }
+
+    #[test]
+    fn deny_beats_approve_and_approve_needs_the_whole_command() {
+        let rules = [
+            rule("approve-npm", "npm .*", Decision::Approve),
+            rule("deny-rm", r"rm\s+-rf", Decision::Deny),
+        ];
+        let decide_for = |command: &str| {
+            let drawn = prompt_box(&["Bash command", "", command], "Do you want to proceed?");
+            let prompt = PromptDetector::default().feed(drawn.as_bytes()).unwrap();
+            decide(&rules, "/", &prompt).map(|m| (m.rule_id, m.decision))
+        };
+        assert_eq!(decide_for("npm test"), Some(("approve-npm".to_string(), Decision::Approve)));
+        assert_eq!(decide_for("npm test && rm -rf /"), Some(("deny-rm".to_string(), Decision::Deny)));
+        assert_eq!(decide_for("cargo test; npm test"), None);
+    }
+
+    #[test]
+    fn edited_rules_are_recompiled() {
+        let prompt = PermissionPrompt::from_text("git status");
+        let mut edited = rule("edited", "git status", Decision::Approve);
+        assert!(edited.matches(&prompt));
+        edited.pattern = "git diff".to_string();
+        assert!(!edited.matches(&prompt));
+        forget_rule("edited");
+        assert!(!COMPILED.lock().unwrap().contains_key("edited"));
+    }
+}
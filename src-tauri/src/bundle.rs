@@ -0,0 +1,517 @@
+use crate::config::ConfigProfile;
+use crate::database::Snippet;
+use crate::policy::PermissionPolicy;
+use crate::terminal::TerminalConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Current version of the profile bundle format. Bundles written by a newer
+/// version of the app are rejected rather than partially imported.
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// A portable set of profiles (with the permission policies they use, and
+/// optionally snippets and workspaces) that can be committed to a repository
+/// and shared between machines.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileBundle {
+    pub version: u32,
+    pub exported_at: String,
+    #[serde(default)]
+    pub profiles: Vec<ConfigProfile>,
+    #[serde(default)]
+    pub snippets: Vec<Snippet>,
+    #[serde(default)]
+    pub workspaces: Vec<BundledWorkspace>,
+    /// Permission policies the profiles refer to
+    #[serde(default)]
+    pub policies: Vec<PermissionPolicy>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundledWorkspace {
+    pub name: String,
+    pub terminals: Vec<TerminalConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BundleFormat {
+    Json,
+    Toml,
+}
+
+impl BundleFormat {
+    /// Picks the format from the file extension (`.json` or `.toml`)
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("json") => Ok(Self::Json),
+            Some("toml") => Ok(Self::Toml),
+            _ => Err("Bundle file must have a .json or .toml extension".to_string()),
+        }
+    }
+}
+
+impl ProfileBundle {
+    pub fn new(
+        profiles: Vec<ConfigProfile>,
+        snippets: Vec<Snippet>,
+        workspaces: Vec<BundledWorkspace>,
+        policies: Vec<PermissionPolicy>,
+    ) -> Self {
+        Self {
+            version: BUNDLE_VERSION,
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            profiles,
+            snippets,
+            workspaces,
+            policies,
+        }
+    }
+
+    pub fn serialize(&self, format: BundleFormat) -> Result<String, String> {
+        match format {
+            BundleFormat::Json => serde_json::to_string_pretty(self)
+                .map_err(|e| format!("Failed to serialize bundle: {}", e)),
+            BundleFormat::Toml => toml::to_string_pretty(self)
+                .map_err(|e| format!("Failed to serialize bundle: {}", e)),
+        }
+    }
+
+    pub fn parse(content: &str, format: BundleFormat) -> Result<Self, String> {
+        let bundle: Self = match format {
+            BundleFormat::Json => serde_json::from_str(content)
+                .map_err(|e| format!("Invalid bundle: {}", e))?,
+            BundleFormat::Toml => toml::from_str(content)
+                .map_err(|e| format!("Invalid bundle: {}", e))?,
+        };
+        if bundle.version == 0 || bundle.version > BUNDLE_VERSION {
+            return Err(format!(
+                "Unsupported bundle version {} (this app supports up to {})",
+                bundle.version, BUNDLE_VERSION
+            ));
+        }
+        Ok(bundle)
+    }
+}
+
+/// How to handle a bundle entry whose id or name already exists locally
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictStrategy {
+    #[default]
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Create,
+    Overwrite,
+    Rename,
+    Skip,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportItem {
+    pub kind: String,
+    pub name: String,
+    pub action: ImportAction,
+    /// What clashed with an existing entry ("id" or "name"), if anything, or
+    /// "internal" for workspace names reserved for the app
+    pub conflict: Option<String>,
+    /// Name the entry is stored under after import (differs from `name` when renamed)
+    pub final_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub items: Vec<ImportItem>,
+}
+
+/// Records to write for an import, along with the per-entry report
+pub struct ImportPlan {
+    pub policies: Vec<PermissionPolicy>,
+    pub profiles: Vec<ConfigProfile>,
+    pub snippets: Vec<Snippet>,
+    pub workspaces: Vec<BundledWorkspace>,
+    pub items: Vec<ImportItem>,
+}
+
+/// Resolved outcome for a single bundle entry
+struct Resolution {
+    action: ImportAction,
+    conflict: Option<String>,
+    id: String,
+    name: String,
+}
+
+/// Tracks ids and (case-insensitive) names that are in use, both locally and
+/// by entries planned earlier in the same import.
+struct Taken {
+    ids: HashSet<String>,
+    /// Lowercased names, each with the id of the entry holding it
+    names: HashMap<String, String>,
+}
+
+impl Taken {
+    fn new(existing: Vec<(String, String)>) -> Self {
+        let ids = existing.iter().map(|(id, _)| id.clone()).collect();
+        let names = existing
+            .into_iter()
+            .map(|(id, name)| (name.to_lowercase(), id))
+            .collect();
+        Self { ids, names }
+    }
+
+    fn unique_name(&self, base: &str) -> String {
+        let mut candidate = format!("{} (imported)", base);
+        let mut n = 2;
+        while self.names.contains_key(&candidate.to_lowercase()) {
+            candidate = format!("{} (imported {})", base, n);
+            n += 1;
+        }
+        candidate
+    }
+
+    /// Resolves one entry. Entries without their own id (workspaces) pass
+    /// `None` and are matched by name only.
+    fn resolve(&mut self, id: Option<&str>, name: &str, strategy: ConflictStrategy) -> Resolution {
+        let id_clash = id.is_some_and(|id| self.ids.contains(id));
+        // Names claimed earlier in this import clash just like local ones
+        let name_owner = self.names.get(&name.to_lowercase()).cloned();
+        let name_clash = match (&name_owner, id) {
+            (Some(owner), Some(id)) => owner != id,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        let id = id.unwrap_or(name).to_string();
+
+        let conflict = match (id_clash, name_clash) {
+            (false, false) => None,
+            (true, false) => Some("id".to_string()),
+            (false, true) => Some("name".to_string()),
+            (true, true) => Some("id, name".to_string()),
+        };
+
+        let (action, id, name) = match (conflict.is_some(), strategy) {
+            (false, _) => (ImportAction::Create, id, name.to_string()),
+            (true, ConflictStrategy::Skip) => (ImportAction::Skip, id, name.to_string()),
+            // Replace the entry with the same id; otherwise take over the entry
+            // with the same name so no duplicate is created.
+            (true, ConflictStrategy::Overwrite) if id_clash && name_clash => {
+                (ImportAction::Overwrite, id, self.unique_name(name))
+            }
+            (true, ConflictStrategy::Overwrite) if id_clash => {
+                (ImportAction::Overwrite, id, name.to_string())
+            }
+            (true, ConflictStrategy::Overwrite) => {
+                (ImportAction::Overwrite, name_owner.unwrap_or(id), name.to_string())
+            }
+            (true, ConflictStrategy::Rename) => {
+                let id = if id_clash { uuid::Uuid::new_v4().to_string() } else { id };
+                let name = if name_clash { self.unique_name(name) } else { name.to_string() };
+                (ImportAction::Rename, id, name)
+            }
+        };
+
+        if action != ImportAction::Skip {
+            self.ids.insert(id.clone());
+            self.names.insert(name.to_lowercase(), id.clone());
+        }
+        Resolution { action, conflict, id, name }
+    }
+}
+
+/// Works out what importing `bundle` would do against the local data without
+/// writing anything. The returned plan is applied as-is for a real import.
+/// Profiles keep their permission policy when the bundle carries it or it
+/// exists locally, and lose it otherwise.
+pub fn plan_import(
+    bundle: ProfileBundle,
+    existing_profiles: &[ConfigProfile],
+    existing_snippets: &[Snippet],
+    existing_workspaces: &[String],
+    existing_policies: &[PermissionPolicy],
+    strategy: ConflictStrategy,
+) -> ImportPlan {
+    let mut plan = ImportPlan {
+        policies: Vec::new(),
+        profiles: Vec::new(),
+        snippets: Vec::new(),
+        workspaces: Vec::new(),
+        items: Vec::new(),
+    };
+
+    // Bundle policy ids mapped to the local policy profiles should use
+    let mut policy_ids: HashMap<String, String> = existing_policies
+        .iter()
+        .map(|p| (p.id.clone(), p.id.clone()))
+        .collect();
+    let mut taken = Taken::new(
+        existing_policies
+            .iter()
+            .map(|p| (p.id.clone(), p.name.clone()))
+            .collect(),
+    );
+    for mut policy in bundle.policies {
+        let r = taken.resolve(Some(&policy.id), &policy.name, strategy);
+        plan.items.push(ImportItem {
+            kind: "policy".to_string(),
+            name: policy.name.clone(),
+            action: r.action.clone(),
+            conflict: r.conflict,
+            final_name: r.name.clone(),
+        });
+        if r.action == ImportAction::Skip {
+            // The policy it clashed with, local or imported earlier, stands in for it
+            let local = existing_policies
+                .iter()
+                .chain(&plan.policies)
+                .find(|p| p.id == policy.id)
+                .or_else(|| {
+                    existing_policies
+                        .iter()
+                        .chain(&plan.policies)
+                        .find(|p| p.name.eq_ignore_ascii_case(&policy.name))
+                });
+            if let Some(local) = local {
+                policy_ids.insert(policy.id, local.id.clone());
+            }
+        } else {
+            policy_ids.insert(policy.id, r.id.clone());
+            // An overwrite may replace an entry planned earlier in this import
+            plan.policies.retain(|p| p.id != r.id);
+            policy.id = r.id;
+            policy.name = r.name;
+            plan.policies.push(policy);
+        }
+    }
+
+    let mut taken = Taken::new(
+        existing_profiles
+            .iter()
+            .map(|p| (p.id.clone(), p.name.clone()))
+            .collect(),
+    );
+    for mut profile in bundle.profiles {
+        let r = taken.resolve(Some(&profile.id), &profile.name, strategy);
+        plan.items.push(ImportItem {
+            kind: "profile".to_string(),
+            name: profile.name.clone(),
+            action: r.action.clone(),
+            conflict: r.conflict,
+            final_name: r.name.clone(),
+        });
+        if r.action != ImportAction::Skip {
+            plan.profiles.retain(|p| p.id != r.id);
+            profile.id = r.id;
+            profile.name = r.name;
+            profile.permission_policy_id = profile
+                .permission_policy_id
+                .and_then(|id| policy_ids.get(&id).cloned());
+            plan.profiles.push(profile);
+        }
+    }
+
+    let mut taken = Taken::new(
+        existing_snippets
+            .iter()
+            .map(|s| (s.id.clone(), s.title.clone()))
+            .collect(),
+    );
+    for mut snippet in bundle.snippets {
+        let r = taken.resolve(Some(&snippet.id), &snippet.title, strategy);
+        plan.items.push(ImportItem {
+            kind: "snippet".to_string(),
+            name: snippet.title.clone(),
+            action: r.action.clone(),
+            conflict: r.conflict,
+            final_name: r.name.clone(),
+        });
+        if r.action != ImportAction::Skip {
+            plan.snippets.retain(|s| s.id != r.id);
+            snippet.id = r.id;
+            snippet.title = r.name;
+            plan.snippets.push(snippet);
+        }
+    }
+
+    // Workspaces are keyed by name only
+    let mut taken = Taken::new(
+        existing_workspaces
+            .iter()
+            .map(|name| (name.clone(), name.clone()))
+            .collect(),
+    );
+    for mut workspace in bundle.workspaces {
+        if workspace.name.starts_with("__") {
+            plan.items.push(ImportItem {
+                kind: "workspace".to_string(),
+                name: workspace.name.clone(),
+                action: ImportAction::Skip,
+                conflict: Some("internal".to_string()),
+                final_name: workspace.name,
+            });
+            continue;
+        }
+        let r = taken.resolve(None, &workspace.name, strategy);
+        plan.items.push(ImportItem {
+            kind: "workspace".to_string(),
+            name: workspace.name.clone(),
+            action: r.action.clone(),
+            conflict: r.conflict,
+            final_name: r.name.clone(),
+        });
+        // Overwriting keeps the stored name so the existing row is replaced
+        match r.action {
+            ImportAction::Skip => {}
+            ImportAction::Overwrite => {
+                plan.workspaces.retain(|w| w.name != r.id);
+                workspace.name = r.id;
+                plan.workspaces.push(workspace);
+            }
+            _ => {
+                workspace.name = r.name;
+                plan.workspaces.push(workspace);
+            }
+        }
+    }
+
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(id: &str, name: &str) -> ConfigProfile {
+        ConfigProfile {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: None,
+            working_directory: "/tmp".to_string(),
+            claude_args: Vec::new(),
+            env_vars: HashMap::new(),
+            is_default: false,
+            permission_policy_id: None,
+        }
+    }
+
+    fn workspace(name: &str) -> BundledWorkspace {
+        BundledWorkspace { name: name.to_string(), terminals: Vec::new() }
+    }
+
+    fn plan(
+        profiles: Vec<ConfigProfile>,
+        workspaces: Vec<BundledWorkspace>,
+        existing: &[ConfigProfile],
+        strategy: ConflictStrategy,
+    ) -> ImportPlan {
+        let bundle = ProfileBundle::new(profiles, Vec::new(), workspaces, Vec::new());
+        plan_import(bundle, existing, &[], &["Main".to_string()], &[], strategy)
+    }
+
+    fn planned(plan: &ImportPlan) -> (Vec<(&str, &str)>, Vec<&str>, Vec<ImportAction>) {
+        (
+            plan.profiles.iter().map(|p| (p.id.as_str(), p.name.as_str())).collect(),
+            plan.workspaces.iter().map(|w| w.name.as_str()).collect(),
+            plan.items.iter().map(|i| i.action.clone()).collect(),
+        )
+    }
+
+    #[test]
+    fn skips_entries_clashing_with_local_ones() {
+        let existing = [profile("p1", "Dev")];
+        let plan = plan(
+            vec![profile("p1", "Other"), profile("p2", "dev"), profile("p3", "New")],
+            vec![workspace("main")],
+            &existing,
+            ConflictStrategy::Skip,
+        );
+        let (profiles, workspaces, actions) = planned(&plan);
+        assert_eq!(profiles, vec![("p3", "New")]);
+        assert!(workspaces.is_empty());
+        assert_eq!(
+            actions,
+            vec![ImportAction::Skip, ImportAction::Skip, ImportAction::Create, ImportAction::Skip]
+        );
+        assert_eq!(plan.items[1].conflict.as_deref(), Some("name"));
+    }
+
+    #[test]
+    fn renames_entries_clashing_with_local_ones() {
+        let existing = [profile("p1", "Dev")];
+        let plan = plan(
+            vec![profile("p1", "Other"), profile("p2", "Dev")],
+            vec![workspace("Main")],
+            &existing,
+            ConflictStrategy::Rename,
+        );
+        let (profiles, workspaces, _) = planned(&plan);
+        assert_ne!(profiles[0].0, "p1");
+        assert_eq!(profiles[0].1, "Other");
+        assert_eq!(profiles[1], ("p2", "Dev (imported)"));
+        assert_eq!(workspaces, vec!["Main (imported)"]);
+    }
+
+    #[test]
+    fn overwrites_entries_clashing_with_local_ones() {
+        let existing = [profile("p1", "Dev"), profile("p2", "Ops")];
+        let plan = plan(
+            vec![profile("p1", "Dev"), profile("p9", "ops")],
+            vec![workspace("MAIN")],
+            &existing,
+            ConflictStrategy::Overwrite,
+        );
+        let (profiles, workspaces, actions) = planned(&plan);
+        // A name clash takes over the local entry rather than adding a duplicate
+        assert_eq!(profiles, vec![("p1", "Dev"), ("p2", "ops")]);
+        assert_eq!(workspaces, vec!["Main"]);
+        assert!(actions.iter().all(|a| *a == ImportAction::Overwrite));
+    }
+
+    #[test]
+    fn applies_the_conflict_rule_to_duplicates_within_a_bundle() {
+        let bundled = || vec![profile("a", "Shared"), profile("b", "shared")];
+        let workspaces = || vec![workspace("Team"), workspace("team")];
+
+        let skipped = plan(bundled(), workspaces(), &[], ConflictStrategy::Skip);
+        let (profiles, workspace_names, _) = planned(&skipped);
+        assert_eq!(profiles, vec![("a", "Shared")]);
+        assert_eq!(workspace_names, vec!["Team"]);
+
+        let renamed = plan(bundled(), workspaces(), &[], ConflictStrategy::Rename);
+        let (profiles, workspace_names, _) = planned(&renamed);
+        assert_eq!(profiles, vec![("a", "Shared"), ("b", "shared (imported)")]);
+        assert_eq!(workspace_names, vec!["Team", "team (imported)"]);
+
+        // The later entry replaces the earlier one instead of sitting beside it
+        let overwritten = plan(bundled(), workspaces(), &[], ConflictStrategy::Overwrite);
+        let (profiles, workspace_names, actions) = planned(&overwritten);
+        assert_eq!(profiles, vec![("a", "shared")]);
+        assert_eq!(workspace_names, vec!["Team"]);
+        assert_eq!(
+            actions,
+            vec![ImportAction::Create, ImportAction::Overwrite, ImportAction::Create, ImportAction::Overwrite]
+        );
+    }
+
+    #[test]
+    fn repeated_ids_within_a_bundle_keep_one_entry() {
+        let plan = plan(
+            vec![profile("a", "First"), profile("a", "Second")],
+            Vec::new(),
+            &[],
+            ConflictStrategy::Overwrite,
+        );
+        let (profiles, _, _) = planned(&plan);
+        assert_eq!(profiles, vec![("a", "Second")]);
+    }
+}
@@ -0,0 +1,1150 @@
+use crate::git;
+use crate::repo::RepoInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+/// Untracked files larger than this are listed without their content (1 MB)
+const MAX_UNTRACKED_FILE_SIZE: u64 = 1024 * 1024;
+
+/// How far into a file git looks for a NUL byte when deciding it is binary
+const BINARY_SNIFF_LEN: usize = 8000;
+
+/// Pins how diffs are split into hunks, whatever the user's diff config, so
+/// the hunks shown match those `toggle_hunk` looks up. libgit2 diffs with
+/// Myers and is given the indent heuristic too.
+const HUNK_SPLIT_ARGS: [&str; 2] = ["--diff-algorithm=myers", "--indent-heuristic"];
+
+/// What happened to a file in the index or the worktree, from git's status
+/// letters
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Modified,
+    TypeChanged,
+    Added,
+    Deleted,
+    Renamed,
+    Copied,
+    Unmerged,
+    Untracked,
+}
+
+impl ChangeKind {
+    fn from_code(code: char) -> Option<Self> {
+        match code {
+            'M' => Some(ChangeKind::Modified),
+            'T' => Some(ChangeKind::TypeChanged),
+            'A' => Some(ChangeKind::Added),
+            'D' => Some(ChangeKind::Deleted),
+            'R' => Some(ChangeKind::Renamed),
+            'C' => Some(ChangeKind::Copied),
+            'U' => Some(ChangeKind::Unmerged),
+            '?' => Some(ChangeKind::Untracked),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffLineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    pub content: String,
+    pub old_line: Option<u32>,
+    pub new_line: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiffHunk {
+    /// The `@@ -a,b +c,d @@ context` line
+    pub header: String,
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    /// Whether the hunk is in the index (HEAD to index) rather than the
+    /// worktree (index to worktree)
+    pub staged: bool,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct FileChange {
+    pub path: String,
+    /// Summary for grouping: new, modified, deleted, renamed, copied,
+    /// typechanged, conflicted or untracked
+    pub status: String,
+    pub index_status: Option<ChangeKind>,
+    pub worktree_status: Option<ChangeKind>,
+    /// Source path of a rename or copy
+    pub original_path: Option<String>,
+    pub added: u32,
+    pub removed: u32,
+    pub binary: bool,
+    pub hunks: Vec<DiffHunk>,
+    /// Some hunk lines were left out because of `max_lines_per_file`, or the
+    /// file was too large to show
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct FileChangesResult {
+    pub terminal_id: String,
+    pub working_directory: String,
+    pub changes: Vec<FileChange>,
+    pub is_git_repo: bool,
+    pub branch: Option<String>,
+    /// Upstream, stash and last commit details; None when git had to be
+    /// run instead of reading the repository directly
+    pub repo: Option<RepoInfo>,
+    pub error: Option<String>,
+}
+
+impl FileChangesResult {
+    /// Keeps at most `max` hunk lines per file, like `max_lines_per_file`
+    pub fn cap_lines(&mut self, max: usize) {
+        for change in &mut self.changes {
+            change.truncated |= cap_lines(&mut change.hunks, max);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChangesOptions {
+    /// Include hunks; counts and binary detection are always filled in.
+    /// Off by default, as hunks can be large and most views only list files.
+    pub include_diff: bool,
+    /// Cap on hunk lines returned per file
+    pub max_lines_per_file: Option<usize>,
+    pub context_lines: u32,
+}
+
+impl Default for ChangesOptions {
+    fn default() -> Self {
+        ChangesOptions {
+            include_diff: false,
+            max_lines_per_file: None,
+            context_lines: 3,
+        }
+    }
+}
+
+/// Diff of one file from a multi-file patch
+#[derive(Debug, Default)]
+struct FileDiff {
+    added: u32,
+    removed: u32,
+    binary: bool,
+    hunks: Vec<DiffHunk>,
+    truncated: bool,
+}
+
+/// The change set of a terminal's working directory, with git failures
+/// reported in `error` rather than failing the call. The repository is read
+/// in-process; git only runs for what libgit2 can't handle.
+pub fn terminal_result(terminal_id: String, working_directory: String, options: &ChangesOptions) -> FileChangesResult {
+    let dir = Path::new(&working_directory);
+    let native = match crate::repo::open(dir) {
+        Ok(Some(repo)) => Some(repo),
+        Ok(None) => None,
+        Err(_) if git::toplevel(dir).is_some() => {
+            let branch = git::current_branch(dir).unwrap_or_else(|| "HEAD".to_string());
+            let (changes, error) = split(collect(dir, options));
+            return FileChangesResult {
+                terminal_id,
+                working_directory,
+                changes,
+                is_git_repo: true,
+                branch: Some(branch),
+                repo: None,
+                error,
+            };
+        }
+        Err(_) => None,
+    };
+    let Some(mut repo) = native else {
+        return FileChangesResult {
+            terminal_id,
+            working_directory,
+            changes: vec![],
+            is_git_repo: false,
+            branch: None,
+            repo: None,
+            error: None,
+        };
+    };
+
+    let info = crate::repo::info(&mut repo);
+    let collected = if crate::repo::has_external_filters(&repo) {
+        collect(dir, options)
+    } else {
+        collect_native(&repo, options)
+    };
+    let (changes, error) = split(collected);
+    FileChangesResult {
+        terminal_id,
+        working_directory,
+        changes,
+        is_git_repo: true,
+        branch: Some(info.branch.clone().unwrap_or_else(|| "HEAD".to_string())),
+        repo: Some(info),
+        error,
+    }
+}
+
+fn split(collected: Result<Vec<FileChange>, String>) -> (Vec<FileChange>, Option<String>) {
+    match collected {
+        Ok(changes) => (changes, None),
+        Err(e) => (vec![], Some(e)),
+    }
+}
+
+/// Collects the changed files under `dir` with their diffs by running git.
+/// Paths are relative to the repository root.
+pub fn collect(dir: &Path, options: &ChangesOptions) -> Result<Vec<FileChange>, String> {
+    let status = git::run(
+        dir,
+        &["--no-optional-locks", "-c", "core.quotePath=false", "status", "--porcelain=v2", "-z", "--untracked-files=all"],
+    )?;
+    let mut changes = parse_status(&status);
+
+    let context = format!("-U{}", options.context_lines.min(100));
+    let diff_args = |staged: bool| {
+        let mut args = vec!["-c", "core.quotePath=false", "diff", "--no-color", "--no-ext-diff", "-M", context.as_str()];
+        args.extend(HUNK_SPLIT_ARGS);
+        if staged {
+            args.push("--cached");
+        }
+        args
+    };
+    // An unborn branch has nothing to compare the index against
+    let has_head = git::run(dir, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok();
+    let staged = if has_head {
+        parse_patch(&git::run(dir, &diff_args(true))?, true)
+    } else {
+        HashMap::new()
+    };
+    let unstaged = parse_patch(&git::run(dir, &diff_args(false))?, false);
+
+    let root = git::toplevel(dir).unwrap_or_else(|| dir.to_string_lossy().to_string());
+    add_diffs(&mut changes, staged, unstaged, Path::new(&root), options);
+    Ok(changes)
+}
+
+/// Like `collect`, reading status and diffs with libgit2
+pub fn collect_native(repo: &git2::Repository, options: &ChangesOptions) -> Result<Vec<FileChange>, String> {
+    let root = repo.workdir().ok_or("The repository has no working tree")?;
+    let mut status_options = git2::StatusOptions::new();
+    status_options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .renames_head_to_index(true);
+    let statuses = repo.statuses(Some(&mut status_options)).map_err(|e| e.message().to_string())?;
+
+    let mut changes = Vec::new();
+    for entry in statuses.iter() {
+        let status = entry.status();
+        let (index_status, worktree_status) = if status.is_conflicted() {
+            (Some(ChangeKind::Unmerged), Some(ChangeKind::Unmerged))
+        } else {
+            (index_kind(status), worktree_kind(status))
+        };
+        if index_status.is_none() && worktree_status.is_none() {
+            continue;
+        }
+        let rename = entry.head_to_index().filter(|_| status.is_index_renamed());
+        let (path, original_path) = match rename {
+            Some(delta) => (delta_path(&delta.new_file()), Some(delta_path(&delta.old_file()))),
+            None => (String::from_utf8_lossy(entry.path_bytes()).to_string(), None),
+        };
+        changes.push(FileChange {
+            path,
+            status: summary(index_status, worktree_status).to_string(),
+            index_status,
+            worktree_status,
+            original_path,
+            added: 0,
+            removed: 0,
+            binary: false,
+            hunks: vec![],
+            truncated: false,
+        });
+    }
+    // git lists untracked files after the tracked ones
+    changes.sort_by_key(|c| c.worktree_status == Some(ChangeKind::Untracked));
+
+    let diff_options = || {
+        let mut o = git2::DiffOptions::new();
+        // As `HUNK_SPLIT_ARGS`, so `toggle_hunk` finds the hunks shown
+        o.context_lines(options.context_lines.min(100)).indent_heuristic(true);
+        o
+    };
+    let head_tree = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+    let mut staged = repo
+        .diff_tree_to_index(head_tree.as_ref(), None, Some(&mut diff_options()))
+        .map_err(|e| e.message().to_string())?;
+    // Pairs deletes with adds like `git diff -M`
+    staged.find_similar(None).map_err(|e| e.message().to_string())?;
+    let unstaged = repo
+        .diff_index_to_workdir(None, Some(&mut diff_options()))
+        .map_err(|e| e.message().to_string())?;
+    let staged = patch_diffs(&staged, true)?;
+    let unstaged = patch_diffs(&unstaged, false)?;
+
+    add_diffs(&mut changes, staged, unstaged, root, options);
+    Ok(changes)
+}
+
+fn index_kind(status: git2::Status) -> Option<ChangeKind> {
+    if status.is_index_new() {
+        Some(ChangeKind::Added)
+    } else if status.is_index_modified() {
+        Some(ChangeKind::Modified)
+    } else if status.is_index_deleted() {
+        Some(ChangeKind::Deleted)
+    } else if status.is_index_renamed() {
+        Some(ChangeKind::Renamed)
+    } else if status.is_index_typechange() {
+        Some(ChangeKind::TypeChanged)
+    } else {
+        None
+    }
+}
+
+fn worktree_kind(status: git2::Status) -> Option<ChangeKind> {
+    if status.is_wt_new() {
+        Some(ChangeKind::Untracked)
+    } else if status.is_wt_modified() {
+        Some(ChangeKind::Modified)
+    } else if status.is_wt_deleted() {
+        Some(ChangeKind::Deleted)
+    } else if status.is_wt_renamed() {
+        Some(ChangeKind::Renamed)
+    } else if status.is_wt_typechange() {
+        Some(ChangeKind::TypeChanged)
+    } else {
+        None
+    }
+}
+
+fn delta_path(file: &git2::DiffFile) -> String {
+    file.path().map(|p| p.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+/// Per-file diffs of a libgit2 diff, keyed by the file's current path
+fn patch_diffs(diff: &git2::Diff, staged: bool) -> Result<HashMap<String, FileDiff>, String> {
+    let mut files = HashMap::new();
+    for (i, delta) in diff.deltas().enumerate() {
+        let path = match delta.new_file().path() {
+            Some(_) => delta_path(&delta.new_file()),
+            None => delta_path(&delta.old_file()),
+        };
+        let mut file = FileDiff::default();
+        if let Some(patch) = git2::Patch::from_diff(diff, i).map_err(|e| e.message().to_string())? {
+            // Only known once the content has been loaded
+            file.binary = patch.delta().flags().is_binary();
+            for h in 0..patch.num_hunks() {
+                let (hunk, line_count) = patch.hunk(h).map_err(|e| e.message().to_string())?;
+                let mut out = DiffHunk {
+                    header: String::from_utf8_lossy(hunk.header()).trim_end().to_string(),
+                    old_start: hunk.old_start(),
+                    old_lines: hunk.old_lines(),
+                    new_start: hunk.new_start(),
+                    new_lines: hunk.new_lines(),
+                    staged,
+                    lines: Vec::with_capacity(line_count),
+                };
+                for l in 0..line_count {
+                    let line = patch.line_in_hunk(h, l).map_err(|e| e.message().to_string())?;
+                    let kind = match line.origin() {
+                        '+' => DiffLineKind::Added,
+                        '-' => DiffLineKind::Removed,
+                        ' ' => DiffLineKind::Context,
+                        // End-of-file newline markers
+                        _ => continue,
+                    };
+                    match kind {
+                        DiffLineKind::Added => file.added += 1,
+                        DiffLineKind::Removed => file.removed += 1,
+                        DiffLineKind::Context => {}
+                    }
+                    let content = String::from_utf8_lossy(line.content());
+                    let content = content.strip_suffix('\n').unwrap_or(&content);
+                    out.lines.push(DiffLine {
+                        kind,
+                        content: content.strip_suffix('\r').unwrap_or(content).to_string(),
+                        old_line: line.old_lineno(),
+                        new_line: line.new_lineno(),
+                    });
+                }
+                file.hunks.push(out);
+            }
+        }
+        files.insert(path, file);
+    }
+    Ok(files)
+}
+
+/// Fills in each change's counts and hunks from the staged and unstaged
+/// diffs, reading untracked files from under `root`
+fn add_diffs(
+    changes: &mut [FileChange],
+    mut staged: HashMap<String, FileDiff>,
+    mut unstaged: HashMap<String, FileDiff>,
+    root: &Path,
+    options: &ChangesOptions,
+) {
+    for change in changes {
+        let mut diffs: Vec<FileDiff> = [staged.remove(&change.path), unstaged.remove(&change.path)]
+            .into_iter()
+            .flatten()
+            .collect();
+        if change.worktree_status == Some(ChangeKind::Untracked) {
+            diffs.push(untracked_diff(&root.join(&change.path)));
+        }
+        for diff in diffs {
+            change.added += diff.added;
+            change.removed += diff.removed;
+            change.binary |= diff.binary;
+            change.truncated |= diff.truncated;
+            if options.include_diff {
+                change.hunks.extend(diff.hunks);
+            }
+        }
+        if let Some(max) = options.max_lines_per_file {
+            change.truncated |= cap_lines(&mut change.hunks, max);
+        }
+    }
+}
+
+/// Changes between two commits or trees, e.g. checkpoints. Only
+/// `worktree_status` is set, and hunks are never marked staged.
+pub fn diff_trees(dir: &Path, from: &str, to: &str, options: &ChangesOptions) -> Result<Vec<FileChange>, String> {
+    let names = git::run(
+        dir,
+        &["-c", "core.quotePath=false", "diff", "--no-ext-diff", "--name-status", "-z", "-M", from, to],
+    )?;
+    let mut changes = Vec::new();
+    let mut records = names.split('\0').filter(|r| !r.is_empty());
+    while let Some(code) = records.next() {
+        let kind = code.chars().next().and_then(ChangeKind::from_code);
+        // Renames and copies list the source path first
+        let original_path = match kind {
+            Some(ChangeKind::Renamed | ChangeKind::Copied) => records.next().map(str::to_string),
+            _ => None,
+        };
+        let Some(path) = records.next() else {
+            break;
+        };
+        changes.push(FileChange {
+            path: path.to_string(),
+            status: summary(None, kind).to_string(),
+            index_status: None,
+            worktree_status: kind,
+            original_path,
+            added: 0,
+            removed: 0,
+            binary: false,
+            hunks: vec![],
+            truncated: false,
+        });
+    }
+
+    let context = format!("-U{}", options.context_lines.min(100));
+    let patch = git::run(
+        dir,
+        &["-c", "core.quotePath=false", "diff", "--no-color", "--no-ext-diff", "-M", &context, from, to],
+    )?;
+    let mut diffs = parse_patch(&patch, false);
+    for change in &mut changes {
+        if let Some(diff) = diffs.remove(&change.path) {
+            change.added = diff.added;
+            change.removed = diff.removed;
+            change.binary = diff.binary;
+            if options.include_diff {
+                change.hunks = diff.hunks;
+            }
+        }
+        if let Some(max) = options.max_lines_per_file {
+            change.truncated |= cap_lines(&mut change.hunks, max);
+        }
+    }
+    Ok(changes)
+}
+
+/// Parses `git status --porcelain=v2 -z` output
+fn parse_status(output: &str) -> Vec<FileChange> {
+    let mut changes = Vec::new();
+    let mut records = output.split('\0').filter(|r| !r.is_empty());
+    while let Some(record) = records.next() {
+        let (path, codes, original_path) = match record.split_at_checked(2) {
+            Some(("1 ", rest)) => match fields(rest, 7) {
+                Some((f, path)) => (path, f[0], None),
+                None => continue,
+            },
+            // Renames and copies carry a score field, then the source path
+            // as a separate record
+            Some(("2 ", rest)) => match fields(rest, 8) {
+                Some((f, path)) => (path, f[0], records.next().map(str::to_string)),
+                None => continue,
+            },
+            Some(("u ", rest)) => match fields(rest, 9) {
+                Some((f, path)) => (path, f[0], None),
+                None => continue,
+            },
+            Some(("? ", path)) => (path, "??", None),
+            _ => continue,
+        };
+        let mut chars = codes.chars();
+        let unmerged = record.starts_with('u');
+        let (index_status, worktree_status) = if unmerged {
+            (Some(ChangeKind::Unmerged), Some(ChangeKind::Unmerged))
+        } else if codes == "??" {
+            (None, Some(ChangeKind::Untracked))
+        } else {
+            (
+                chars.next().and_then(ChangeKind::from_code),
+                chars.next().and_then(ChangeKind::from_code),
+            )
+        };
+        changes.push(FileChange {
+            path: path.to_string(),
+            status: summary(index_status, worktree_status).to_string(),
+            index_status,
+            worktree_status,
+            original_path,
+            added: 0,
+            removed: 0,
+            binary: false,
+            hunks: vec![],
+            truncated: false,
+        });
+    }
+    changes
+}
+
+/// Splits off `count` space-separated fields; the rest is the path, which
+/// may itself contain spaces
+fn fields(rest: &str, count: usize) -> Option<(Vec<&str>, &str)> {
+    let mut parts = rest.splitn(count + 1, ' ');
+    let fields: Vec<&str> = parts.by_ref().take(count).collect();
+    let path = parts.next()?;
+    (fields.len() == count).then_some((fields, path))
+}
+
+fn summary(index: Option<ChangeKind>, worktree: Option<ChangeKind>) -> &'static str {
+    let kinds = [index, worktree];
+    let has = |kind: ChangeKind| kinds.contains(&Some(kind));
+    if has(ChangeKind::Unmerged) {
+        "conflicted"
+    } else if has(ChangeKind::Untracked) {
+        "untracked"
+    } else if has(ChangeKind::Renamed) {
+        "renamed"
+    } else if has(ChangeKind::Copied) {
+        "copied"
+    } else if has(ChangeKind::Deleted) {
+        "deleted"
+    } else if has(ChangeKind::Added) {
+        "new"
+    } else if has(ChangeKind::TypeChanged) {
+        "typechanged"
+    } else {
+        "modified"
+    }
+}
+
+/// Parses a multi-file unified diff into per-file diffs keyed by the file's
+/// current path
+fn parse_patch(patch: &str, staged: bool) -> HashMap<String, FileDiff> {
+    let mut files = HashMap::new();
+    let mut current: Option<(String, FileDiff)> = None;
+    let mut old_line = 0;
+    let mut new_line = 0;
+
+    for line in patch.lines() {
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            if let Some((path, diff)) = current.take() {
+                files.insert(path, diff);
+            }
+            current = Some((header_path(header).unwrap_or_default(), FileDiff::default()));
+            continue;
+        }
+        let Some((path, diff)) = current.as_mut() else {
+            continue;
+        };
+
+        if let Some(hunk) = diff.hunks.last_mut().filter(|_| !line.starts_with("@@")) {
+            let (kind, content) = match line.split_at_checked(1) {
+                Some(("+", rest)) => (DiffLineKind::Added, rest),
+                Some(("-", rest)) => (DiffLineKind::Removed, rest),
+                Some((" ", rest)) => (DiffLineKind::Context, rest),
+                // "\ No newline at end of file"
+                _ => continue,
+            };
+            let (old, new) = match kind {
+                DiffLineKind::Added => {
+                    diff.added += 1;
+                    new_line += 1;
+                    (None, Some(new_line - 1))
+                }
+                DiffLineKind::Removed => {
+                    diff.removed += 1;
+                    old_line += 1;
+                    (Some(old_line - 1), None)
+                }
+                DiffLineKind::Context => {
+                    old_line += 1;
+                    new_line += 1;
+                    (Some(old_line - 1), Some(new_line - 1))
+                }
+            };
+            hunk.lines.push(DiffLine { kind, content: content.to_string(), old_line: old, new_line: new });
+        } else if let Some(hunk) = parse_hunk_header(line, staged) {
+            old_line = hunk.old_start;
+            new_line = hunk.new_start;
+            diff.hunks.push(hunk);
+        } else if let Some(p) = line.strip_prefix("+++ ").and_then(|p| side_path(p, "b/")) {
+            *path = p;
+        } else if let Some(p) = line.strip_prefix("rename to ") {
+            *path = unquote(p);
+        } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+            diff.binary = true;
+        }
+    }
+    if let Some((path, diff)) = current {
+        files.insert(path, diff);
+    }
+    files
+}
+
+/// Path from a `diff --git a/<path> b/<path>` header. Only unambiguous when
+/// both sides are the same, which holds for everything but renames; those
+/// are corrected by the `rename to` line.
+fn header_path(header: &str) -> Option<String> {
+    let (a, b) = if header.starts_with('"') {
+        // Both sides are quoted alike, so the first ends at its closing quote
+        let mut escaped = false;
+        let end = header.char_indices().skip(1).find_map(|(i, c)| match c {
+            _ if escaped => {
+                escaped = false;
+                None
+            }
+            '\\' => {
+                escaped = true;
+                None
+            }
+            '"' => Some(i + 1),
+            _ => None,
+        })?;
+        (&header[..end], header.get(end + 1..)?)
+    } else {
+        let half = header.len().checked_sub(1)? / 2;
+        (header.get(..half)?, header.get(half + 1..)?)
+    };
+    let (a, b) = (side_path(a, "a/")?, side_path(b, "b/")?);
+    (a == b).then_some(a)
+}
+
+/// One side of a diff header or `---`/`+++` line, without its `a/` or `b/`
+fn side_path(side: &str, prefix: &str) -> Option<String> {
+    unquote(side).strip_prefix(prefix).map(str::to_string)
+}
+
+/// Undoes git's C-style quoting of a path, which it applies to paths with
+/// `"`, `\`, control characters or, without `core.quotePath=false`, any
+/// non-ASCII byte. Drops the tab git appends to unquoted paths with spaces.
+fn unquote(path: &str) -> String {
+    let path = path.trim_end_matches('\t');
+    let Some(quoted) = path.strip_prefix('"').and_then(|p| p.strip_suffix('"')) else {
+        return path.to_string();
+    };
+    let mut bytes = Vec::with_capacity(quoted.len());
+    let mut rest = quoted.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        let Some((&escape, tail)) = rest.split_first() else {
+            break;
+        };
+        rest = tail;
+        bytes.push(match escape {
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'v' => 0x0b,
+            b'0'..=b'3' if rest.len() >= 2 && rest[..2].iter().all(|b| (b'0'..=b'7').contains(b)) => {
+                let value = (escape - b'0') * 64 + (rest[0] - b'0') * 8 + (rest[1] - b'0');
+                rest = &rest[2..];
+                value
+            }
+            other => other,
+        });
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn parse_hunk_header(line: &str, staged: bool) -> Option<DiffHunk> {
+    let rest = line.strip_prefix("@@ -")?;
+    let (ranges, _) = rest.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let range = |r: &str| -> Option<(u32, u32)> {
+        match r.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((r.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_lines) = range(old)?;
+    let (new_start, new_lines) = range(new)?;
+    Some(DiffHunk {
+        header: line.to_string(),
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        staged,
+        lines: vec![],
+    })
+}
+
+/// An untracked file shown as a single all-added hunk
+fn untracked_diff(path: &Path) -> FileDiff {
+    let mut diff = FileDiff::default();
+    // Symlinks aren't followed; git tracks the link, not its target
+    let Ok(meta) = std::fs::symlink_metadata(path) else {
+        return diff;
+    };
+    if !meta.is_file() {
+        return diff;
+    }
+    if meta.len() > MAX_UNTRACKED_FILE_SIZE {
+        diff.truncated = true;
+        return diff;
+    }
+    let mut bytes = Vec::new();
+    if std::fs::File::open(path).and_then(|mut f| f.read_to_end(&mut bytes)).is_err() {
+        return diff;
+    }
+    if bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0) {
+        diff.binary = true;
+        return diff;
+    }
+
+    let text = String::from_utf8_lossy(&bytes);
+    let lines: Vec<DiffLine> = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| DiffLine {
+            kind: DiffLineKind::Added,
+            content: line.to_string(),
+            old_line: None,
+            new_line: Some(i as u32 + 1),
+        })
+        .collect();
+    diff.added = lines.len() as u32;
+    if !lines.is_empty() {
+        diff.hunks.push(DiffHunk {
+            header: format!("@@ -0,0 +1,{} @@", lines.len()),
+            old_start: 0,
+            old_lines: 0,
+            new_start: 1,
+            new_lines: lines.len() as u32,
+            staged: false,
+            lines,
+        });
+    }
+    diff
+}
+
+/// Keeps at most `max` lines across the hunks, dropping hunks that no
+/// longer have any. Returns whether anything was cut.
+fn cap_lines(hunks: &mut Vec<DiffHunk>, max: usize) -> bool {
+    let mut remaining = max;
+    let mut cut = false;
+    hunks.retain_mut(|hunk| {
+        if remaining == 0 {
+            cut = true;
+            return false;
+        }
+        if hunk.lines.len() > remaining {
+            hunk.lines.truncate(remaining);
+            cut = true;
+        }
+        remaining -= hunk.lines.len();
+        true
+    });
+    cut
+}
+
+/// Stash message prefix marking snapshots taken before a discard
+const SNAPSHOT_PREFIX: &str = "claude-terminal discard";
+
+/// Changes saved to the stash before they were discarded
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiscardSnapshot {
+    /// Commit id of the stash entry; stable while `stash@{n}` indexes shift
+    pub commit: String,
+    pub message: String,
+    pub created_at: String,
+}
+
+/// Identifies a hunk from `collect` so it can be staged or unstaged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HunkRef {
+    pub path: String,
+    /// The hunk's `@@` line, as returned by `collect` with the same
+    /// `context_lines`
+    pub header: String,
+    pub staged: bool,
+}
+
+fn check_paths(paths: &[String]) -> Result<Vec<String>, String> {
+    if paths.is_empty() {
+        return Err("No files given".to_string());
+    }
+    paths
+        .iter()
+        .map(|p| {
+            if p.is_empty() || Path::new(p).is_absolute() || p.split(['/', '\\']).any(|c| c == "..") {
+                Err(format!("Invalid path: {}", p))
+            } else {
+                Ok(git::literal_pathspec(p))
+            }
+        })
+        .collect()
+}
+
+fn with_pathspecs<'a>(args: &[&'a str], pathspecs: &'a [String]) -> Vec<&'a str> {
+    let mut args = args.to_vec();
+    args.push("--");
+    args.extend(pathspecs.iter().map(String::as_str));
+    args
+}
+
+pub fn stage_paths(dir: &Path, paths: &[String]) -> Result<(), String> {
+    let pathspecs = check_paths(paths)?;
+    git::run(dir, &with_pathspecs(&["add", "-A"], &pathspecs)).map(|_| ())
+}
+
+pub fn unstage_paths(dir: &Path, paths: &[String]) -> Result<(), String> {
+    let pathspecs = check_paths(paths)?;
+    if git::run(dir, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok() {
+        git::run(dir, &with_pathspecs(&["reset", "-q"], &pathspecs)).map(|_| ())
+    } else {
+        // Nothing to reset to before the first commit
+        git::run(dir, &with_pathspecs(&["rm", "-r", "-q", "--cached"], &pathspecs)).map(|_| ())
+    }
+}
+
+/// Stages an unstaged hunk, or unstages a staged one
+pub fn toggle_hunk(dir: &Path, hunk: &HunkRef, context_lines: u32) -> Result<(), String> {
+    let pathspecs = check_paths(std::slice::from_ref(&hunk.path))?;
+    let context = format!("-U{}", context_lines.min(100));
+    let mut args = vec!["diff", "--no-color", "--no-ext-diff", context.as_str()];
+    args.extend(HUNK_SPLIT_ARGS);
+    if hunk.staged {
+        args.push("--cached");
+    }
+    let patch = git::run(dir, &with_pathspecs(&args, &pathspecs))?;
+    if patch.trim().is_empty() {
+        // Untracked files have no diff against the index; stage them whole
+        if !hunk.staged {
+            return stage_paths(dir, std::slice::from_ref(&hunk.path));
+        }
+        return Err("The file has no staged changes".to_string());
+    }
+
+    let patch = single_hunk_patch(&patch, &hunk.header)
+        .ok_or("The hunk has changed since it was shown; refresh and try again")?;
+    let mut args = vec!["apply", "--cached", "--whitespace=nowarn"];
+    if hunk.staged {
+        args.push("--reverse");
+    }
+    if context_lines == 0 {
+        args.push("--unidiff-zero");
+    }
+    args.push("-");
+    // From a subdirectory, git apply silently skips paths outside it
+    let root = git::toplevel(dir).ok_or("Not a git repository")?;
+    git::run_with_input(Path::new(&root), &args, &patch).map(|_| ())
+}
+
+/// Cuts a single-file patch down to its file header and one hunk
+fn single_hunk_patch(patch: &str, header: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut in_header = true;
+    let mut in_hunk = false;
+    let mut found = false;
+    for line in patch.split_inclusive('\n') {
+        if line.starts_with("@@") {
+            in_header = false;
+            in_hunk = same_hunk(line.trim_end_matches(['\n', '\r']), header);
+            found |= in_hunk;
+        }
+        if in_header || in_hunk {
+            out.push_str(line);
+        }
+    }
+    found.then_some(out)
+}
+
+/// Whether two `@@` lines cover the same ranges. The text after the ranges
+/// is ignored since libgit2 and git pick function context differently.
+fn same_hunk(a: &str, b: &str) -> bool {
+    let ranges = |line: &str| parse_hunk_header(line, false).map(|h| (h.old_start, h.old_lines, h.new_start, h.new_lines));
+    ranges(a).is_some_and(|r| Some(r) == ranges(b))
+}
+
+/// Saves the files' changes, including untracked files, to the stash and
+/// resets them, so a discard can be undone with `restore_snapshot`
+pub fn discard_paths(dir: &Path, paths: &[String]) -> Result<DiscardSnapshot, String> {
+    let pathspecs = check_paths(paths)?;
+    let mut listed: Vec<&str> = paths.iter().take(5).map(String::as_str).collect();
+    if paths.len() > 5 {
+        listed.push("...");
+    }
+    let message = format!("{}: {}", SNAPSHOT_PREFIX, listed.join(", "));
+    // The stash can remove the directory git was started in
+    let root = git::toplevel(dir).ok_or("Not a git repository")?;
+    let dir = Path::new(&root);
+    let before = git::run_trimmed(dir, &["rev-parse", "--verify", "--quiet", "refs/stash"]).ok();
+    git::run(dir, &with_pathspecs(&["stash", "push", "--include-untracked", "-q", "-m", &message], &pathspecs))?;
+    let commit = git::run_trimmed(dir, &["rev-parse", "--verify", "--quiet", "refs/stash"])
+        .ok()
+        .filter(|c| Some(c) != before.as_ref())
+        .ok_or("There were no changes to discard")?;
+    list_snapshots(dir)?
+        .into_iter()
+        .find(|s| s.commit == commit)
+        .ok_or_else(|| "Failed to record the discard snapshot".to_string())
+}
+
+/// Discard snapshots still in the stash, newest first
+pub fn list_snapshots(dir: &Path) -> Result<Vec<DiscardSnapshot>, String> {
+    let output = git::run(dir, &["stash", "list", "--format=%H%x00%gs%x00%cI"])?;
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\0');
+            let commit = parts.next()?.to_string();
+            // The reflog subject reads "On <branch>: <message>"
+            let subject = parts.next()?;
+            let message = subject.split_once(": ").map_or(subject, |(_, m)| m).to_string();
+            let created_at = parts.next()?.to_string();
+            message
+                .starts_with(SNAPSHOT_PREFIX)
+                .then_some(DiscardSnapshot { commit, message, created_at })
+        })
+        .collect())
+}
+
+/// Applies a discard snapshot back onto the worktree and drops it
+pub fn restore_snapshot(dir: &Path, commit: &str) -> Result<(), String> {
+    if !list_snapshots(dir)?.iter().any(|s| s.commit == commit) {
+        return Err("Snapshot not found".to_string());
+    }
+    let stashes = git::run(dir, &["stash", "list", "--format=%H"])?;
+    let index = stashes.lines().position(|c| c == commit).ok_or("Snapshot not found")?;
+    let stash = format!("stash@{{{}}}", index);
+    // Restore staged changes as staged where possible
+    if git::run(dir, &["stash", "apply", "-q", "--index", &stash]).is_err() {
+        git::run(dir, &["stash", "apply", "-q", &stash])?;
+    }
+    git::run(dir, &["stash", "drop", "-q", &stash]).map(|_| ())
+}
+
+/// Commits what is staged
+pub fn commit(dir: &Path, message: &str) -> Result<String, String> {
+    if message.trim().is_empty() {
+        return Err("Commit message can't be empty".to_string());
+    }
+    git::run_with_input(dir, &["commit", "-q", "-F", "-"], message)?;
+    git::run_trimmed(dir, &["rev-parse", "HEAD"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_porcelain_status() {
+        let output = "1 .M N... 100644 100644 100644 7898192 7898192 a.txt\0\
+                      2 R. N... 100644 100644 100644 4cb29ea 4cb29ea R100 new name.txt\0old name.txt\0\
+                      u UU N... 100644 100644 100644 100644 1111111 2222222 3333333 both.txt\0\
+                      ? un tracked.txt\0";
+        let changes = parse_status(output);
+        let summary: Vec<_> = changes
+            .iter()
+            .map(|c| {
+                (c.path.as_str(), c.status.as_str(), c.index_status, c.worktree_status, c.original_path.as_deref())
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("a.txt", "modified", None, Some(ChangeKind::Modified), None),
+                ("new name.txt", "renamed", Some(ChangeKind::Renamed), None, Some("old name.txt")),
+                ("both.txt", "conflicted", Some(ChangeKind::Unmerged), Some(ChangeKind::Unmerged), None),
+                ("un tracked.txt", "untracked", None, Some(ChangeKind::Untracked), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_status_on_unborn_branch() {
+        let output = "1 A. N... 000000 100644 100644 0000000000000000000000000000000000000000 \
+                      587be6b4c3f93f93c489c0111bba5596147a26cb first file.txt\0";
+        let changes = parse_status(output);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "first file.txt");
+        assert_eq!(changes[0].status, "new");
+        assert_eq!(changes[0].index_status, Some(ChangeKind::Added));
+        assert_eq!(changes[0].worktree_status, None);
+    }
+
+    #[test]
+    fn parses_status_type_changes() {
+        let changes = parse_status("1 T. N... 100644 120000 120000 1111111 2222222 link\0");
+        assert_eq!(changes[0].status, "typechanged");
+        assert_eq!(changes[0].index_status, Some(ChangeKind::TypeChanged));
+    }
+
+    #[test]
+    fn parses_patch_with_rename_and_spaces() {
+        let patch = "\
+diff --git a/old name.txt b/new name.txt
+similarity index 71%
+rename from old name.txt
+rename to new name.txt
+index 4cb29ea..ddc897f 100644
+--- a/old name.txt\t
++++ b/new name.txt\t
+@@ -1,3 +1,3 @@
+ one
+-two
++TWO
+ three
+diff --git a/a.txt b/a.txt
+index 7898192..f04eb26 100644
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1,3 @@
+-a
++one
++2
++three
+\\ No newline at end of file
+diff --git a/image.png b/image.png
+index 1111111..2222222 100644
+Binary files a/image.png and b/image.png differ
+";
+        let files = parse_patch(patch, true);
+        let mut paths: Vec<&String> = files.keys().collect();
+        paths.sort();
+        assert_eq!(paths, ["a.txt", "image.png", "new name.txt"]);
+
+        let renamed = &files["new name.txt"];
+        assert_eq!((renamed.added, renamed.removed), (1, 1));
+        let hunk = &renamed.hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (1, 3, 1, 3));
+        assert!(hunk.staged);
+        let lines: Vec<(DiffLineKind, &str, Option<u32>, Option<u32>)> = hunk
+            .lines
+            .iter()
+            .map(|l| (l.kind, l.content.as_str(), l.old_line, l.new_line))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (DiffLineKind::Context, "one", Some(1), Some(1)),
+                (DiffLineKind::Removed, "two", Some(2), None),
+                (DiffLineKind::Added, "TWO", None, Some(2)),
+                (DiffLineKind::Context, "three", Some(3), Some(3)),
+            ]
+        );
+
+        let modified = &files["a.txt"];
+        assert_eq!((modified.added, modified.removed), (3, 1));
+        assert_eq!(modified.hunks[0].lines.len(), 4);
+        assert!(files["image.png"].binary);
+    }
+
+    #[test]
+    fn parses_pure_rename_without_hunks() {
+        let patch = "\
+diff --git a/old name.txt b/new name.txt
+similarity index 100%
+rename from old name.txt
+rename to new name.txt
+";
+        let files = parse_patch(patch, true);
+        let diff = &files["new name.txt"];
+        assert!(diff.hunks.is_empty());
+        assert_eq!((diff.added, diff.removed), (0, 0));
+    }
+
+    #[test]
+    fn reads_path_from_diff_header() {
+        assert_eq!(header_path("a/src/main.rs b/src/main.rs").as_deref(), Some("src/main.rs"));
+        assert_eq!(header_path("a/with space.txt b/with space.txt").as_deref(), Some("with space.txt"));
+        assert_eq!(header_path("\"a/quo\\\"te\" \"b/quo\\\"te\"").as_deref(), Some("quo\"te"));
+        assert_eq!(header_path("\"a/tab\\there\" \"b/tab\\there\"").as_deref(), Some("tab\there"));
+        // Renames are left to the `rename to` line
+        assert_eq!(header_path("a/old.txt b/new.txt"), None);
+        assert_eq!(header_path(""), None);
+    }
+
+    #[test]
+    fn unquotes_c_style_paths() {
+        assert_eq!(unquote("plain.txt"), "plain.txt");
+        assert_eq!(unquote("with space.txt\t"), "with space.txt");
+        assert_eq!(unquote(r#""quo\"te""#), "quo\"te");
+        assert_eq!(unquote(r#""back\\slash""#), "back\\slash");
+        assert_eq!(unquote(r#""tab\tand\nline""#), "tab\tand\nline");
+        assert_eq!(unquote(r#""caf\303\251.txt""#), "café.txt");
+    }
+
+    #[test]
+    fn parses_patch_with_quoted_paths() {
+        let patch = "\
+diff --git \"a/quo\\\"te\" \"b/quo\\\"te\"
+index 7898192..f04eb26 100644
+--- \"a/quo\\\"te\"
++++ \"b/quo\\\"te\"
+@@ -1 +1 @@
+-a
++b
+diff --git \"a/old\\\\name\" \"b/caf\\303\\251\"
+similarity index 50%
+rename from \"old\\\\name\"
+rename to \"caf\\303\\251\"
+index 4cb29ea..ddc897f 100644
+--- \"a/old\\\\name\"
++++ \"b/caf\\303\\251\"
+@@ -1 +1 @@
+-x
++y
+";
+        let files = parse_patch(patch, false);
+        let mut paths: Vec<&String> = files.keys().collect();
+        paths.sort();
+        assert_eq!(paths, ["caf\u{e9}", "quo\"te"]);
+        assert_eq!((files["quo\"te"].added, files["quo\"te"].removed), (1, 1));
+    }
+}
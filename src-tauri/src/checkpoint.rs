@@ -0,0 +1,403 @@
+use crate::git;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+
+/// Hidden ref namespace; checkpoints live at `<prefix>/<session>/<n>`
+const REF_PREFIX: &str = "refs/claudeterminal";
+
+/// Checkpoints kept per session. Older refs are deleted as new ones are
+/// made; checkpoint commits don't chain to each other, so git's garbage
+/// collection can then drop their objects.
+const MAX_CHECKPOINTS_PER_SESSION: usize = 50;
+
+/// Commit message prefix of checkpoint commits
+const MESSAGE_PREFIX: &str = "claude-terminal checkpoint";
+
+/// A working session gets a checkpoint at least this often
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// claude redraws a running tool's line as it updates the screen; the same
+/// line seen again within this window isn't a new tool run
+const TOOL_REDRAW_WINDOW: Duration = Duration::from_secs(10);
+
+/// Stripped output kept between chunks so a tool line split across reads
+/// is still seen
+const TOOL_TAIL_LEN: usize = 256;
+
+/// Longest `git add -A` or `write-tree` may take on a snapshot before it is
+/// given up, e.g. on a huge worktree or a clean filter that hangs
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Tool lines claude prints as it starts a tool that can change files
+static TOOL_RUN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"⏺ (Bash|Edit|MultiEdit|Write|Update|Create|NotebookEdit)\([^\n]*").unwrap()
+});
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckpointTrigger {
+    /// First output of the session
+    Start,
+    Periodic,
+    /// claude was about to run a tool
+    Tool,
+    Manual,
+    /// Taken just before restoring another checkpoint
+    Restore,
+}
+
+impl CheckpointTrigger {
+    fn as_str(self) -> &'static str {
+        match self {
+            CheckpointTrigger::Start => "start",
+            CheckpointTrigger::Periodic => "periodic",
+            CheckpointTrigger::Tool => "tool",
+            CheckpointTrigger::Manual => "manual",
+            CheckpointTrigger::Restore => "restore",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "start" => CheckpointTrigger::Start,
+            "periodic" => CheckpointTrigger::Periodic,
+            "tool" => CheckpointTrigger::Tool,
+            "restore" => CheckpointTrigger::Restore,
+            _ => CheckpointTrigger::Manual,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub session_id: String,
+    pub number: u32,
+    pub ref_name: String,
+    pub commit: String,
+    pub trigger: CheckpointTrigger,
+    pub created_at: String,
+}
+
+fn check_session_id(session_id: &str) -> Result<(), String> {
+    if session_id.is_empty() || !session_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err("Invalid session id".to_string());
+    }
+    Ok(())
+}
+
+/// Checkpoints in the repository containing `dir`, for one session or all,
+/// oldest first within each session
+pub fn list(dir: &Path, session_id: Option<&str>) -> Result<Vec<Checkpoint>, String> {
+    let prefix = match session_id {
+        Some(id) => {
+            check_session_id(id)?;
+            format!("{}/{}/", REF_PREFIX, id)
+        }
+        None => format!("{}/", REF_PREFIX),
+    };
+    let output = git::run(
+        dir,
+        &[
+            "for-each-ref",
+            "--format=%(refname)%00%(objectname)%00%(creatordate:iso-strict)%00%(contents:subject)",
+            &prefix,
+        ],
+    )?;
+    let mut checkpoints: Vec<Checkpoint> = output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(4, '\0');
+            let ref_name = parts.next()?.to_string();
+            let commit = parts.next()?.to_string();
+            let created_at = parts.next()?.to_string();
+            let subject = parts.next()?;
+            let (session, number) = ref_name.strip_prefix(REF_PREFIX)?.trim_start_matches('/').split_once('/')?;
+            let trigger = subject
+                .rsplit_once('(')
+                .map_or("manual", |(_, t)| t.trim_end_matches(')'));
+            Some(Checkpoint {
+                session_id: session.to_string(),
+                number: number.parse().ok()?,
+                ref_name: ref_name.clone(),
+                commit,
+                trigger: CheckpointTrigger::parse(trigger),
+                created_at,
+            })
+        })
+        .collect();
+    checkpoints.sort_by(|a, b| a.session_id.cmp(&b.session_id).then(a.number.cmp(&b.number)));
+    Ok(checkpoints)
+}
+
+/// Writes the working tree, as `git add -A` would see it, to a tree object
+/// using a scratch index, so the user's index is left alone
+fn snapshot_tree(root: &Path) -> Result<String, String> {
+    let index = git::run_trimmed(root, &["rev-parse", "--git-path", "index"])?;
+    let index = root.join(index);
+    let scratch = std::env::temp_dir().join(format!("claudeterminal-index-{}", uuid::Uuid::new_v4()));
+    // Starting from the real index lets git reuse its cached file stats
+    if index.is_file() {
+        std::fs::copy(&index, &scratch).map_err(|e| format!("Failed to copy the index: {}", e))?;
+    }
+    let scratch_str = scratch.to_string_lossy().to_string();
+    let env = [("GIT_INDEX_FILE", scratch_str.as_str())];
+    let result = git::run_with_timeout(root, &["add", "-A", "--", "."], &env, SNAPSHOT_TIMEOUT)
+        .and_then(|_| git::run_with_timeout(root, &["write-tree"], &env, SNAPSHOT_TIMEOUT))
+        .map(|tree| tree.trim().to_string());
+    let _ = std::fs::remove_file(&scratch);
+    // Left behind when git is stopped by the timeout
+    let _ = std::fs::remove_file(scratch.with_extension("lock"));
+    result
+}
+
+fn repo_root(dir: &Path) -> Result<PathBuf, String> {
+    git::toplevel(dir)
+        .map(PathBuf::from)
+        .ok_or_else(|| "Not a git repository".to_string())
+}
+
+/// Records the working tree as the session's next checkpoint, keeping the
+/// session's newest `MAX_CHECKPOINTS_PER_SESSION`. Returns None when nothing
+/// changed since the session's last checkpoint.
+pub fn create(dir: &Path, session_id: &str, trigger: CheckpointTrigger) -> Result<Option<Checkpoint>, String> {
+    check_session_id(session_id)?;
+    let root = repo_root(dir)?;
+    let existing = list(&root, Some(session_id))?;
+    let tree = snapshot_tree(&root)?;
+
+    if let Some(last) = existing.last() {
+        let last_tree = git::run_trimmed(&root, &["rev-parse", &format!("{}^{{tree}}", last.commit)])?;
+        if last_tree == tree {
+            return Ok(None);
+        }
+    }
+    // Parented on HEAD rather than the previous checkpoint, so pruned
+    // checkpoints don't stay reachable through newer ones
+    let parent = git::run_trimmed(&root, &["rev-parse", "--verify", "--quiet", "HEAD"]).ok();
+
+    let number = existing.last().map_or(1, |c| c.number + 1);
+    let message = format!("{} {} ({})", MESSAGE_PREFIX, number, trigger.as_str());
+    // Signing would prompt for a passphrase or key touch on every checkpoint
+    let mut args = vec!["commit-tree", "--no-gpg-sign", tree.as_str(), "-m", message.as_str()];
+    if let Some(parent) = &parent {
+        args.push("-p");
+        args.push(parent);
+    }
+    // commit-tree needs an identity even though nobody sees these commits
+    let env = [
+        ("GIT_AUTHOR_NAME", "Claude Terminal"),
+        ("GIT_AUTHOR_EMAIL", "checkpoints@claudeterminal.local"),
+        ("GIT_COMMITTER_NAME", "Claude Terminal"),
+        ("GIT_COMMITTER_EMAIL", "checkpoints@claudeterminal.local"),
+    ];
+    let commit = git::run_with_env(&root, &args, &env)?.trim().to_string();
+    let ref_name = format!("{}/{}/{}", REF_PREFIX, session_id, number);
+    git::run(&root, &["update-ref", &ref_name, &commit])?;
+
+    let checkpoints = list(&root, Some(session_id))?;
+    let excess = checkpoints.len().saturating_sub(MAX_CHECKPOINTS_PER_SESSION);
+    for old in &checkpoints[..excess] {
+        let _ = git::run(&root, &["update-ref", "-d", &old.ref_name]);
+    }
+    Ok(checkpoints.into_iter().find(|c| c.ref_name == ref_name))
+}
+
+/// Resolves a checkpoint ref, or "worktree" for the current working tree,
+/// to something `git diff` accepts
+fn resolve(root: &Path, target: &str) -> Result<String, String> {
+    if target == "worktree" {
+        return snapshot_tree(root);
+    }
+    if !target.starts_with(&format!("{}/", REF_PREFIX)) {
+        return Err(format!("Not a checkpoint: {}", target));
+    }
+    git::run_trimmed(root, &["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", target)])
+        .map_err(|_| format!("Checkpoint not found: {}", target))
+}
+
+/// Changes between two checkpoints, or between a checkpoint and the
+/// current working tree when `to` is "worktree"
+pub fn diff(
+    dir: &Path,
+    from: &str,
+    to: &str,
+    options: &crate::changes::ChangesOptions,
+) -> Result<Vec<crate::changes::FileChange>, String> {
+    let root = repo_root(dir)?;
+    let from = resolve(&root, from)?;
+    let to = resolve(&root, to)?;
+    crate::changes::diff_trees(&root, &from, &to, options)
+}
+
+/// Puts the working tree back to a checkpoint. The current state is saved
+/// as a checkpoint of `session_id` first so the restore can be undone.
+/// Files that didn't exist at the checkpoint are deleted; ignored files,
+/// the index and the branch are left alone.
+pub fn restore(dir: &Path, ref_name: &str, session_id: &str) -> Result<Option<Checkpoint>, String> {
+    let root = repo_root(dir)?;
+    let target = resolve(&root, ref_name)?;
+    let safety = create(&root, session_id, CheckpointTrigger::Restore)?;
+    let current = snapshot_tree(&root)?;
+
+    // Paths that only exist now, i.e. were added after the checkpoint
+    let added = git::run(
+        &root,
+        &["-c", "core.quotePath=false", "diff", "--name-only", "-z", "--no-renames", "--diff-filter=A", &target, &current],
+    )?;
+
+    let scratch = std::env::temp_dir().join(format!("claudeterminal-index-{}", uuid::Uuid::new_v4()));
+    let scratch_str = scratch.to_string_lossy().to_string();
+    let env = [("GIT_INDEX_FILE", scratch_str.as_str())];
+    let result = git::run_with_env(&root, &["read-tree", &target], &env)
+        .and_then(|_| git::run_with_env(&root, &["checkout-index", "-a", "-f"], &env));
+    let _ = std::fs::remove_file(&scratch);
+    result?;
+
+    for path in added.split('\0').filter(|p| !p.is_empty()) {
+        let _ = std::fs::remove_file(root.join(path));
+    }
+    Ok(safety)
+}
+
+/// Deletes a session's checkpoint refs
+pub fn delete(dir: &Path, session_id: &str) -> Result<usize, String> {
+    let root = repo_root(dir)?;
+    let checkpoints = list(&root, Some(session_id))?;
+    for checkpoint in &checkpoints {
+        git::run(&root, &["update-ref", "-d", &checkpoint.ref_name])?;
+    }
+    Ok(checkpoints.len())
+}
+
+/// Decides when a running session should be checkpointed, from its output
+pub struct CheckpointScheduler {
+    tail: String,
+    last: Option<Instant>,
+    /// The last tool line seen, to tell redraws from new tool runs
+    last_tool: Option<(String, Instant)>,
+    running: Arc<AtomicBool>,
+    disabled: Arc<AtomicBool>,
+}
+
+impl Default for CheckpointScheduler {
+    fn default() -> Self {
+        CheckpointScheduler {
+            tail: String::new(),
+            last: None,
+            last_tool: None,
+            running: Arc::new(AtomicBool::new(false)),
+            disabled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl CheckpointScheduler {
+    /// Feeds PTY output. Returns the trigger when a checkpoint is due.
+    pub fn feed(&mut self, data: &[u8]) -> Option<CheckpointTrigger> {
+        if self.disabled.load(Ordering::Relaxed) {
+            return None;
+        }
+        let stripped = strip_ansi_escapes::strip(data);
+        self.tail.push_str(&String::from_utf8_lossy(&stripped));
+        let tool_line = TOOL_RUN.find_iter(&self.tail).last().map(|m| m.as_str().trim_end().to_string());
+        if tool_line.is_some() {
+            self.tail.clear();
+        } else if self.tail.len() > TOOL_TAIL_LEN {
+            let mut cut = self.tail.len() - TOOL_TAIL_LEN;
+            while !self.tail.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.tail.drain(..cut);
+        }
+
+        let now = Instant::now();
+        let trigger = if let Some(line) = tool_line {
+            let redrawn = self
+                .last_tool
+                .as_ref()
+                .is_some_and(|(last, at)| *last == line && at.elapsed() < TOOL_REDRAW_WINDOW);
+            self.last_tool = Some((line, now));
+            if redrawn {
+                return None;
+            }
+            CheckpointTrigger::Tool
+        } else {
+            match self.last {
+                None => CheckpointTrigger::Start,
+                Some(at) if at.elapsed() >= CHECKPOINT_INTERVAL => CheckpointTrigger::Periodic,
+                Some(_) => return None,
+            }
+        };
+        if self.running.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.last = Some(now);
+        Some(trigger)
+    }
+
+    /// Takes a checkpoint on a blocking thread while the session carries on,
+    /// so a tool checkpoint may already hold the start of the tool's changes.
+    /// Directories outside git turn the scheduler off.
+    pub fn spawn(&self, working_directory: String, session_id: String, trigger: CheckpointTrigger) {
+        let running = self.running.clone();
+        let disabled = self.disabled.clone();
+        running.store(true, Ordering::Relaxed);
+        tokio::task::spawn_blocking(move || {
+            let dir = Path::new(&working_directory);
+            if git::toplevel(dir).is_none() {
+                disabled.store(true, Ordering::Relaxed);
+            } else if let Err(e) = create(dir, &session_id, trigger) {
+                eprintln!("Failed to create checkpoint: {}", e);
+            }
+            running.store(false, Ordering::Relaxed);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tool_run_matches_only_tools_that_change_files() {
+        for line in ["⏺ Bash(npm test)", "⏺ Update(src/main.rs)", "⏺ Write(notes.md)", "⏺ MultiEdit(a.rs)"] {
+            assert!(TOOL_RUN.is_match(line), "{}", line);
+        }
+        for line in ["⏺ Read(src/main.rs)", "⏺ Grep(pattern)", "Bash(npm test)", "⏺ Bashful(x)", "⏺ I'll run Bash next"] {
+            assert!(!TOOL_RUN.is_match(line), "{}", line);
+        }
+        let m = TOOL_RUN.find("⏺ Bash(ls -la)\r\n⏺ Done").unwrap();
+        assert_eq!(m.as_str().trim_end(), "⏺ Bash(ls -la)");
+    }
+
+    #[test]
+    fn first_output_starts_and_tool_lines_follow() {
+        let mut scheduler = CheckpointScheduler::default();
+        assert_eq!(scheduler.feed(b"Welcome to Claude\r\n"), Some(CheckpointTrigger::Start));
+        assert_eq!(scheduler.feed(b"thinking...\r\n"), None);
+        assert_eq!(scheduler.feed("⏺ Bash(cargo test)\r\n".as_bytes()), Some(CheckpointTrigger::Tool));
+        assert_eq!(scheduler.feed("⏺ Read(Cargo.toml)\r\n".as_bytes()), None);
+    }
+
+    #[test]
+    fn tool_line_split_across_chunks_and_styled_is_seen() {
+        let mut scheduler = CheckpointScheduler::default();
+        scheduler.feed(b"ready\r\n");
+        assert_eq!(scheduler.feed("\x1b[1m⏺\x1b[0m Wri".as_bytes()), None);
+        assert_eq!(scheduler.feed(b"te(\x1b[2mout.txt\x1b[0m)\r\n"), Some(CheckpointTrigger::Tool));
+    }
+
+    #[test]
+    fn redrawn_tool_line_is_not_a_new_run() {
+        let mut scheduler = CheckpointScheduler::default();
+        scheduler.feed(b"ready\r\n");
+        let line = "\x1b[2K\r⏺ Edit(src/lib.rs)".as_bytes();
+        assert_eq!(scheduler.feed(line), Some(CheckpointTrigger::Tool));
+        assert_eq!(scheduler.feed(line), None);
+        assert_eq!(scheduler.feed("⏺ Edit(src/main.rs)".as_bytes()), Some(CheckpointTrigger::Tool));
+    }
+}
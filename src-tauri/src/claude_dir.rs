@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Which `.claude` directory a definition lives in
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ClaudeScope {
+    /// The user's `~/.claude`
+    User,
+    /// `<path>/.claude`, shared with the repository
+    Project { path: String },
+    /// `<path>/.claude` as well, but listed in the repository's
+    /// `.git/info/exclude` so the file is never committed
+    ProjectLocal { path: String },
+}
+
+/// The kinds of markdown definitions claude loads from a `.claude` directory
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DefinitionKind {
+    Agent,
+    Command,
+}
+
+impl DefinitionKind {
+    fn dir_name(self) -> &'static str {
+        match self {
+            DefinitionKind::Agent => "agents",
+            DefinitionKind::Command => "commands",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            DefinitionKind::Agent => "Agent",
+            DefinitionKind::Command => "Command",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DefinitionEntry {
+    pub name: String,
+    pub scope: ClaudeScope,
+    /// True when a definition with the same name in a higher-precedence scope
+    /// is the one claude actually uses
+    pub shadowed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DefinitionDiff {
+    pub name: String,
+    pub from_exists: bool,
+    pub to_exists: bool,
+    pub identical: bool,
+    /// Unified diff from the `from` scope to the `to` scope
+    pub unified: String,
+}
+
+/// Resolved location of one scope's definitions directory
+pub struct ScopeDir {
+    pub dir: PathBuf,
+    /// Set for project scopes: the git exclude file and the directory's path
+    /// relative to the repository root, when the project is a git repository
+    exclude: Option<(PathBuf, String)>,
+    local: bool,
+}
+
+impl ScopeDir {
+    pub fn resolve(scope: &ClaudeScope, kind: DefinitionKind, claude_dir: &Path) -> Result<Self, String> {
+        let (path, local) = match scope {
+            ClaudeScope::User => {
+                return Ok(Self { dir: claude_dir.join(kind.dir_name()), exclude: None, local: false });
+            }
+            ClaudeScope::Project { path } => (path, false),
+            ClaudeScope::ProjectLocal { path } => (path, true),
+        };
+        let root = Path::new(path)
+            .canonicalize()
+            .map_err(|e| format!("Invalid project path: {}", e))?;
+        if !root.is_dir() {
+            return Err(format!("Project path is not a directory: {}", path));
+        }
+        let dir = root.join(".claude").join(kind.dir_name());
+        let exclude = find_exclude_file(&root).and_then(|(repo_root, exclude_file)| {
+            let relative = dir.strip_prefix(&repo_root).ok()?;
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            Some((exclude_file, relative))
+        });
+        if local && exclude.is_none() {
+            return Err("The project-local scope needs the project to be a git repository".to_string());
+        }
+        Ok(Self { dir, exclude, local })
+    }
+
+    /// Lists the definition file names in this scope, sorted
+    pub fn list(&self) -> Result<Vec<String>, String> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+        let excluded = self.excluded_names();
+        let entries = std::fs::read_dir(&self.dir).map_err(|e| e.to_string())?;
+        let mut names: Vec<String> = entries
+            .flatten()
+            .filter(|e| e.path().is_file())
+            .filter_map(|e| e.file_name().to_str().map(String::from))
+            .filter(|name| self.exclude.is_none() || excluded.contains(name) == self.local)
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Returns the path of a definition if it exists in this scope
+    pub fn find(&self, name: &str) -> Option<PathBuf> {
+        let path = self.dir.join(name);
+        if !path.is_file() {
+            return None;
+        }
+        if self.exclude.is_some() && self.excluded_names().iter().any(|n| n == name) != self.local {
+            return None;
+        }
+        Some(path)
+    }
+
+    pub fn read(&self, kind: DefinitionKind, name: &str) -> Result<String, String> {
+        let path = self
+            .find(name)
+            .ok_or_else(|| format!("{} file not found: {}", kind.label(), name))?;
+        std::fs::read_to_string(&path).map_err(|e| e.to_string())
+    }
+
+    pub fn write(&self, name: &str, content: &str) -> Result<(), String> {
+        std::fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        std::fs::write(self.dir.join(name), content).map_err(|e| e.to_string())?;
+        self.set_excluded(name, self.local)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), String> {
+        if let Some(path) = self.find(name) {
+            std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+            if self.local {
+                self.set_excluded(name, false)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether two scopes share the same directory (project and project-local)
+    pub fn same_dir(&self, other: &ScopeDir) -> bool {
+        self.dir == other.dir
+    }
+
+    /// Moves a definition between the project and project-local scopes, which
+    /// only changes whether git ignores it
+    pub fn set_local(&self, name: &str, local: bool) -> Result<(), String> {
+        self.set_excluded(name, local)
+    }
+
+    fn excluded_names(&self) -> Vec<String> {
+        let Some((exclude_file, relative)) = &self.exclude else {
+            return vec![];
+        };
+        let prefix = format!("/{}/", relative);
+        std::fs::read_to_string(exclude_file)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix(&prefix).map(String::from))
+            .collect()
+    }
+
+    fn set_excluded(&self, name: &str, excluded: bool) -> Result<(), String> {
+        let Some((exclude_file, relative)) = &self.exclude else {
+            return Ok(());
+        };
+        let entry = format!("/{}/{}", relative, name);
+        let content = std::fs::read_to_string(exclude_file).unwrap_or_default();
+        let present = content.lines().any(|line| line.trim() == entry);
+        if present == excluded {
+            return Ok(());
+        }
+        let updated = if excluded {
+            let mut updated = content;
+            if !updated.is_empty() && !updated.ends_with('\n') {
+                updated.push('\n');
+            }
+            updated.push_str(&entry);
+            updated.push('\n');
+            updated
+        } else {
+            content
+                .lines()
+                .filter(|line| line.trim() != entry)
+                .map(|line| format!("{}\n", line))
+                .collect()
+        };
+        if let Some(parent) = exclude_file.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(exclude_file, updated)
+            .map_err(|e| format!("Failed to update {}: {}", exclude_file.display(), e))
+    }
+}
+
+/// Finds the repository root and its `info/exclude` file, following `.git`
+/// files used by worktrees and submodules
+fn find_exclude_file(start: &Path) -> Option<(PathBuf, PathBuf)> {
+    for dir in start.ancestors() {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Some((dir.to_path_buf(), dot_git.join("info").join("exclude")));
+        }
+        if dot_git.is_file() {
+            let content = std::fs::read_to_string(&dot_git).ok()?;
+            let git_dir = content.trim().strip_prefix("gitdir:")?.trim();
+            let git_dir = dir.join(git_dir);
+            // Linked worktrees share the exclude file of the main repository
+            let common = std::fs::read_to_string(git_dir.join("commondir"))
+                .map(|c| git_dir.join(c.trim()))
+                .unwrap_or(git_dir);
+            return Some((dir.to_path_buf(), common.join("info").join("exclude")));
+        }
+    }
+    None
+}
+
+/// Lists definitions across scopes. Scopes are given in precedence order
+/// (highest first); a name already seen in an earlier scope is marked shadowed.
+pub fn list_scopes(
+    kind: DefinitionKind,
+    scopes: &[ClaudeScope],
+    claude_dir: &Path,
+) -> Result<Vec<DefinitionEntry>, String> {
+    let mut seen = std::collections::HashSet::new();
+    let mut entries = Vec::new();
+    for scope in scopes {
+        let dir = ScopeDir::resolve(scope, kind, claude_dir)?;
+        for name in dir.list()? {
+            let shadowed = !seen.insert(name.clone());
+            entries.push(DefinitionEntry { name, scope: scope.clone(), shadowed });
+        }
+    }
+    Ok(entries)
+}
+
+/// Produces a unified diff of one definition between two scopes
+pub fn diff(name: &str, from: &ScopeDir, to: &ScopeDir) -> DefinitionDiff {
+    let read = |dir: &ScopeDir| dir.find(name).and_then(|p| std::fs::read_to_string(p).ok());
+    let before = read(from);
+    let after = read(to);
+    let old = before.as_deref().unwrap_or("");
+    let new = after.as_deref().unwrap_or("");
+    let unified = similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&from.dir.join(name).to_string_lossy(), &to.dir.join(name).to_string_lossy())
+        .to_string();
+    DefinitionDiff {
+        name: name.to_string(),
+        from_exists: before.is_some(),
+        to_exists: after.is_some(),
+        identical: before == after,
+        unified,
+    }
+}
+
+/// Copies a definition into another scope. With `remove_source` this is a
+/// move, used to promote a definition (e.g. project-local to project).
+pub fn transfer(
+    name: &str,
+    from: &ScopeDir,
+    to: &ScopeDir,
+    overwrite: bool,
+    remove_source: bool,
+) -> Result<(), String> {
+    let source = from.find(name).ok_or_else(|| format!("Not found in source scope: {}", name))?;
+    if from.same_dir(to) {
+        if from.local == to.local {
+            return Err("Source and destination scopes are the same".to_string());
+        }
+        // Project and project-local share a directory; only the exclusion changes
+        if !remove_source {
+            return Err("A definition can't be in the project and project-local scopes at once".to_string());
+        }
+        return to.set_local(name, to.local);
+    }
+    if to.dir.join(name).exists() && !overwrite {
+        return Err(format!("{} already exists in the destination scope", name));
+    }
+    let content = std::fs::read_to_string(&source).map_err(|e| e.to_string())?;
+    to.write(name, &content)?;
+    if remove_source {
+        from.delete(name)?;
+    }
+    Ok(())
+}
@@ -1,10 +1,27 @@
+use crate::attribution::{FileChangeEntry, FileSession, SessionFile};
+use crate::auto_approve::{AutoApproveAuditEntry, AutoApproveRule, PermissionPrompt, RuleMatch};
+use crate::bundle::{BundleFormat, BundledWorkspace, ConflictStrategy, ImportReport, ProfileBundle};
+use crate::changes::{ChangesOptions, DiscardSnapshot, FileChange, FileChangesResult, HunkRef};
+use crate::checkpoint::{Checkpoint, CheckpointTrigger};
+use crate::claude_dir::{ClaudeScope, DefinitionDiff, DefinitionEntry, DefinitionKind, ScopeDir};
 use crate::config::{ConfigProfile, HintCategory};
-use crate::database::{SessionHistoryEntry, Snippet};
+use crate::conflicts::TerminalConflict;
+use crate::database::{Database, SessionHistoryEntry, Snippet};
+use crate::diagnostics::Diagnostic;
+use crate::frontmatter::ParsedDefinition;
+use crate::hooks::{HookCommand, HookEvent, HookEventEntry, HookMatcher};
+use crate::mcp::{McpScope, McpServerConfig, McpServerEntry};
+use crate::policy::{MaterializedPolicy, PermissionPolicy};
+use crate::pull_request::{PullRequestDescription, PullRequestOptions, PullRequestResult};
+use crate::teams::{TeamInfo, TeamTaskList, TerminalProcess};
+use crate::terminal::TerminalManager;
+use crate::worktree::{WorktreeAction, WorktreeStatus};
 use crate::AppState;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use tauri::{command, AppHandle, Emitter, State};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Mutex};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateTerminalRequest {
@@ -14,6 +31,20 @@ pub struct CreateTerminalRequest {
     pub env_vars: HashMap<String, String>,
     pub color_tag: Option<String>,
     pub nickname: Option<String>,
+    #[serde(default)]
+    pub kind: crate::terminal::TerminalKind,
+    /// Profile the terminal is launched from; its permission policy applies
+    /// unless `permission_policy_id` is given
+    #[serde(default)]
+    pub profile_id: Option<String>,
+    #[serde(default)]
+    pub permission_policy_id: Option<String>,
+    /// Start the terminal in a new git worktree on its own branch
+    #[serde(default)]
+    pub worktree: bool,
+    /// Branch for the worktree; generated from the label when omitted
+    #[serde(default)]
+    pub worktree_branch: Option<String>,
 }
 
 #[command]
@@ -21,6 +52,16 @@ pub async fn create_terminal(
     app: AppHandle,
     state: State<'_, AppState>,
     request: CreateTerminalRequest,
+) -> Result<crate::terminal::TerminalConfig, String> {
+    spawn_terminal(&app, &state, request).await
+}
+
+/// Spawns a terminal, records it in session history and starts forwarding its
+/// output to the frontend. Shared by `create_terminal` and `launch_workspace`.
+async fn spawn_terminal(
+    app: &AppHandle,
+    state: &AppState,
+    request: CreateTerminalRequest,
 ) -> Result<crate::terminal::TerminalConfig, String> {
     let (tx, mut rx) = mpsc::channel::<(String, Vec<u8>)>(100);
 
@@ -37,17 +78,74 @@ pub async fn create_terminal(
         logs_dir.join(filename).to_string_lossy().to_string()
     };
 
+    // Resolve the permission policy, either given directly or via the profile
+    let policy = if request.kind == crate::terminal::TerminalKind::Claude {
+        let db = state.db.lock().await;
+        let policy_id = match (&request.permission_policy_id, &request.profile_id) {
+            (Some(id), _) => Some(id.clone()),
+            (None, Some(profile_id)) => db
+                .get_profiles()?
+                .into_iter()
+                .find(|p| &p.id == profile_id)
+                .and_then(|p| p.permission_policy_id),
+            (None, None) => None,
+        };
+        match policy_id {
+            Some(id) => Some(
+                db.get_permission_policy(&id)?
+                    .ok_or_else(|| format!("Permission policy not found: {}", id))?,
+            ),
+            None => None,
+        }
+    } else {
+        None
+    };
+    let mut launch_args = request.claude_args.clone();
+    if let Some(policy) = &policy {
+        launch_args.extend(policy.materialize(true)?.args);
+    }
+
+    // Create a fresh worktree, or pick up the one a restored session ran in
+    let (worktree, working_directory, created_worktree) = if request.worktree {
+        let (info, dir) = crate::worktree::create(
+            &request.working_directory,
+            request.worktree_branch.as_deref(),
+            &request.label,
+        )?;
+        (Some(info), dir, true)
+    } else {
+        (crate::worktree::find(&request.working_directory), request.working_directory, false)
+    };
+
     let config = {
         let mut terminals = state.terminals.lock().await;
-        terminals.create_terminal(
+        let config = match terminals.create_terminal(
             request.label.clone(),
-            request.working_directory,
-            request.claude_args,
+            working_directory,
+            launch_args,
             request.env_vars,
             request.color_tag,
             request.nickname,
+            request.kind,
             tx,
             Some(log_path.clone()),
+        ) {
+            Ok(config) => config,
+            Err(e) => {
+                if let Some(info) = worktree.as_ref().filter(|_| created_worktree) {
+                    let _ = crate::worktree::remove(info);
+                }
+                return Err(e);
+            }
+        };
+        // Keep the generated policy arguments out of the stored config so a
+        // relaunch applies the policy as it is then, not as it was
+        terminals.set_launch_details(
+            &config.id,
+            request.profile_id,
+            policy.map(|p| p.id),
+            worktree,
+            request.claude_args,
         )?
     };
 
@@ -62,12 +160,30 @@ pub async fn create_terminal(
         );
     }
 
+    state.file_tracker.watch(&config.id, &config.working_directory);
+
     let terminal_id = config.id.clone();
     let db_arc = state.db.clone();
+    let terminals_arc = state.terminals.clone();
+    let file_tracker = state.file_tracker.clone();
+    let working_directory = config.working_directory.clone();
+    // Only claude shows permission prompts
+    let mut detector = (config.kind == crate::terminal::TerminalKind::Claude)
+        .then(crate::auto_approve::PromptDetector::default);
+    let mut checkpoints = (config.kind == crate::terminal::TerminalKind::Claude)
+        .then(crate::checkpoint::CheckpointScheduler::default);
 
     let app_clone = app.clone();
+    let files_watcher = crate::files_watcher::FilesWatcher::start(
+        app.clone(),
+        state.terminals.clone(),
+        state.conflicts.clone(),
+        config.id.clone(),
+        config.working_directory.clone(),
+    );
     tokio::spawn(async move {
         while let Some((id, data)) = rx.recv().await {
+            file_tracker.mark_active(&id);
             if let Err(e) = app_clone.emit("terminal-output", serde_json::json!({
                 "id": id,
                 "data": data,
@@ -75,9 +191,21 @@ pub async fn create_terminal(
                 eprintln!("Failed to emit terminal-output: {}", e);
                 break;
             }
+
+            if let Some(scheduler) = checkpoints.as_mut() {
+                if let Some(trigger) = scheduler.feed(&data) {
+                    scheduler.spawn(working_directory.clone(), id.clone(), trigger);
+                }
+            }
+
+            if let Some(prompt) = detector.as_mut().and_then(|d| d.feed(&data)) {
+                auto_answer(&app_clone, &db_arc, &terminals_arc, &id, &working_directory, &prompt).await;
+            }
         }
 
         // Terminal process exited — update session history and notify frontend
+        drop(files_watcher);
+        file_tracker.unwatch(&terminal_id);
         {
             let db = db_arc.lock().await;
             let _ = db.update_session_ended(&terminal_id, &chrono::Utc::now().to_rfc3339());
@@ -93,6 +221,45 @@ pub async fn create_terminal(
     Ok(config)
 }
 
+/// Answers a detected permission prompt when an auto-approve rule matches it,
+/// and records the decision in the audit trail
+async fn auto_answer(
+    app: &AppHandle,
+    db: &Arc<Mutex<Database>>,
+    terminals: &Arc<Mutex<TerminalManager>>,
+    terminal_id: &str,
+    working_directory: &str,
+    prompt: &PermissionPrompt,
+) {
+    let rules = match db.lock().await.get_auto_approve_rules() {
+        Ok(rules) => rules,
+        Err(e) => {
+            eprintln!("Failed to load auto-approve rules: {}", e);
+            return;
+        }
+    };
+    let Some(matched) = crate::auto_approve::decide(&rules, working_directory, prompt) else {
+        return;
+    };
+
+    if let Err(e) = terminals.lock().await.write(terminal_id, matched.decision.keys()) {
+        eprintln!("Failed to answer permission prompt: {}", e);
+        return;
+    }
+    if let Err(e) = db.lock().await.insert_auto_approve_audit(terminal_id, &prompt.text, &matched) {
+        eprintln!("Failed to record auto-approve decision: {}", e);
+    }
+    if let Err(e) = app.emit("terminal-auto-approved", serde_json::json!({
+        "id": terminal_id,
+        "prompt": prompt.text,
+        "rule_id": matched.rule_id,
+        "rule_name": matched.rule_name,
+        "decision": matched.decision,
+    })) {
+        eprintln!("Failed to emit terminal-auto-approved: {}", e);
+    }
+}
+
 /// Maximum size for a single write to terminal (64 KB)
 const MAX_TERMINAL_WRITE_SIZE: usize = 65_536;
 
@@ -130,6 +297,49 @@ pub async fn close_terminal(state: State<'_, AppState>, id: String) -> Result<()
     terminals.close(&id)
 }
 
+/// Managed worktrees, including kept ones whose terminal has closed
+#[command]
+pub async fn get_worktrees(state: State<'_, AppState>) -> Result<Vec<WorktreeStatus>, String> {
+    let configs = state.terminals.lock().await.get_all_configs();
+    Ok(crate::worktree::list()?
+        .into_iter()
+        .map(|info| {
+            let terminal_id = configs
+                .iter()
+                .find(|c| c.worktree.as_ref().is_some_and(|w| w.id == info.id))
+                .map(|c| c.id.clone());
+            crate::worktree::status(info, terminal_id)
+        })
+        .collect())
+}
+
+/// Closes the terminals running in a worktree, then keeps, merges back or
+/// removes it. A merge that can't go ahead leaves the terminals open.
+#[command]
+pub async fn finish_worktree(
+    state: State<'_, AppState>,
+    worktree_id: String,
+    action: WorktreeAction,
+) -> Result<(), String> {
+    let info = crate::worktree::load(&worktree_id)?;
+    if action == WorktreeAction::Merge {
+        crate::worktree::check_merge(&info)?;
+    }
+    {
+        let mut terminals = state.terminals.lock().await;
+        for config in terminals.get_all_configs() {
+            if config.worktree.as_ref().is_some_and(|w| w.id == info.id) {
+                terminals.close(&config.id)?;
+            }
+        }
+    }
+    match action {
+        WorktreeAction::Keep => Ok(()),
+        WorktreeAction::Merge => crate::worktree::merge(&info),
+        WorktreeAction::Remove => crate::worktree::remove(&info),
+    }
+}
+
 #[command]
 pub async fn get_terminals(
     state: State<'_, AppState>,
@@ -148,6 +358,16 @@ pub async fn update_terminal_label(
     terminals.update_label(&id, label)
 }
 
+#[command]
+pub async fn update_terminal_color_tag(
+    state: State<'_, AppState>,
+    id: String,
+    color_tag: Option<String>,
+) -> Result<(), String> {
+    let mut terminals = state.terminals.lock().await;
+    terminals.update_color_tag(&id, color_tag)
+}
+
 #[command]
 pub async fn update_terminal_nickname(
     state: State<'_, AppState>,
@@ -179,6 +399,201 @@ pub async fn delete_profile(state: State<'_, AppState>, id: String) -> Result<()
     db.delete_profile(&id)
 }
 
+// Permission policies
+
+#[command]
+pub async fn get_permission_policies(state: State<'_, AppState>) -> Result<Vec<PermissionPolicy>, String> {
+    let db = state.db.lock().await;
+    db.get_permission_policies()
+}
+
+/// Saves a policy, assigning an id to new ones, and returns it
+#[command]
+pub async fn save_permission_policy(
+    state: State<'_, AppState>,
+    mut policy: PermissionPolicy,
+) -> Result<PermissionPolicy, String> {
+    if policy.id.is_empty() {
+        policy.id = uuid::Uuid::new_v4().to_string();
+    } else if !policy.id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err("Invalid policy id".to_string());
+    }
+    policy.validate()?;
+    let db = state.db.lock().await;
+    db.save_permission_policy(&policy)?;
+    Ok(policy)
+}
+
+#[command]
+pub async fn delete_permission_policy(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    let db = state.db.lock().await;
+    db.delete_permission_policy(&id)?;
+    crate::policy::remove_settings_file(&id);
+    Ok(())
+}
+
+/// Shows the rules and launch arguments a policy would produce, without
+/// writing anything
+#[command]
+pub async fn preview_permission_policy(policy: PermissionPolicy) -> Result<MaterializedPolicy, String> {
+    policy.validate()?;
+    policy.materialize(false)
+}
+
+// Auto-approve rules
+
+#[command]
+pub async fn get_auto_approve_rules(state: State<'_, AppState>) -> Result<Vec<AutoApproveRule>, String> {
+    let db = state.db.lock().await;
+    db.get_auto_approve_rules()
+}
+
+/// Saves a rule, assigning an id to new ones, and returns it
+#[command]
+pub async fn save_auto_approve_rule(
+    state: State<'_, AppState>,
+    mut rule: AutoApproveRule,
+) -> Result<AutoApproveRule, String> {
+    if rule.id.is_empty() {
+        rule.id = uuid::Uuid::new_v4().to_string();
+    }
+    rule.validate()?;
+    let db = state.db.lock().await;
+    db.save_auto_approve_rule(&rule)?;
+    Ok(rule)
+}
+
+#[command]
+pub async fn delete_auto_approve_rule(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    let db = state.db.lock().await;
+    db.delete_auto_approve_rule(&id)?;
+    crate::auto_approve::forget_rule(&id);
+    Ok(())
+}
+
+/// Automatic decisions, newest first, optionally for a single terminal
+#[command]
+pub async fn get_auto_approve_audit(
+    state: State<'_, AppState>,
+    terminal_id: Option<String>,
+    limit: Option<u32>,
+) -> Result<Vec<AutoApproveAuditEntry>, String> {
+    let db = state.db.lock().await;
+    db.get_auto_approve_audit(terminal_id.as_deref(), limit.unwrap_or(200).min(5000) as i64)
+}
+
+/// Shows which saved rule, if any, would answer a prompt in a directory
+#[command]
+pub async fn test_auto_approve_rules(
+    state: State<'_, AppState>,
+    prompt: String,
+    working_directory: String,
+) -> Result<Option<RuleMatch>, String> {
+    let db = state.db.lock().await;
+    let rules = db.get_auto_approve_rules()?;
+    let prompt = PermissionPrompt::from_text(&prompt);
+    Ok(crate::auto_approve::decide(&rules, &working_directory, &prompt))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExportProfilesOptions {
+    #[serde(default)]
+    pub include_snippets: bool,
+    /// Names of saved workspaces to include in the bundle
+    #[serde(default)]
+    pub workspaces: Vec<String>,
+}
+
+/// Writes the selected profiles (all profiles when `ids` is empty) to a
+/// versioned bundle file. The format is chosen from the `.json`/`.toml` extension.
+#[command]
+pub async fn export_profiles(
+    state: State<'_, AppState>,
+    ids: Vec<String>,
+    path: String,
+    options: Option<ExportProfilesOptions>,
+) -> Result<(), String> {
+    let options = options.unwrap_or_default();
+    let path = std::path::PathBuf::from(path);
+    let format = BundleFormat::from_path(&path)?;
+
+    let bundle = {
+        let db = state.db.lock().await;
+        let profiles: Vec<ConfigProfile> = db
+            .get_profiles()?
+            .into_iter()
+            .filter(|p| ids.is_empty() || ids.contains(&p.id))
+            .collect();
+        if !ids.is_empty() && profiles.len() != ids.len() {
+            return Err("One or more selected profiles no longer exist".to_string());
+        }
+        let snippets = if options.include_snippets {
+            db.get_snippets()?
+        } else {
+            vec![]
+        };
+        let mut workspaces = Vec::new();
+        for name in &options.workspaces {
+            if name.starts_with("__") {
+                return Err("Cannot export internal workspaces".to_string());
+            }
+            workspaces.push(BundledWorkspace {
+                name: name.clone(),
+                terminals: db.load_workspace(name)?,
+            });
+        }
+        let mut policies: Vec<PermissionPolicy> = Vec::new();
+        for id in profiles.iter().filter_map(|p| p.permission_policy_id.as_deref()) {
+            if policies.iter().any(|p| p.id == id) {
+                continue;
+            }
+            policies.extend(db.get_permission_policy(id)?);
+        }
+        ProfileBundle::new(profiles, snippets, workspaces, policies)
+    };
+
+    let content = bundle.serialize(format)?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to write bundle: {}", e))
+}
+
+/// Imports a bundle written by `export_profiles`. With `dry_run` set, nothing is
+/// written and the report only previews what would happen to each entry.
+#[command]
+pub async fn import_profiles(
+    state: State<'_, AppState>,
+    path: String,
+    strategy: Option<ConflictStrategy>,
+    dry_run: Option<bool>,
+) -> Result<ImportReport, String> {
+    let path = std::path::PathBuf::from(path);
+    let format = BundleFormat::from_path(&path)?;
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read bundle: {}", e))?;
+    let bundle = ProfileBundle::parse(&content, format)?;
+    let dry_run = dry_run.unwrap_or(false);
+
+    let db = state.db.lock().await;
+    let existing_workspaces: Vec<String> =
+        db.get_workspaces()?.into_iter().map(|w| w.name).collect();
+    let plan = crate::bundle::plan_import(
+        bundle,
+        &db.get_profiles()?,
+        &db.get_snippets()?,
+        &existing_workspaces,
+        &db.get_permission_policies()?,
+        strategy.unwrap_or_default(),
+    );
+
+    if !dry_run {
+        db.apply_import(&plan)?;
+    }
+
+    Ok(ImportReport {
+        dry_run,
+        items: plan.items,
+    })
+}
+
 #[command]
 pub async fn get_claude_version() -> Result<String, String> {
     let output = shell_command("claude", &["--version"])
@@ -314,7 +729,7 @@ fn shell_escape_arg(arg: &str) -> String {
 /// Creates a Command that works cross-platform.
 /// On Windows, wraps the command with `cmd /C` so that `.cmd`/`.bat` scripts
 /// (like `npm.cmd`, `claude.cmd`) are resolved correctly.
-fn shell_command(program: &str, args: &[&str]) -> std::process::Command {
+pub(crate) fn shell_command(program: &str, args: &[&str]) -> std::process::Command {
     if cfg!(target_os = "windows") {
         let mut cmd = std::process::Command::new("cmd");
         cmd.arg("/C").arg(program);
@@ -463,6 +878,207 @@ pub async fn load_workspace(
     db.load_workspace(&name)
 }
 
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LaunchWorkspaceOptions {
+    /// Delay between consecutive terminal spawns, in milliseconds
+    #[serde(default)]
+    pub stagger_ms: u64,
+    /// Skip terminals whose working directory already has an open terminal
+    #[serde(default)]
+    pub skip_open_directories: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LaunchStatus {
+    Launched,
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TerminalLaunchResult {
+    /// Id of the terminal as saved in the workspace
+    pub source_id: String,
+    pub label: String,
+    pub status: LaunchStatus,
+    pub terminal: Option<crate::terminal::TerminalConfig>,
+    pub error: Option<String>,
+}
+
+/// Maximum stagger between terminal spawns (10 seconds)
+const MAX_LAUNCH_STAGGER_MS: u64 = 10_000;
+
+/// Spawns every terminal of a saved workspace in order. A failed spawn does not
+/// stop the launch; each terminal's outcome is reported in the returned list,
+/// which follows the workspace's terminal order.
+#[command]
+pub async fn launch_workspace(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    name: String,
+    options: Option<LaunchWorkspaceOptions>,
+) -> Result<Vec<TerminalLaunchResult>, String> {
+    let options = options.unwrap_or_default();
+    let stagger = std::time::Duration::from_millis(options.stagger_ms.min(MAX_LAUNCH_STAGGER_MS));
+
+    let configs = {
+        let db = state.db.lock().await;
+        db.load_workspace(&name)?
+    };
+
+    let open_directories: Vec<std::path::PathBuf> = if options.skip_open_directories {
+        let terminals = state.terminals.lock().await;
+        terminals
+            .get_all_configs()
+            .iter()
+            .map(|c| normalize_directory(&c.working_directory))
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let mut results = Vec::with_capacity(configs.len());
+    let mut spawned_any = false;
+    for config in configs {
+        if options.skip_open_directories
+            && open_directories.contains(&normalize_directory(&config.working_directory))
+        {
+            results.push(TerminalLaunchResult {
+                source_id: config.id,
+                label: config.label,
+                status: LaunchStatus::Skipped,
+                terminal: None,
+                error: None,
+            });
+            continue;
+        }
+
+        if spawned_any && !stagger.is_zero() {
+            tokio::time::sleep(stagger).await;
+        }
+        spawned_any = true;
+
+        let request = CreateTerminalRequest {
+            label: config.label.clone(),
+            working_directory: config.working_directory,
+            claude_args: config.claude_args,
+            env_vars: config.env_vars,
+            color_tag: config.color_tag,
+            nickname: config.nickname,
+            kind: config.kind,
+            profile_id: config.profile_id,
+            permission_policy_id: config.permission_policy_id,
+            worktree: false,
+            worktree_branch: None,
+        };
+        let result = match spawn_terminal(&app, &state, request).await {
+            Ok(terminal) => TerminalLaunchResult {
+                source_id: config.id,
+                label: config.label,
+                status: LaunchStatus::Launched,
+                terminal: Some(terminal),
+                error: None,
+            },
+            Err(e) => TerminalLaunchResult {
+                source_id: config.id,
+                label: config.label,
+                status: LaunchStatus::Failed,
+                terminal: None,
+                error: Some(e),
+            },
+        };
+        results.push(result);
+    }
+
+    Ok(results)
+}
+
+/// Canonicalizes a directory for comparison, falling back to the raw path when
+/// it no longer exists
+fn normalize_directory(dir: &str) -> std::path::PathBuf {
+    let path = std::path::Path::new(dir);
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[command]
+pub async fn list_workspace_revisions(
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<Vec<crate::database::WorkspaceRevision>, String> {
+    let db = state.db.lock().await;
+    db.get_workspace_revisions(&name)
+}
+
+#[command]
+pub async fn diff_workspace_revisions(
+    state: State<'_, AppState>,
+    name: String,
+    from: i64,
+    to: i64,
+) -> Result<crate::workspace_diff::WorkspaceDiff, String> {
+    let db = state.db.lock().await;
+    let before = db.load_workspace_revision(&name, from)?;
+    let after = db.load_workspace_revision(&name, to)?;
+    Ok(crate::workspace_diff::diff_terminals(from, before, to, after))
+}
+
+/// Makes an older revision the current layout. The restore is saved as a new
+/// revision, so it can itself be undone.
+#[command]
+pub async fn restore_workspace_revision(
+    state: State<'_, AppState>,
+    name: String,
+    revision: i64,
+) -> Result<Vec<crate::terminal::TerminalConfig>, String> {
+    let db = state.db.lock().await;
+    let terminals = db.load_workspace_revision(&name, revision)?;
+    db.save_workspace(&name, &terminals)?;
+    Ok(terminals)
+}
+
+/// Finds the `.claudeterminal.toml` that applies to `directory` (searching
+/// upward to the repository root) and returns its terminals, or `None` when
+/// the project has no file. Args and env vars are left out until the file's
+/// content is trusted with `trust_project_file`.
+#[command]
+pub async fn load_project_workspace(
+    state: State<'_, AppState>,
+    directory: String,
+) -> Result<Option<crate::project_file::ProjectWorkspace>, String> {
+    let start = std::path::Path::new(&directory)
+        .canonicalize()
+        .map_err(|e| format!("Invalid directory: {}", e))?;
+    let Some(path) = crate::project_file::discover(&start) else {
+        return Ok(None);
+    };
+    // Keyed as in `trust_project_file`
+    let key = path.canonicalize().unwrap_or_else(|_| path.clone()).to_string_lossy().to_string();
+    let db = state.db.lock().await;
+    crate::project_file::load(&path, |content| {
+        db.is_project_file_trusted(&key, content).unwrap_or(false)
+    })
+    .map(Some)
+}
+
+/// Trusts a project file with the content the user reviewed, as returned by
+/// `load_project_workspace`. Any later edit needs trusting again.
+#[command]
+pub async fn trust_project_file(
+    state: State<'_, AppState>,
+    file_path: String,
+    content: String,
+) -> Result<(), String> {
+    let path = std::path::Path::new(&file_path)
+        .canonicalize()
+        .map_err(|e| format!("Invalid project file: {}", e))?;
+    if path.file_name().is_none_or(|n| n != crate::project_file::PROJECT_FILE_NAME) {
+        return Err(format!("Not a {} file", crate::project_file::PROJECT_FILE_NAME));
+    }
+    let db = state.db.lock().await;
+    db.trust_project_file(&path.to_string_lossy(), &content)
+}
+
 #[command]
 pub async fn save_session_for_restore(state: State<'_, AppState>) -> Result<(), String> {
     let configs = {
@@ -487,117 +1103,314 @@ pub async fn clear_last_session(state: State<'_, AppState>) -> Result<(), String
     db.clear_last_session()
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct FileChange {
-    pub path: String,
-    pub status: String,
+async fn terminal_working_directory(state: &AppState, id: &str) -> Result<String, String> {
+    let terminals = state.terminals.lock().await;
+    terminals
+        .get_all_configs()
+        .into_iter()
+        .find(|c| c.id == id)
+        .map(|c| c.working_directory)
+        .ok_or_else(|| "Terminal not found".to_string())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct FileChangesResult {
-    pub terminal_id: String,
-    pub working_directory: String,
-    pub changes: Vec<FileChange>,
-    pub is_git_repo: bool,
-    pub branch: Option<String>,
-    pub error: Option<String>,
+/// Collects the changes in a terminal's working directory
+async fn terminal_changes(
+    state: &AppState,
+    id: String,
+    options: ChangesOptions,
+) -> Result<FileChangesResult, String> {
+    let working_directory = terminal_working_directory(state, &id).await?;
+    tokio::task::spawn_blocking(move || crate::changes::terminal_result(id, working_directory, &options))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Runs a blocking git action in a terminal's working directory
+async fn in_terminal_repo<T, F>(state: &AppState, id: &str, action: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&std::path::Path) -> Result<T, String> + Send + 'static,
+{
+    let dir = terminal_working_directory(state, id).await?;
+    tokio::task::spawn_blocking(move || action(std::path::Path::new(&dir)))
+        .await
+        .map_err(|e| e.to_string())?
 }
 
 #[command]
 pub async fn get_terminal_changes(
     state: State<'_, AppState>,
     id: String,
+    options: Option<ChangesOptions>,
 ) -> Result<FileChangesResult, String> {
-    let working_directory = {
-        let terminals = state.terminals.lock().await;
-        let configs = terminals.get_all_configs();
-        configs
-            .into_iter()
-            .find(|c| c.id == id)
-            .map(|c| c.working_directory.clone())
-            .ok_or_else(|| "Terminal not found".to_string())?
-    };
-
-    // Check if it's a git repo and get branch name
-    let branch_output = shell_command("git", &["rev-parse", "--abbrev-ref", "HEAD"])
-        .current_dir(&working_directory)
-        .output();
-
-    let (is_git_repo, branch) = match branch_output {
-        Ok(output) if output.status.success() => {
-            let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
-            (true, Some(branch))
-        }
-        _ => (false, None),
-    };
-
-    if !is_git_repo {
-        return Ok(FileChangesResult {
-            terminal_id: id,
-            working_directory,
-            changes: vec![],
-            is_git_repo: false,
-            branch: None,
-            error: None,
-        });
-    }
+    terminal_changes(&state, id, options.unwrap_or_default()).await
+}
 
-    // Get changed files
-    let status_output = shell_command("git", &["status", "--porcelain"])
-        .current_dir(&working_directory)
-        .output()
-        .map_err(|e| format!("Failed to run git status: {}", e))?;
+#[command]
+pub async fn stage_terminal_files(
+    state: State<'_, AppState>,
+    id: String,
+    paths: Vec<String>,
+    options: Option<ChangesOptions>,
+) -> Result<FileChangesResult, String> {
+    in_terminal_repo(&state, &id, move |dir| crate::changes::stage_paths(dir, &paths)).await?;
+    terminal_changes(&state, id, options.unwrap_or_default()).await
+}
 
-    if !status_output.status.success() {
-        return Ok(FileChangesResult {
-            terminal_id: id,
-            working_directory,
-            changes: vec![],
-            is_git_repo: true,
-            branch,
-            error: Some(String::from_utf8_lossy(&status_output.stderr).trim().to_string()),
-        });
-    }
-
-    let stdout = String::from_utf8_lossy(&status_output.stdout);
-    let changes: Vec<FileChange> = stdout
-        .lines()
-        .filter(|line| line.len() >= 3)
-        .map(|line| {
-            let code = &line[..2];
-            let path = line[3..].to_string();
-            let status = match code.trim() {
-                "??" => "untracked",
-                "A" | "A " => "new",
-                "M" | "M " | " M" | "MM" => "modified",
-                "D" | "D " | " D" => "deleted",
-                r if r.starts_with('R') => "renamed",
-                _ => "modified",
-            };
-            FileChange {
-                path,
-                status: status.to_string(),
-            }
-        })
-        .collect();
+#[command]
+pub async fn unstage_terminal_files(
+    state: State<'_, AppState>,
+    id: String,
+    paths: Vec<String>,
+    options: Option<ChangesOptions>,
+) -> Result<FileChangesResult, String> {
+    in_terminal_repo(&state, &id, move |dir| crate::changes::unstage_paths(dir, &paths)).await?;
+    terminal_changes(&state, id, options.unwrap_or_default()).await
+}
 
-    Ok(FileChangesResult {
-        terminal_id: id,
-        working_directory,
-        changes,
-        is_git_repo: true,
-        branch,
-        error: None,
+/// Stages an unstaged hunk or unstages a staged one. `options` must use the
+/// same `context_lines` the hunk was listed with.
+#[command]
+pub async fn toggle_terminal_hunk(
+    state: State<'_, AppState>,
+    id: String,
+    hunk: HunkRef,
+    options: Option<ChangesOptions>,
+) -> Result<FileChangesResult, String> {
+    let options = options.unwrap_or_default();
+    let context_lines = options.context_lines;
+    in_terminal_repo(&state, &id, move |dir| {
+        crate::changes::toggle_hunk(dir, &hunk, context_lines)
     })
+    .await?;
+    terminal_changes(&state, id, options).await
 }
 
-// Session history commands
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiscardResult {
+    pub snapshot: DiscardSnapshot,
+    pub changes: FileChangesResult,
+}
 
+/// Discards the files' changes after saving them to a stash snapshot
 #[command]
-pub async fn get_session_history(
+pub async fn discard_terminal_files(
     state: State<'_, AppState>,
-) -> Result<Vec<SessionHistoryEntry>, String> {
-    let db = state.db.lock().await;
+    id: String,
+    paths: Vec<String>,
+    options: Option<ChangesOptions>,
+) -> Result<DiscardResult, String> {
+    let snapshot =
+        in_terminal_repo(&state, &id, move |dir| crate::changes::discard_paths(dir, &paths)).await?;
+    let changes = terminal_changes(&state, id, options.unwrap_or_default()).await?;
+    Ok(DiscardResult { snapshot, changes })
+}
+
+#[command]
+pub async fn list_discard_snapshots(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<Vec<DiscardSnapshot>, String> {
+    in_terminal_repo(&state, &id, crate::changes::list_snapshots).await
+}
+
+#[command]
+pub async fn restore_discard_snapshot(
+    state: State<'_, AppState>,
+    id: String,
+    commit: String,
+    options: Option<ChangesOptions>,
+) -> Result<FileChangesResult, String> {
+    in_terminal_repo(&state, &id, move |dir| crate::changes::restore_snapshot(dir, &commit)).await?;
+    terminal_changes(&state, id, options.unwrap_or_default()).await
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommitResult {
+    pub commit: String,
+    pub changes: FileChangesResult,
+}
+
+/// Commits the staged changes in a terminal's repository
+#[command]
+pub async fn commit_terminal_changes(
+    state: State<'_, AppState>,
+    id: String,
+    message: String,
+    options: Option<ChangesOptions>,
+) -> Result<CommitResult, String> {
+    let commit =
+        in_terminal_repo(&state, &id, move |dir| crate::changes::commit(dir, &message)).await?;
+    let changes = terminal_changes(&state, id, options.unwrap_or_default()).await?;
+    Ok(CommitResult { commit, changes })
+}
+
+/// Pairs of running terminals whose uncommitted changes in one repository
+/// overlap
+#[command]
+pub async fn get_terminal_conflicts(state: State<'_, AppState>) -> Result<Vec<TerminalConflict>, String> {
+    let mut conflicts = state.conflicts.conflicts();
+    // Names may have changed since the changes were last read
+    let configs = state.terminals.lock().await.get_all_configs();
+    for side in conflicts.iter_mut().flat_map(|c| c.terminals.iter_mut()) {
+        if let Some(config) = configs.iter().find(|c| c.id == side.terminal_id) {
+            side.label = config.nickname.clone().unwrap_or_else(|| config.label.clone());
+        }
+    }
+    Ok(conflicts)
+}
+
+// Pull requests
+
+/// A terminal's display name and what its session printed, which pull
+/// request titles and bodies are generated from
+async fn terminal_transcript(state: &State<'_, AppState>, id: &str) -> Result<(String, String), String> {
+    let label = {
+        let terminals = state.terminals.lock().await;
+        terminals
+            .get_all_configs()
+            .into_iter()
+            .find(|c| c.id == id)
+            .map(|c| c.nickname.unwrap_or(c.label))
+            .ok_or_else(|| "Terminal not found".to_string())?
+    };
+    let transcript = get_session_log(state.clone(), id.to_string()).await?.unwrap_or_default();
+    Ok((label, transcript))
+}
+
+/// Title and body a pull request for the terminal's session would get
+#[command]
+pub async fn describe_pull_request(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<PullRequestDescription, String> {
+    let (label, transcript) = terminal_transcript(&state, &id).await?;
+    Ok(crate::pull_request::describe(&transcript, &label))
+}
+
+/// Commits the terminal's pending changes, pushes them on a branch and opens
+/// a pull request
+#[command]
+pub async fn create_pull_request(
+    state: State<'_, AppState>,
+    id: String,
+    options: Option<PullRequestOptions>,
+) -> Result<PullRequestResult, String> {
+    let (label, transcript) = terminal_transcript(&state, &id).await?;
+    let options = options.unwrap_or_default();
+    in_terminal_repo(&state, &id, move |dir| {
+        crate::pull_request::create(dir, &label, &transcript, &options)
+    })
+    .await
+}
+
+// Checkpoints
+
+/// Checkpoints the terminal's working tree now, as part of its session
+#[command]
+pub async fn create_checkpoint(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<Option<Checkpoint>, String> {
+    let session_id = id.clone();
+    in_terminal_repo(&state, &id, move |dir| {
+        crate::checkpoint::create(dir, &session_id, CheckpointTrigger::Manual)
+    })
+    .await
+}
+
+/// Checkpoints in a repository, for one session or all of them. Works after
+/// the session's terminal has closed.
+#[command]
+pub async fn list_checkpoints(
+    working_directory: String,
+    session_id: Option<String>,
+) -> Result<Vec<Checkpoint>, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::checkpoint::list(std::path::Path::new(&working_directory), session_id.as_deref())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Changes between two checkpoint refs; `to` defaults to the current
+/// working tree
+#[command]
+pub async fn diff_checkpoints(
+    working_directory: String,
+    from: String,
+    to: Option<String>,
+    options: Option<ChangesOptions>,
+) -> Result<Vec<FileChange>, String> {
+    let to = to.unwrap_or_else(|| "worktree".to_string());
+    let options = options.unwrap_or_default();
+    tokio::task::spawn_blocking(move || {
+        crate::checkpoint::diff(std::path::Path::new(&working_directory), &from, &to, &options)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Restores the working tree to a checkpoint, first checkpointing the
+/// current state into `session_id`. Returns that safety checkpoint.
+#[command]
+pub async fn restore_checkpoint(
+    working_directory: String,
+    ref_name: String,
+    session_id: String,
+) -> Result<Option<Checkpoint>, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::checkpoint::restore(std::path::Path::new(&working_directory), &ref_name, &session_id)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[command]
+pub async fn delete_checkpoints(working_directory: String, session_id: String) -> Result<usize, String> {
+    tokio::task::spawn_blocking(move || {
+        crate::checkpoint::delete(std::path::Path::new(&working_directory), &session_id)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Database status and recovery
+
+#[command]
+pub async fn get_database_status(
+    state: State<'_, AppState>,
+) -> Result<crate::database::DatabaseStatus, String> {
+    let db = state.db.lock().await;
+    Ok(db.status())
+}
+
+#[command]
+pub async fn retry_database_open(
+    state: State<'_, AppState>,
+) -> Result<crate::database::DatabaseStatus, String> {
+    let mut db = state.db.lock().await;
+    db.reopen()?;
+    Ok(db.status())
+}
+
+#[command]
+pub async fn restore_database_backup(
+    state: State<'_, AppState>,
+    file_name: String,
+) -> Result<crate::database::DatabaseStatus, String> {
+    let mut db = state.db.lock().await;
+    db.restore_backup(&file_name)?;
+    Ok(db.status())
+}
+
+// Session history commands
+
+#[command]
+pub async fn get_session_history(
+    state: State<'_, AppState>,
+) -> Result<Vec<SessionHistoryEntry>, String> {
+    let db = state.db.lock().await;
     db.get_session_history()
 }
 
@@ -648,6 +1461,41 @@ pub async fn delete_session_history(
     db.delete_session_history_entry(id)
 }
 
+/// Files a session changed, most recently changed first
+#[command]
+pub async fn get_session_files(
+    state: State<'_, AppState>,
+    session_id: i64,
+) -> Result<Vec<SessionFile>, String> {
+    let db = state.db.lock().await;
+    db.get_session_files(session_id)
+}
+
+/// Every file change recorded for a session, oldest first
+#[command]
+pub async fn get_session_timeline(
+    state: State<'_, AppState>,
+    session_id: i64,
+) -> Result<Vec<FileChangeEntry>, String> {
+    let db = state.db.lock().await;
+    db.get_session_timeline(session_id)
+}
+
+/// Sessions that changed a file, most recent first
+#[command]
+pub async fn get_file_sessions(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<Vec<FileSession>, String> {
+    // Changes are stored under canonical paths
+    let path = std::path::Path::new(&path)
+        .canonicalize()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or(path);
+    let db = state.db.lock().await;
+    db.get_file_sessions(&path)
+}
+
 /// Retrieve the log content for a terminal from a previous session.
 /// Looks up the most recent session_history entry for the given terminal_id,
 /// reads the log file, and returns its content (capped at 512 KB).
@@ -726,14 +1574,19 @@ pub async fn delete_snippet(state: State<'_, AppState>, id: String) -> Result<()
 
 // Claude Global Configuration (~/.claude/)
 
-/// Returns the path to the user's ~/.claude directory
-fn get_claude_dir() -> Result<std::path::PathBuf, String> {
+/// Returns the user's home directory
+fn get_home_dir() -> Result<std::path::PathBuf, String> {
     let home = if cfg!(target_os = "windows") {
         std::env::var("USERPROFILE").map_err(|_| "USERPROFILE not set".to_string())?
     } else {
         std::env::var("HOME").map_err(|_| "HOME not set".to_string())?
     };
-    Ok(std::path::Path::new(&home).join(".claude"))
+    Ok(std::path::PathBuf::from(home))
+}
+
+/// Returns the path to the user's ~/.claude directory
+pub(crate) fn get_claude_dir() -> Result<std::path::PathBuf, String> {
+    Ok(get_home_dir()?.join(".claude"))
 }
 
 /// Validates that a filename is safe (no path traversal)
@@ -759,189 +1612,761 @@ pub async fn read_claude_settings() -> Result<String, String> {
 
 #[command]
 pub async fn write_claude_settings(content: String) -> Result<(), String> {
-    // Validate it's valid JSON
-    serde_json::from_str::<serde_json::Value>(&content)
-        .map_err(|e| format!("Invalid JSON: {}", e))?;
-    let claude_dir = get_claude_dir()?;
-    std::fs::create_dir_all(&claude_dir).map_err(|e| e.to_string())?;
-    std::fs::write(claude_dir.join("settings.json"), &content)
-        .map_err(|e| format!("Failed to write settings.json: {}", e))
-}
-
-#[command]
-pub async fn list_claude_agents() -> Result<Vec<String>, String> {
-    let agents_dir = get_claude_dir()?.join("agents");
-    if !agents_dir.exists() {
-        return Ok(vec![]);
-    }
-    let entries = std::fs::read_dir(&agents_dir).map_err(|e| e.to_string())?;
-    let mut names: Vec<String> = entries
-        .flatten()
-        .filter(|e| e.path().is_file())
-        .filter_map(|e| e.file_name().to_str().map(String::from))
+    // Refuses content that isn't JSON, then backs up and replaces the file atomically
+    crate::settings_file::save_settings(&get_claude_dir()?.join("settings.json"), &content)
+}
+
+#[command]
+pub async fn validate_claude_settings(
+    content: String,
+) -> Result<Vec<Diagnostic>, String> {
+    crate::settings_file::validate_settings(&content)
+}
+
+#[command]
+pub async fn list_claude_settings_backups() -> Result<Vec<crate::settings_file::SettingsBackup>, String> {
+    Ok(crate::settings_file::list_backups())
+}
+
+/// Restores settings.json from a backup and returns the restored content
+#[command]
+pub async fn restore_claude_settings_backup(file_name: String) -> Result<String, String> {
+    crate::settings_file::restore_backup(&get_claude_dir()?.join("settings.json"), &file_name)
+}
+
+// Claude hooks (the "hooks" key of ~/.claude/settings.json)
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClaudeHooks {
+    pub events: Vec<HookEventEntry>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Reads settings.json and parses its hooks. Returns the raw text as well so
+/// writes can patch the hooks key without reformatting the rest of the file.
+fn read_settings_hooks() -> Result<(std::path::PathBuf, String, Vec<HookEventEntry>), String> {
+    let settings_path = get_claude_dir()?.join("settings.json");
+    let content = if settings_path.exists() {
+        std::fs::read_to_string(&settings_path)
+            .map_err(|e| format!("Failed to read settings.json: {}", e))?
+    } else {
+        "{}".to_string()
+    };
+    let settings: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("settings.json is not valid JSON: {}", e))?;
+    let events = crate::hooks::parse_hooks(settings.get("hooks"))?;
+    Ok((settings_path, content, events))
+}
+
+fn write_settings_hooks(
+    settings_path: &std::path::Path,
+    content: &str,
+    events: Vec<HookEventEntry>,
+) -> Result<ClaudeHooks, String> {
+    let hooks = crate::hooks::hooks_to_value(&events)?;
+    let updated = crate::settings_file::replace_top_level_key(content, "hooks", &hooks)?;
+    crate::settings_file::write_with_backup(settings_path, &updated)?;
+    let events: Vec<HookEventEntry> = events.into_iter().filter(|e| !e.matchers.is_empty()).collect();
+    let diagnostics = crate::hooks::validate_hooks(&events);
+    Ok(ClaudeHooks { events, diagnostics })
+}
+
+/// Validates a single matcher group before it is saved, refusing on errors
+fn check_hook_group(event: &str, group: &HookMatcher) -> Result<(), String> {
+    let entry = HookEventEntry {
+        event: event.to_string(),
+        known: true,
+        matchers: vec![group.clone()],
+    };
+    let errors: Vec<String> = crate::hooks::validate_hooks(std::slice::from_ref(&entry))
+        .into_iter()
+        .filter(Diagnostic::is_error)
+        .map(|d| d.message)
         .collect();
-    names.sort();
-    Ok(names)
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+fn find_hook_group<'a>(
+    events: &'a mut [HookEventEntry],
+    event: &str,
+    group_index: usize,
+) -> Result<&'a mut HookMatcher, String> {
+    events
+        .iter_mut()
+        .find(|e| e.event == event)
+        .and_then(|e| e.matchers.get_mut(group_index))
+        .ok_or_else(|| format!("No hook group {} for {}", group_index, event))
 }
 
 #[command]
-pub async fn read_claude_agent(name: String) -> Result<String, String> {
-    validate_filename(&name)?;
-    let path = get_claude_dir()?.join("agents").join(&name);
-    if !path.exists() {
-        return Err(format!("Agent file not found: {}", name));
+pub async fn get_claude_hooks() -> Result<ClaudeHooks, String> {
+    let (_, _, events) = read_settings_hooks()?;
+    let diagnostics = crate::hooks::validate_hooks(&events);
+    Ok(ClaudeHooks { events, diagnostics })
+}
+
+/// Adds a hook to the group with the same matcher, creating the group (and the
+/// event) when needed
+#[command]
+pub async fn add_claude_hook(
+    event: HookEvent,
+    matcher: Option<String>,
+    hook: HookCommand,
+) -> Result<ClaudeHooks, String> {
+    let (path, content, mut events) = read_settings_hooks()?;
+    let matcher = matcher.filter(|m| !m.is_empty());
+
+    let index = match events.iter().position(|e| e.event == event.name()) {
+        Some(index) => index,
+        None => {
+            events.push(HookEventEntry {
+                event: event.name().to_string(),
+                known: true,
+                matchers: vec![],
+            });
+            events.len() - 1
+        }
+    };
+    let groups = &mut events[index].matchers;
+    let group = match groups.iter().position(|g| g.matcher.as_deref().filter(|m| !m.is_empty()) == matcher.as_deref()) {
+        Some(i) => &mut groups[i],
+        None => {
+            groups.push(HookMatcher { matcher, hooks: vec![], extra: Default::default() });
+            groups.last_mut().unwrap()
+        }
+    };
+    group.hooks.push(hook);
+    check_hook_group(event.name(), group)?;
+
+    write_settings_hooks(&path, &content, events)
+}
+
+#[command]
+pub async fn update_claude_hook(
+    event: String,
+    group_index: usize,
+    hook_index: usize,
+    hook: HookCommand,
+) -> Result<ClaudeHooks, String> {
+    let (path, content, mut events) = read_settings_hooks()?;
+    let group = find_hook_group(&mut events, &event, group_index)?;
+    let slot = group
+        .hooks
+        .get_mut(hook_index)
+        .ok_or_else(|| format!("No hook {} in group {} for {}", hook_index, group_index, event))?;
+    *slot = hook;
+    check_hook_group(&event, group)?;
+    write_settings_hooks(&path, &content, events)
+}
+
+#[command]
+pub async fn set_claude_hook_matcher(
+    event: String,
+    group_index: usize,
+    matcher: Option<String>,
+) -> Result<ClaudeHooks, String> {
+    let (path, content, mut events) = read_settings_hooks()?;
+    let group = find_hook_group(&mut events, &event, group_index)?;
+    group.matcher = matcher.filter(|m| !m.is_empty());
+    check_hook_group(&event, group)?;
+    write_settings_hooks(&path, &content, events)
+}
+
+/// Removes one hook. Groups and events left empty are removed with it.
+#[command]
+pub async fn delete_claude_hook(
+    event: String,
+    group_index: usize,
+    hook_index: usize,
+) -> Result<ClaudeHooks, String> {
+    let (path, content, mut events) = read_settings_hooks()?;
+    let group = find_hook_group(&mut events, &event, group_index)?;
+    if hook_index >= group.hooks.len() {
+        return Err(format!("No hook {} in group {} for {}", hook_index, group_index, event));
     }
-    std::fs::read_to_string(&path).map_err(|e| e.to_string())
+    group.hooks.remove(hook_index);
+    if let Some(entry) = events.iter_mut().find(|e| e.event == event) {
+        entry.matchers.retain(|g| !g.hooks.is_empty());
+    }
+    write_settings_hooks(&path, &content, events)
+}
+
+/// Runs a hook command with a sample (or supplied) event payload on stdin,
+/// without claude involved, and reports its output and exit code
+#[command]
+pub async fn dry_run_claude_hook(
+    event: HookEvent,
+    hook: HookCommand,
+    payload: Option<serde_json::Value>,
+    cwd: Option<String>,
+) -> Result<crate::hooks::HookDryRunResult, String> {
+    let cwd = match cwd {
+        Some(dir) => std::path::Path::new(&dir)
+            .canonicalize()
+            .map_err(|e| format!("Invalid working directory: {}", e))?,
+        None => get_home_dir()?,
+    };
+    let payload = payload.unwrap_or_else(|| event.sample_payload(&cwd.to_string_lossy()));
+    tokio::task::spawn_blocking(move || crate::hooks::dry_run(&hook, &payload, &cwd))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Resolves the definitions directory for a scope, defaulting to ~/.claude
+fn definition_dir(scope: Option<ClaudeScope>, kind: DefinitionKind) -> Result<ScopeDir, String> {
+    ScopeDir::resolve(&scope.unwrap_or(ClaudeScope::User), kind, &get_claude_dir()?)
+}
+
+/// Parses a definition and, for agents, checks its name against the other
+/// agents in the same scope
+fn lint_definition(
+    dir: &ScopeDir,
+    kind: DefinitionKind,
+    name: &str,
+    content: &str,
+) -> Result<ParsedDefinition, String> {
+    let mut definitions = vec![crate::frontmatter::parse(kind, name, content)];
+    if kind == DefinitionKind::Agent {
+        for other in dir.list()?.into_iter().filter(|other| other != name) {
+            if let Ok(content) = dir.read(kind, &other) {
+                definitions.push(crate::frontmatter::parse(kind, &other, &content));
+            }
+        }
+        crate::frontmatter::check_duplicate_names(&mut definitions);
+    }
+    Ok(definitions.swap_remove(0))
+}
+
+/// Writes a definition unless linting finds errors that would make claude
+/// skip it. Returns the remaining warnings.
+fn save_definition(
+    dir: &ScopeDir,
+    kind: DefinitionKind,
+    name: &str,
+    content: &str,
+) -> Result<Vec<Diagnostic>, String> {
+    let parsed = lint_definition(dir, kind, name, content)?;
+    if parsed.has_errors() {
+        let errors: Vec<String> = parsed
+            .diagnostics
+            .iter()
+            .filter(|d| d.is_error())
+            .map(|d| d.message.clone())
+            .collect();
+        return Err(format!("{} was not saved: {}", name, errors.join("; ")));
+    }
+    dir.write(name, content)?;
+    Ok(parsed.diagnostics)
+}
+
+#[command]
+pub async fn list_claude_agents(scope: Option<ClaudeScope>) -> Result<Vec<String>, String> {
+    definition_dir(scope, DefinitionKind::Agent)?.list()
 }
 
 #[command]
-pub async fn write_claude_agent(name: String, content: String) -> Result<(), String> {
+pub async fn read_claude_agent(name: String, scope: Option<ClaudeScope>) -> Result<String, String> {
+    validate_filename(&name)?;
+    definition_dir(scope, DefinitionKind::Agent)?.read(DefinitionKind::Agent, &name)
+}
+
+#[command]
+pub async fn write_claude_agent(
+    name: String,
+    content: String,
+    scope: Option<ClaudeScope>,
+) -> Result<Vec<Diagnostic>, String> {
+    validate_filename(&name)?;
+    let dir = definition_dir(scope, DefinitionKind::Agent)?;
+    save_definition(&dir, DefinitionKind::Agent, &name, &content)
+}
+
+#[command]
+pub async fn delete_claude_agent(name: String, scope: Option<ClaudeScope>) -> Result<(), String> {
+    validate_filename(&name)?;
+    definition_dir(scope, DefinitionKind::Agent)?.delete(&name)
+}
+
+#[command]
+pub async fn list_claude_commands(scope: Option<ClaudeScope>) -> Result<Vec<String>, String> {
+    definition_dir(scope, DefinitionKind::Command)?.list()
+}
+
+#[command]
+pub async fn read_claude_command(name: String, scope: Option<ClaudeScope>) -> Result<String, String> {
+    validate_filename(&name)?;
+    definition_dir(scope, DefinitionKind::Command)?.read(DefinitionKind::Command, &name)
+}
+
+#[command]
+pub async fn write_claude_command(
+    name: String,
+    content: String,
+    scope: Option<ClaudeScope>,
+) -> Result<Vec<Diagnostic>, String> {
     validate_filename(&name)?;
-    let agents_dir = get_claude_dir()?.join("agents");
-    std::fs::create_dir_all(&agents_dir).map_err(|e| e.to_string())?;
-    std::fs::write(agents_dir.join(&name), &content).map_err(|e| e.to_string())
+    let dir = definition_dir(scope, DefinitionKind::Command)?;
+    save_definition(&dir, DefinitionKind::Command, &name, &content)
 }
 
 #[command]
-pub async fn delete_claude_agent(name: String) -> Result<(), String> {
+pub async fn delete_claude_command(name: String, scope: Option<ClaudeScope>) -> Result<(), String> {
     validate_filename(&name)?;
-    let path = get_claude_dir()?.join("agents").join(&name);
-    if path.exists() {
-        std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+    definition_dir(scope, DefinitionKind::Command)?.delete(&name)
+}
+
+/// Parses and lints unsaved editor content for an agent or command
+#[command]
+pub async fn lint_claude_definition(
+    kind: DefinitionKind,
+    name: String,
+    content: String,
+    scope: Option<ClaudeScope>,
+) -> Result<ParsedDefinition, String> {
+    validate_filename(&name)?;
+    lint_definition(&definition_dir(scope, kind)?, kind, &name, &content)
+}
+
+/// Parses and lints every agent or command in a scope
+#[command]
+pub async fn lint_claude_definitions(
+    kind: DefinitionKind,
+    scope: Option<ClaudeScope>,
+) -> Result<Vec<ParsedDefinition>, String> {
+    let dir = definition_dir(scope, kind)?;
+    let mut definitions = Vec::new();
+    for name in dir.list()? {
+        let content = dir.read(kind, &name)?;
+        definitions.push(crate::frontmatter::parse(kind, &name, &content));
     }
-    Ok(())
+    if kind == DefinitionKind::Agent {
+        crate::frontmatter::check_duplicate_names(&mut definitions);
+    }
+    Ok(definitions)
 }
 
+/// Lists agents or commands from every scope that applies to a project, in
+/// claude's precedence order (project-local, project, user)
 #[command]
-pub async fn list_claude_commands() -> Result<Vec<String>, String> {
-    let commands_dir = get_claude_dir()?.join("commands");
-    if !commands_dir.exists() {
-        return Ok(vec![]);
+pub async fn list_claude_definitions(
+    kind: DefinitionKind,
+    project_path: Option<String>,
+) -> Result<Vec<DefinitionEntry>, String> {
+    let mut scopes = Vec::new();
+    if let Some(path) = project_path {
+        let local = ClaudeScope::ProjectLocal { path: path.clone() };
+        // Projects outside git have no project-local scope
+        if ScopeDir::resolve(&local, kind, &get_claude_dir()?).is_ok() {
+            scopes.push(local);
+        }
+        scopes.push(ClaudeScope::Project { path });
     }
-    let entries = std::fs::read_dir(&commands_dir).map_err(|e| e.to_string())?;
-    let mut names: Vec<String> = entries
-        .flatten()
-        .filter(|e| e.path().is_file())
-        .filter_map(|e| e.file_name().to_str().map(String::from))
-        .collect();
-    names.sort();
-    Ok(names)
+    scopes.push(ClaudeScope::User);
+    crate::claude_dir::list_scopes(kind, &scopes, &get_claude_dir()?)
 }
 
 #[command]
-pub async fn read_claude_command(name: String) -> Result<String, String> {
+pub async fn diff_claude_definition(
+    kind: DefinitionKind,
+    name: String,
+    from: ClaudeScope,
+    to: ClaudeScope,
+) -> Result<DefinitionDiff, String> {
     validate_filename(&name)?;
-    let path = get_claude_dir()?.join("commands").join(&name);
-    if !path.exists() {
-        return Err(format!("Command file not found: {}", name));
-    }
-    std::fs::read_to_string(&path).map_err(|e| e.to_string())
+    let from = definition_dir(Some(from), kind)?;
+    let to = definition_dir(Some(to), kind)?;
+    Ok(crate::claude_dir::diff(&name, &from, &to))
 }
 
 #[command]
-pub async fn write_claude_command(name: String, content: String) -> Result<(), String> {
+pub async fn copy_claude_definition(
+    kind: DefinitionKind,
+    name: String,
+    from: ClaudeScope,
+    to: ClaudeScope,
+    overwrite: Option<bool>,
+) -> Result<(), String> {
     validate_filename(&name)?;
-    let commands_dir = get_claude_dir()?.join("commands");
-    std::fs::create_dir_all(&commands_dir).map_err(|e| e.to_string())?;
-    std::fs::write(commands_dir.join(&name), &content).map_err(|e| e.to_string())
+    let from = definition_dir(Some(from), kind)?;
+    let to = definition_dir(Some(to), kind)?;
+    crate::claude_dir::transfer(&name, &from, &to, overwrite.unwrap_or(false), false)
 }
 
+/// Moves a definition to another scope, e.g. sharing a project-local agent
+/// with the repository or making a project command available everywhere
 #[command]
-pub async fn delete_claude_command(name: String) -> Result<(), String> {
+pub async fn promote_claude_definition(
+    kind: DefinitionKind,
+    name: String,
+    from: ClaudeScope,
+    to: ClaudeScope,
+    overwrite: Option<bool>,
+) -> Result<(), String> {
     validate_filename(&name)?;
-    let path = get_claude_dir()?.join("commands").join(&name);
-    if path.exists() {
-        std::fs::remove_file(&path).map_err(|e| e.to_string())?;
-    }
-    Ok(())
+    let from = definition_dir(Some(from), kind)?;
+    let to = definition_dir(Some(to), kind)?;
+    crate::claude_dir::transfer(&name, &from, &to, overwrite.unwrap_or(false), true)
 }
 
-// Agent Teams (multi-agent orchestration)
+// CLAUDE.md memory files
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct TeamMember {
-    pub agent_id: String,
-    pub name: String,
-    pub agent_type: String,
-    pub model: Option<String>,
-    pub joined_at: Option<u64>,
-    pub cwd: Option<String>,
+/// Lists the memory files that apply to a directory and the merged context
+/// claude would load there, with `@path` imports resolved
+#[command]
+pub async fn get_memory_preview(directory: String) -> Result<crate::memory::MemoryPreview, String> {
+    let home = get_home_dir()?;
+    tokio::task::spawn_blocking(move || crate::memory::preview(std::path::Path::new(&directory), &home))
+        .await
+        .map_err(|e| e.to_string())?
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct TeamConfig {
-    pub name: String,
-    pub description: Option<String>,
-    pub created_at: Option<u64>,
-    pub lead_agent_id: Option<String>,
-    pub members: Vec<TeamMember>,
+#[command]
+pub async fn read_memory_file(path: String) -> Result<crate::memory::MemoryFileContent, String> {
+    crate::memory::read(std::path::Path::new(&path), &get_home_dir()?)
+}
+
+/// Saves a memory file. `expected_modified_at` comes from the last read and
+/// makes the save fail instead of overwriting changes made elsewhere.
+#[command]
+pub async fn write_memory_file(
+    path: String,
+    content: String,
+    expected_modified_at: Option<String>,
+) -> Result<crate::memory::MemoryFileContent, String> {
+    let home = get_home_dir()?;
+    crate::memory::write(std::path::Path::new(&path), &home, &content, expected_modified_at.as_deref())
+}
+
+// MCP servers (~/.claude.json and project .mcp.json)
+
+/// Resolves the config file for a scope and the directory its servers run in
+fn mcp_scope_paths(scope: &McpScope) -> Result<(std::path::PathBuf, std::path::PathBuf), String> {
+    let home = get_home_dir()?;
+    match scope {
+        McpScope::User => Ok((crate::mcp::servers_file(scope, &home), home)),
+        McpScope::Project { path } => {
+            let root = std::path::Path::new(path)
+                .canonicalize()
+                .map_err(|e| format!("Invalid project path: {}", e))?;
+            if !root.is_dir() {
+                return Err("Project path is not a directory".to_string());
+            }
+            Ok((root.join(".mcp.json"), root))
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct TeamInfo {
-    pub dir_name: String,
-    pub config: TeamConfig,
-    pub task_count: Option<u32>,
+/// Runs validation and turns any errors into a single error message. Warnings
+/// are returned so the caller can still show them after a successful save.
+fn check_mcp_server(
+    name: &str,
+    config: &McpServerConfig,
+    cwd: &std::path::Path,
+) -> Result<Vec<Diagnostic>, String> {
+    let diagnostics = crate::mcp::validate_server(name, config, Some(cwd));
+    let errors: Vec<&str> = diagnostics
+        .iter()
+        .filter(|d| d.is_error())
+        .map(|d| d.message.as_str())
+        .collect();
+    if !errors.is_empty() {
+        return Err(errors.join("; "));
+    }
+    Ok(diagnostics)
 }
 
 #[command]
-pub async fn get_active_teams() -> Result<Vec<TeamInfo>, String> {
-    let home = if cfg!(target_os = "windows") {
-        std::env::var("USERPROFILE").map_err(|_| "USERPROFILE not set".to_string())?
-    } else {
-        std::env::var("HOME").map_err(|_| "HOME not set".to_string())?
+pub async fn list_mcp_servers(
+    state: State<'_, AppState>,
+    project_path: Option<String>,
+) -> Result<Vec<McpServerEntry>, String> {
+    let mut entries = Vec::new();
+
+    let (user_file, _) = mcp_scope_paths(&McpScope::User)?;
+    for (name, config) in crate::mcp::read_servers(&user_file)? {
+        entries.push(McpServerEntry { name, scope: McpScope::User, enabled: true, config });
+    }
+    let disabled = {
+        let db = state.db.lock().await;
+        db.get_disabled_mcp_servers()?
     };
+    for (name, value) in disabled {
+        if let Ok(config) = serde_json::from_value::<McpServerConfig>(value) {
+            entries.push(McpServerEntry { name, scope: McpScope::User, enabled: false, config });
+        }
+    }
 
-    let teams_dir = std::path::Path::new(&home).join(".claude").join("teams");
-    if !teams_dir.exists() {
-        return Ok(vec![]);
+    if let Some(path) = project_path {
+        let scope = McpScope::Project { path };
+        let (project_file, root) = mcp_scope_paths(&scope)?;
+        let local_settings = crate::mcp::read_json_object(&crate::mcp::project_local_settings(
+            &root.to_string_lossy(),
+        ))?;
+        let disabled = crate::mcp::read_name_list(&local_settings, "disabledMcpjsonServers");
+        for (name, config) in crate::mcp::read_servers(&project_file)? {
+            let enabled = !disabled.contains(&name);
+            entries.push(McpServerEntry { name, scope: scope.clone(), enabled, config });
+        }
     }
 
-    let entries = std::fs::read_dir(&teams_dir).map_err(|e| e.to_string())?;
-    let mut teams = Vec::new();
+    Ok(entries)
+}
 
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if !path.is_dir() {
-            continue;
+#[command]
+pub async fn validate_mcp_server(
+    scope: McpScope,
+    name: String,
+    config: McpServerConfig,
+) -> Result<Vec<Diagnostic>, String> {
+    let (_, cwd) = mcp_scope_paths(&scope)?;
+    Ok(crate::mcp::validate_server(&name, &config, Some(&cwd)))
+}
+
+#[command]
+pub async fn add_mcp_server(
+    state: State<'_, AppState>,
+    scope: McpScope,
+    name: String,
+    config: McpServerConfig,
+) -> Result<Vec<Diagnostic>, String> {
+    let (file, cwd) = mcp_scope_paths(&scope)?;
+    let diagnostics = check_mcp_server(&name, &config, &cwd)?;
+    if scope == McpScope::User {
+        let db = state.db.lock().await;
+        if db.get_disabled_mcp_servers()?.iter().any(|(n, _)| *n == name) {
+            return Err(format!("MCP server \"{}\" already exists (disabled)", name));
         }
+    }
+    let value = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+    crate::mcp::update_servers(&file, |servers| {
+        if servers.contains_key(&name) {
+            return Err(format!("MCP server \"{}\" already exists", name));
+        }
+        servers.insert(name.clone(), value);
+        Ok(())
+    })?;
+    Ok(diagnostics)
+}
 
-        let config_path = path.join("config.json");
-        if !config_path.exists() {
-            continue;
+#[command]
+pub async fn update_mcp_server(
+    state: State<'_, AppState>,
+    scope: McpScope,
+    name: String,
+    config: McpServerConfig,
+) -> Result<Vec<Diagnostic>, String> {
+    let (file, cwd) = mcp_scope_paths(&scope)?;
+    let diagnostics = check_mcp_server(&name, &config, &cwd)?;
+    let value = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+
+    if scope == McpScope::User {
+        let db = state.db.lock().await;
+        if db.get_disabled_mcp_servers()?.iter().any(|(n, _)| *n == name) {
+            db.save_disabled_mcp_server(&name, &value)?;
+            return Ok(diagnostics);
+        }
+    }
+    crate::mcp::update_servers(&file, |servers| match servers.get_mut(&name) {
+        Some(existing) => {
+            *existing = value;
+            Ok(())
         }
+        None => Err(format!("MCP server \"{}\" not found", name)),
+    })?;
+    Ok(diagnostics)
+}
 
-        let config_str = match std::fs::read_to_string(&config_path) {
-            Ok(s) => s,
-            Err(_) => continue,
-        };
+#[command]
+pub async fn remove_mcp_server(
+    state: State<'_, AppState>,
+    scope: McpScope,
+    name: String,
+) -> Result<(), String> {
+    let (file, _) = mcp_scope_paths(&scope)?;
+    if scope == McpScope::User {
+        let db = state.db.lock().await;
+        db.delete_disabled_mcp_server(&name)?;
+    }
+    if crate::mcp::read_servers(&file)?.iter().any(|(n, _)| *n == name) {
+        crate::mcp::update_servers(&file, |servers| {
+            servers.remove(&name);
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
 
-        let config: TeamConfig = match serde_json::from_str(&config_str) {
-            Ok(c) => c,
-            Err(_) => continue,
-        };
+/// Enables or disables a server. User servers are moved out of `~/.claude.json`
+/// into the app database while disabled; project servers are toggled in the
+/// project's `.claude/settings.local.json` so the shared `.mcp.json` is untouched.
+#[command]
+pub async fn set_mcp_server_enabled(
+    state: State<'_, AppState>,
+    scope: McpScope,
+    name: String,
+    enabled: bool,
+) -> Result<(), String> {
+    let (file, root) = mcp_scope_paths(&scope)?;
+    match scope {
+        McpScope::Project { .. } => {
+            if !crate::mcp::read_servers(&file)?.iter().any(|(n, _)| *n == name) {
+                return Err(format!("MCP server \"{}\" not found", name));
+            }
+            crate::mcp::set_project_server_enabled(&root.to_string_lossy(), &name, enabled)
+        }
+        McpScope::User => {
+            let db = state.db.lock().await;
+            if enabled {
+                let value = db
+                    .get_disabled_mcp_servers()?
+                    .into_iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| v);
+                let Some(value) = value else {
+                    return Ok(());
+                };
+                crate::mcp::update_servers(&file, |servers| {
+                    servers.insert(name.clone(), value);
+                    Ok(())
+                })?;
+                db.delete_disabled_mcp_server(&name)
+            } else {
+                let mut removed = None;
+                crate::mcp::update_servers(&file, |servers| {
+                    removed = servers.remove(&name);
+                    Ok(())
+                })?;
+                match removed {
+                    Some(value) => db.save_disabled_mcp_server(&name, &value),
+                    None => Err(format!("MCP server \"{}\" not found", name)),
+                }
+            }
+        }
+    }
+}
 
-        let dir_name = entry.file_name().to_string_lossy().to_string();
+/// Starts a stdio server locally, performs the MCP initialize handshake and
+/// reports the tools it exposes
+#[command]
+pub async fn test_mcp_server(
+    scope: McpScope,
+    config: McpServerConfig,
+) -> Result<crate::mcp::McpTestResult, String> {
+    let (_, cwd) = mcp_scope_paths(&scope)?;
+    tokio::task::spawn_blocking(move || crate::mcp::test_launch(&config, Some(&cwd)))
+        .await
+        .map_err(|e| e.to_string())?
+}
 
-        // Read task count from .highwatermark
-        let tasks_dir = std::path::Path::new(&home)
-            .join(".claude")
-            .join("tasks")
-            .join(&dir_name);
-        let hwm_path = tasks_dir.join(".highwatermark");
-        let task_count = std::fs::read_to_string(&hwm_path)
-            .ok()
-            .and_then(|s| s.trim().parse::<u32>().ok());
+// Agent Teams (multi-agent orchestration)
 
-        teams.push(TeamInfo {
-            dir_name,
-            config,
-            task_count,
-        });
+#[command]
+pub async fn get_active_teams(state: State<'_, AppState>) -> Result<Vec<TeamInfo>, String> {
+    let mut teams = crate::teams::read_teams(&get_claude_dir()?)?;
+    let terminals: Vec<TerminalProcess> = {
+        let manager = state.terminals.lock().await;
+        manager
+            .get_processes()
+            .into_iter()
+            .filter(|(config, _)| config.kind == crate::terminal::TerminalKind::Claude)
+            .map(|(config, pid)| TerminalProcess {
+                terminal_id: config.id,
+                working_directory: config.working_directory,
+                created_at_ms: config.created_at.timestamp_millis(),
+                pid,
+            })
+            .collect()
+    };
+    if !terminals.is_empty() {
+        // Walking the process table shells out to `ps`, so keep it off the async runtime
+        teams = tokio::task::spawn_blocking(move || {
+            crate::teams::map_members(&mut teams, &terminals);
+            teams
+        })
+        .await
+        .map_err(|e| e.to_string())?;
     }
-
     Ok(teams)
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JoinTeamRequest {
+    /// Directory name of the team under ~/.claude/teams
+    pub team: String,
+    /// Member name; the agent id becomes `<name>@<team>`
+    pub name: String,
+    pub agent_type: Option<String>,
+    pub model: Option<String>,
+    /// Defaults to the team lead's working directory
+    pub working_directory: Option<String>,
+    pub color_tag: Option<String>,
+}
+
+/// Spawns a terminal running claude as a new member of an existing team
+#[command]
+pub async fn join_team_terminal(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    request: JoinTeamRequest,
+) -> Result<crate::terminal::TerminalConfig, String> {
+    let valid_name = |s: &str| {
+        !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+    if !valid_name(&request.name) {
+        return Err("Member names may only contain letters, digits, '-' and '_'".to_string());
+    }
+    let team = crate::teams::read_teams(&get_claude_dir()?)?
+        .into_iter()
+        .find(|t| t.dir_name == request.team)
+        .ok_or_else(|| format!("Team not found: {}", request.team))?;
+    if !valid_name(&team.config.name) {
+        return Err(format!("Team name \"{}\" can't be passed to claude safely", team.config.name));
+    }
+    let agent_id = format!("{}@{}", request.name, team.config.name);
+    if team.config.members.iter().any(|m| m.agent_id == agent_id || m.name == request.name) {
+        return Err(format!("{} is already a member of {}", request.name, team.config.name));
+    }
+
+    let lead = team
+        .config
+        .members
+        .iter()
+        .find(|m| Some(&m.agent_id) == team.config.lead_agent_id.as_ref())
+        .or(team.config.members.first());
+    let working_directory = request
+        .working_directory
+        .or_else(|| lead.and_then(|m| m.cwd.clone()))
+        .ok_or("No working directory given and the team lead has none")?;
+
+    let mut env_vars = HashMap::new();
+    env_vars.insert("CLAUDE_CODE_EXPERIMENTAL_AGENT_TEAMS".to_string(), "1".to_string());
+    let create = CreateTerminalRequest {
+        label: format!("{} ({})", request.name, team.config.name),
+        working_directory,
+        claude_args: crate::teams::member_args(
+            &team.config.name,
+            &request.name,
+            request.agent_type.as_deref(),
+            request.model.as_deref(),
+        ),
+        env_vars,
+        color_tag: request.color_tag,
+        nickname: Some(request.name),
+        kind: crate::terminal::TerminalKind::Claude,
+        profile_id: None,
+        permission_policy_id: None,
+        worktree: false,
+        worktree_branch: None,
+    };
+    spawn_terminal(&app, &state, create).await
+}
+
+/// Returns every task of a team with its owner, status and dependencies
+#[command]
+pub async fn get_team_tasks(team: String) -> Result<TeamTaskList, String> {
+    crate::teams::read_tasks(&get_claude_dir()?, &team)
+}
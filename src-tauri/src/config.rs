@@ -10,6 +10,9 @@ pub struct ConfigProfile {
     pub claude_args: Vec<String>,
     pub env_vars: HashMap<String, String>,
     pub is_default: bool,
+    /// Permission policy applied to terminals launched from this profile
+    #[serde(default)]
+    pub permission_policy_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
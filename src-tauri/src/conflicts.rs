@@ -0,0 +1,347 @@
+use crate::attribution::ChangeTracker;
+use crate::changes::{DiffLineKind, FileChange};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlapKind {
+    /// Both terminals changed the file
+    Path,
+    /// Their changes touch the same lines, in worktrees at the same commit
+    Hunk,
+}
+
+/// Inclusive range of lines, numbered as in the file before the changes
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct LineRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConflictFile {
+    /// Path relative to the worktree root
+    pub path: String,
+    pub overlap: OverlapKind,
+    /// Lines both terminals changed, for hunk overlaps
+    pub ranges: Vec<LineRange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConflictSide {
+    pub terminal_id: String,
+    pub label: String,
+    pub worktree: String,
+}
+
+/// Uncommitted changes of two live terminals in one repository that overlap
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TerminalConflict {
+    /// Git directory shared by the terminals' worktrees
+    pub repository: String,
+    pub terminals: [ConflictSide; 2],
+    /// Both terminals run in the same working tree. Their changes can't be
+    /// told apart by diff there, so files count when each terminal was the
+    /// only active one during some change to them, and overlaps are per path.
+    pub shared_worktree: bool,
+    pub files: Vec<ConflictFile>,
+}
+
+struct TerminalSnapshot {
+    label: String,
+    repository: PathBuf,
+    worktree: PathBuf,
+    /// Commit the worktree has checked out, which line numbers refer to
+    base: Option<String>,
+    /// Changed paths with the lines their hunks cover
+    files: HashMap<String, Vec<LineRange>>,
+}
+
+/// Terminal ids of a pair, smallest first
+type Pair = (String, String);
+
+#[derive(Default)]
+struct MonitorState {
+    terminals: HashMap<String, TerminalSnapshot>,
+    /// Overlapping files last reported for each pair of terminals
+    reported: HashMap<Pair, HashSet<(String, OverlapKind)>>,
+}
+
+/// Compares the change sets of running terminals to find ones working on
+/// the same files
+#[derive(Clone)]
+pub struct ConflictMonitor {
+    state: Arc<std::sync::Mutex<MonitorState>>,
+    tracker: ChangeTracker,
+}
+
+impl ConflictMonitor {
+    pub fn new(tracker: ChangeTracker) -> Self {
+        ConflictMonitor {
+            state: Arc::new(std::sync::Mutex::new(MonitorState::default())),
+            tracker,
+        }
+    }
+
+    /// Records a terminal's current changes and returns its conflicts that
+    /// have files not reported before
+    pub fn update(
+        &self,
+        terminal_id: &str,
+        label: String,
+        repository: PathBuf,
+        worktree: PathBuf,
+        base: Option<String>,
+        changes: &[FileChange],
+    ) -> Vec<TerminalConflict> {
+        let worktree = worktree.canonicalize().unwrap_or(worktree);
+        let changed: HashSet<PathBuf> = changes.iter().map(|c| worktree.join(&c.path)).collect();
+        self.tracker.retain_sole_changes(terminal_id, &changed);
+        let snapshot = TerminalSnapshot {
+            label,
+            repository,
+            worktree,
+            base,
+            files: changes.iter().map(|c| (c.path.clone(), changed_lines(c))).collect(),
+        };
+        let mut state = self.state.lock().unwrap();
+        state.terminals.insert(terminal_id.to_string(), snapshot);
+
+        let mut others: Vec<&String> = state.terminals.keys().filter(|id| *id != terminal_id).collect();
+        others.sort();
+        let current: Vec<TerminalConflict> = others
+            .into_iter()
+            .filter_map(|other| self.conflict(&state, terminal_id, other))
+            .collect();
+
+        let mut fresh = Vec::new();
+        let mut pairs = HashSet::new();
+        for conflict in current {
+            let pair = pair(&conflict.terminals[0].terminal_id, &conflict.terminals[1].terminal_id);
+            let files: HashSet<(String, OverlapKind)> =
+                conflict.files.iter().map(|f| (f.path.clone(), f.overlap)).collect();
+            let previous = state.reported.insert(pair.clone(), files.clone()).unwrap_or_default();
+            if !files.is_subset(&previous) {
+                fresh.push(conflict);
+            }
+            pairs.insert(pair);
+        }
+        // Pairs that stopped overlapping are reported again if they start over
+        state
+            .reported
+            .retain(|pair, _| (pair.0 != terminal_id && pair.1 != terminal_id) || pairs.contains(pair));
+        fresh
+    }
+
+    /// Forgets a terminal that has stopped
+    pub fn remove(&self, terminal_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.terminals.remove(terminal_id);
+        state.reported.retain(|pair, _| pair.0 != terminal_id && pair.1 != terminal_id);
+    }
+
+    /// Every pair of terminals whose changes overlap right now
+    pub fn conflicts(&self) -> Vec<TerminalConflict> {
+        let state = self.state.lock().unwrap();
+        let mut ids: Vec<&String> = state.terminals.keys().collect();
+        ids.sort();
+        let mut conflicts = Vec::new();
+        for (i, a) in ids.iter().enumerate() {
+            for b in &ids[i + 1..] {
+                conflicts.extend(self.conflict(&state, a, b));
+            }
+        }
+        conflicts
+    }
+
+    fn conflict(&self, state: &MonitorState, a: &str, b: &str) -> Option<TerminalConflict> {
+        let (a, b) = if a <= b { (a, b) } else { (b, a) };
+        let (first, second) = (state.terminals.get(a)?, state.terminals.get(b)?);
+        if first.repository != second.repository {
+            return None;
+        }
+        let shared_worktree = first.worktree == second.worktree;
+
+        let mut paths: Vec<&String> = first.files.keys().collect();
+        paths.sort();
+        let mut files = Vec::new();
+        if shared_worktree {
+            let (ours, theirs) = (self.tracker.sole_changes(a), self.tracker.sole_changes(b));
+            for path in paths {
+                let full = first.worktree.join(path);
+                if ours.contains(&full) && theirs.contains(&full) {
+                    files.push(ConflictFile {
+                        path: path.clone(),
+                        overlap: OverlapKind::Path,
+                        ranges: vec![],
+                    });
+                }
+            }
+        } else {
+            // Line numbers only line up when both start from the same commit
+            let same_base = first.base.is_some() && first.base == second.base;
+            for path in paths {
+                let Some(their_lines) = second.files.get(path) else {
+                    continue;
+                };
+                let ranges = if same_base { intersect(&first.files[path], their_lines) } else { vec![] };
+                files.push(ConflictFile {
+                    path: path.clone(),
+                    overlap: if ranges.is_empty() { OverlapKind::Path } else { OverlapKind::Hunk },
+                    ranges,
+                });
+            }
+        }
+        if files.is_empty() {
+            return None;
+        }
+
+        let side = |id: &str, snapshot: &TerminalSnapshot| ConflictSide {
+            terminal_id: id.to_string(),
+            label: snapshot.label.clone(),
+            worktree: snapshot.worktree.to_string_lossy().to_string(),
+        };
+        Some(TerminalConflict {
+            repository: first.repository.to_string_lossy().to_string(),
+            terminals: [side(a, first), side(b, second)],
+            shared_worktree,
+            files,
+        })
+    }
+}
+
+fn pair(a: &str, b: &str) -> Pair {
+    if a <= b {
+        (a.to_string(), b.to_string())
+    } else {
+        (b.to_string(), a.to_string())
+    }
+}
+
+/// Lines of the original file each run of added and removed lines replaces,
+/// leaving out context. An insertion counts as the line it follows, so
+/// insertions at one spot, or next to a changed line, overlap.
+fn changed_lines(change: &FileChange) -> Vec<LineRange> {
+    let mut ranges = Vec::new();
+    for hunk in &change.hunks {
+        let mut previous = hunk.old_start.saturating_sub(1);
+        let mut run: Option<LineRange> = None;
+        for line in &hunk.lines {
+            match line.kind {
+                DiffLineKind::Context => {
+                    ranges.extend(run.take());
+                    previous = line.old_line.unwrap_or(previous);
+                }
+                DiffLineKind::Removed => {
+                    let old = line.old_line.unwrap_or(previous + 1);
+                    let start = run.map_or(old, |r| r.start);
+                    run = Some(LineRange { start, end: old });
+                    previous = old;
+                }
+                DiffLineKind::Added => {
+                    let at = previous.max(1);
+                    run.get_or_insert(LineRange { start: at, end: at });
+                }
+            }
+        }
+        ranges.extend(run);
+    }
+    ranges
+}
+
+fn intersect(ours: &[LineRange], theirs: &[LineRange]) -> Vec<LineRange> {
+    let mut ranges: Vec<LineRange> = ours
+        .iter()
+        .flat_map(|a| {
+            theirs.iter().filter_map(move |b| {
+                let (start, end) = (a.start.max(b.start), a.end.min(b.end));
+                (start <= end).then_some(LineRange { start, end })
+            })
+        })
+        .collect();
+    ranges.sort_by_key(|r| (r.start, r.end));
+    ranges.dedup();
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::changes::{DiffHunk, DiffLine};
+
+    fn line(kind: DiffLineKind, old_line: Option<u32>, new_line: Option<u32>) -> DiffLine {
+        DiffLine { kind, content: String::new(), old_line, new_line }
+    }
+
+    fn change(hunks: Vec<(u32, Vec<DiffLine>)>) -> FileChange {
+        FileChange {
+            path: "file.txt".to_string(),
+            status: "modified".to_string(),
+            index_status: None,
+            worktree_status: None,
+            original_path: None,
+            added: 0,
+            removed: 0,
+            binary: false,
+            hunks: hunks
+                .into_iter()
+                .map(|(old_start, lines)| DiffHunk {
+                    header: String::new(),
+                    old_start,
+                    old_lines: 0,
+                    new_start: old_start,
+                    new_lines: 0,
+                    staged: false,
+                    lines,
+                })
+                .collect(),
+            truncated: false,
+        }
+    }
+
+    fn range(start: u32, end: u32) -> LineRange {
+        LineRange { start, end }
+    }
+
+    #[test]
+    fn changed_lines_leave_out_context() {
+        use DiffLineKind::*;
+        let change = change(vec![(
+            3,
+            vec![
+                line(Context, Some(3), Some(3)),
+                line(Removed, Some(4), None),
+                line(Removed, Some(5), None),
+                line(Added, None, Some(4)),
+                line(Context, Some(6), Some(5)),
+                line(Context, Some(7), Some(6)),
+                line(Removed, Some(8), None),
+                line(Context, Some(9), Some(7)),
+            ],
+        )]);
+        assert_eq!(changed_lines(&change), vec![range(4, 5), range(8, 8)]);
+    }
+
+    #[test]
+    fn insertions_count_as_the_line_they_follow() {
+        use DiffLineKind::*;
+        let change = change(vec![
+            (10, vec![line(Context, Some(10), Some(10)), line(Added, None, Some(11)), line(Added, None, Some(12))]),
+            // At the very top of the file
+            (0, vec![line(Added, None, Some(1))]),
+        ]);
+        assert_eq!(changed_lines(&change), vec![range(10, 10), range(1, 1)]);
+    }
+
+    #[test]
+    fn intersect_keeps_shared_lines() {
+        let ours = [range(1, 5), range(10, 12), range(20, 20)];
+        let theirs = [range(4, 11), range(20, 25), range(30, 31)];
+        assert_eq!(intersect(&ours, &theirs), vec![range(4, 5), range(10, 11), range(20, 20)]);
+        assert!(intersect(&[range(1, 2)], &[range(3, 4)]).is_empty());
+        assert_eq!(intersect(&[range(1, 3), range(2, 4)], &[range(2, 3)]), vec![range(2, 3)]);
+    }
+}
@@ -1,8 +1,13 @@
+use crate::attribution::{AttributedChange, FileChangeEntry, FileChangeKind, FileSession, SessionFile};
+use crate::auto_approve::{AutoApproveAuditEntry, AutoApproveRule, Decision, RuleMatch};
+use crate::bundle::ImportPlan;
 use crate::config::ConfigProfile;
+use crate::policy::PermissionPolicy;
 use crate::terminal::TerminalConfig;
 use rusqlite::{params, Connection};
 use directories::ProjectDirs;
 use serde::{Serialize, Deserialize};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SessionHistoryEntry {
@@ -30,68 +35,366 @@ pub struct WorkspaceInfo {
     pub created_at: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkspaceRevision {
+    pub revision: i64,
+    pub terminal_count: usize,
+    pub created_at: String,
+}
+
+/// Number of revisions kept per workspace; older ones are pruned on save
+const MAX_WORKSPACE_REVISIONS: i64 = 50;
+
+/// Schema migrations, applied in order inside a transaction each. Entry `i`
+/// upgrades a database from `user_version` i to i + 1. Released migrations must
+/// never be edited; append a new one instead.
+const MIGRATIONS: &[&str] = &[
+    // 1: initial schema. Databases created before versioning already match it,
+    // which is why every statement tolerates existing objects.
+    "
+    CREATE TABLE IF NOT EXISTS profiles (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        description TEXT,
+        working_directory TEXT NOT NULL,
+        claude_args TEXT NOT NULL,
+        env_vars TEXT NOT NULL,
+        is_default INTEGER DEFAULT 0
+    );
+
+    CREATE TABLE IF NOT EXISTS workspaces (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT UNIQUE NOT NULL,
+        terminals TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS session_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        terminal_id TEXT NOT NULL,
+        label TEXT NOT NULL,
+        started_at TEXT NOT NULL,
+        ended_at TEXT,
+        log_path TEXT
+    );
+
+    CREATE TABLE IF NOT EXISTS snippets (
+        id TEXT PRIMARY KEY,
+        title TEXT NOT NULL,
+        content TEXT NOT NULL,
+        category TEXT NOT NULL DEFAULT 'General',
+        created_at TEXT NOT NULL
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_profiles_name ON profiles(name);
+    CREATE INDEX IF NOT EXISTS idx_workspaces_name ON workspaces(name);
+    CREATE INDEX IF NOT EXISTS idx_session_history_terminal_id ON session_history(terminal_id);
+    CREATE INDEX IF NOT EXISTS idx_snippets_category ON snippets(category);
+    ",
+    // 2: workspace revision history
+    "
+    CREATE TABLE IF NOT EXISTS workspace_revisions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        workspace_name TEXT NOT NULL,
+        revision INTEGER NOT NULL,
+        terminals TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        UNIQUE(workspace_name, revision)
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_workspace_revisions_name ON workspace_revisions(workspace_name);
+    ",
+    // 3: user-level MCP servers parked while disabled (claude has no disabled flag)
+    "
+    CREATE TABLE disabled_mcp_servers (
+        name TEXT PRIMARY KEY,
+        config TEXT NOT NULL,
+        disabled_at TEXT NOT NULL
+    );
+    ",
+    // 4: permission policies, attachable to profiles
+    "
+    CREATE TABLE permission_policies (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        policy TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+
+    ALTER TABLE profiles ADD COLUMN permission_policy_id TEXT;
+    ",
+    // 5: auto-approve rules for permission prompts and their audit trail
+    "
+    CREATE TABLE auto_approve_rules (
+        id TEXT PRIMARY KEY,
+        rule TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+
+    CREATE TABLE auto_approve_audit (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        terminal_id TEXT NOT NULL,
+        prompt_text TEXT NOT NULL,
+        rule_id TEXT NOT NULL,
+        rule_name TEXT NOT NULL,
+        decision TEXT NOT NULL,
+        decided_at TEXT NOT NULL
+    );
+
+    CREATE INDEX idx_auto_approve_audit_terminal_id ON auto_approve_audit(terminal_id);
+    ",
+    // 6: file changes attributed to sessions
+    "
+    CREATE TABLE session_file_changes (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        session_id INTEGER NOT NULL,
+        path TEXT NOT NULL,
+        change TEXT NOT NULL,
+        changed_at TEXT NOT NULL,
+        shared INTEGER NOT NULL DEFAULT 0
+    );
+
+    CREATE INDEX idx_session_file_changes_session_id ON session_file_changes(session_id);
+    CREATE INDEX idx_session_file_changes_path ON session_file_changes(path);
+    ",
+    // 7: project files the user has reviewed, with the content they saw
+    "
+    CREATE TABLE trusted_project_files (
+        path TEXT PRIMARY KEY,
+        content TEXT NOT NULL,
+        trusted_at TEXT NOT NULL
+    );
+    ",
+];
+
+const DB_FILE_NAME: &str = "claudeterminal.db";
+
+/// Number of pre-migration backups kept in the backups directory
+const MAX_DATABASE_BACKUPS: usize = 5;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseBackup {
+    pub file_name: String,
+    pub size: u64,
+    pub modified_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseStatus {
+    /// False when the app is running on a temporary in-memory database
+    pub persistent: bool,
+    /// Why the on-disk database could not be opened, if it couldn't
+    pub error: Option<String>,
+    pub schema_version: i64,
+    pub latest_schema_version: i64,
+    pub backups: Vec<DatabaseBackup>,
+}
+
 pub struct Database {
     conn: Connection,
+    /// Set when the on-disk database failed to open and `conn` is an in-memory fallback
+    open_error: Option<String>,
 }
 
 impl Database {
     pub fn new() -> Result<Self, String> {
-        let data_dir = ProjectDirs::from("com", "claudeterminal", "ClaudeTerminal")
+        let data_dir = Self::data_dir()?;
+        std::fs::create_dir_all(&data_dir).map_err(|e| e.to_string())?;
+
+        let db_path = data_dir.join(DB_FILE_NAME);
+        let mut conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+
+        conn.execute_batch("PRAGMA journal_mode=WAL;")
+            .map_err(|e| e.to_string())?;
+        Self::migrate(&mut conn, Some(&data_dir))?;
+
+        Ok(Self { conn, open_error: None })
+    }
+
+    /// Opens the on-disk database, falling back to an in-memory one when that
+    /// fails so the app can still start and report the problem through
+    /// `status()` instead of aborting during setup.
+    pub fn open_or_fallback() -> Result<Self, String> {
+        match Self::new() {
+            Ok(db) => Ok(db),
+            Err(e) => {
+                eprintln!("Failed to open database, using a temporary in-memory database: {}", e);
+                Self::in_memory(e)
+            }
+        }
+    }
+
+    fn in_memory(open_error: String) -> Result<Self, String> {
+        let mut conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+        Self::migrate(&mut conn, None)?;
+        Ok(Self { conn, open_error: Some(open_error) })
+    }
+
+    fn data_dir() -> Result<PathBuf, String> {
+        Ok(ProjectDirs::from("com", "claudeterminal", "ClaudeTerminal")
             .ok_or("Failed to get project directories")?
             .data_dir()
-            .to_path_buf();
+            .to_path_buf())
+    }
 
-        std::fs::create_dir_all(&data_dir).map_err(|e| e.to_string())?;
+    /// Brings the schema up to date. When `data_dir` is given and the database
+    /// already holds data, a backup is written before the first migration runs.
+    fn migrate(conn: &mut Connection, data_dir: Option<&Path>) -> Result<(), String> {
+        let current: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        let latest = MIGRATIONS.len() as i64;
 
-        let db_path = data_dir.join("claudeterminal.db");
-        let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-
-        conn.execute_batch(
-            "
-            PRAGMA journal_mode=WAL;
-
-            CREATE TABLE IF NOT EXISTS profiles (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL,
-                description TEXT,
-                working_directory TEXT NOT NULL,
-                claude_args TEXT NOT NULL,
-                env_vars TEXT NOT NULL,
-                is_default INTEGER DEFAULT 0
-            );
-
-            CREATE TABLE IF NOT EXISTS workspaces (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT UNIQUE NOT NULL,
-                terminals TEXT NOT NULL,
-                created_at TEXT NOT NULL
-            );
-
-            CREATE TABLE IF NOT EXISTS session_history (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                terminal_id TEXT NOT NULL,
-                label TEXT NOT NULL,
-                started_at TEXT NOT NULL,
-                ended_at TEXT,
-                log_path TEXT
-            );
-
-            CREATE TABLE IF NOT EXISTS snippets (
-                id TEXT PRIMARY KEY,
-                title TEXT NOT NULL,
-                content TEXT NOT NULL,
-                category TEXT NOT NULL DEFAULT 'General',
-                created_at TEXT NOT NULL
-            );
-
-            CREATE INDEX IF NOT EXISTS idx_profiles_name ON profiles(name);
-            CREATE INDEX IF NOT EXISTS idx_workspaces_name ON workspaces(name);
-            CREATE INDEX IF NOT EXISTS idx_session_history_terminal_id ON session_history(terminal_id);
-            CREATE INDEX IF NOT EXISTS idx_snippets_category ON snippets(category);
-            "
-        ).map_err(|e| e.to_string())?;
+        if current > latest {
+            return Err(format!(
+                "Database schema version {} is newer than this version of ClaudeTerminal supports ({}). \
+                 Update the app or restore a backup.",
+                current, latest
+            ));
+        }
+        if current == latest {
+            return Ok(());
+        }
+
+        let has_data: bool = conn
+            .query_row("SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table')", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        let backup = match data_dir {
+            Some(dir) if has_data => Some(Self::backup(conn, dir, current)?),
+            _ => None,
+        };
+
+        for (index, sql) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+            let version = index as i64 + 1;
+            let result = conn.transaction().and_then(|tx| {
+                tx.execute_batch(sql)?;
+                tx.pragma_update(None, "user_version", version)?;
+                tx.commit()
+            });
+            if let Err(e) = result {
+                return Err(match &backup {
+                    Some(path) => format!(
+                        "Migration to schema version {} failed: {}. A backup of the previous database is at {}",
+                        version, e, path.display()
+                    ),
+                    None => format!("Migration to schema version {} failed: {}", version, e),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the live database into the backups directory and prunes old backups
+    fn backup(conn: &Connection, data_dir: &Path, version: i64) -> Result<PathBuf, String> {
+        let backups_dir = data_dir.join("backups");
+        std::fs::create_dir_all(&backups_dir).map_err(|e| e.to_string())?;
+
+        let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S_%3f");
+        let path = backups_dir.join(format!("claudeterminal-v{}-{}.db", version, timestamp));
+        // VACUUM INTO produces a consistent copy even with WAL enabled
+        conn.execute("VACUUM INTO ?1", params![path.to_string_lossy()])
+            .map_err(|e| format!("Failed to back up database before migrating: {}", e))?;
+
+        let backups = Self::list_backups_in(&backups_dir);
+        for old in backups.iter().skip(MAX_DATABASE_BACKUPS) {
+            let _ = std::fs::remove_file(backups_dir.join(&old.file_name));
+        }
+        Ok(path)
+    }
+
+    /// Backups in a directory, newest first
+    fn list_backups_in(backups_dir: &Path) -> Vec<DatabaseBackup> {
+        let Ok(entries) = std::fs::read_dir(backups_dir) else {
+            return vec![];
+        };
+        let mut backups: Vec<DatabaseBackup> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let file_name = entry.file_name().to_str()?.to_string();
+                if !file_name.starts_with("claudeterminal-") || !file_name.ends_with(".db") {
+                    return None;
+                }
+                let metadata = entry.metadata().ok()?;
+                let modified_at = metadata
+                    .modified()
+                    .ok()
+                    .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339());
+                Some(DatabaseBackup { file_name, size: metadata.len(), modified_at })
+            })
+            .collect();
+        // By the timestamp at the end of the name, as the schema version
+        // before it doesn't sort as text (v10 < v9)
+        let timestamp = |backup: &DatabaseBackup| {
+            let stem = backup.file_name.trim_end_matches(".db");
+            stem.rsplit('-').next().unwrap_or(stem).to_string()
+        };
+        backups.sort_by_key(|b| std::cmp::Reverse(timestamp(b)));
+        backups
+    }
+
+    pub fn status(&self) -> DatabaseStatus {
+        let schema_version = self.conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap_or(0);
+        let backups = Self::data_dir()
+            .map(|dir| Self::list_backups_in(&dir.join("backups")))
+            .unwrap_or_default();
+        DatabaseStatus {
+            persistent: self.open_error.is_none(),
+            error: self.open_error.clone(),
+            schema_version,
+            latest_schema_version: MIGRATIONS.len() as i64,
+            backups,
+        }
+    }
 
-        Ok(Self { conn })
+    /// Retries opening the on-disk database, e.g. after the user fixed permissions
+    pub fn reopen(&mut self) -> Result<(), String> {
+        *self = Self::new()?;
+        Ok(())
+    }
+
+    /// Replaces the on-disk database with a backup and reopens it. The current
+    /// database file is closed first; on failure the app is left on the
+    /// in-memory fallback with the error recorded.
+    pub fn restore_backup(&mut self, file_name: &str) -> Result<(), String> {
+        if file_name.contains(['/', '\\', '\0']) || file_name.contains("..")
+            || !file_name.starts_with("claudeterminal-") || !file_name.ends_with(".db")
+        {
+            return Err("Invalid backup file name".to_string());
+        }
+        let data_dir = Self::data_dir()?;
+        let backup_path = data_dir.join("backups").join(file_name);
+        if !backup_path.is_file() {
+            return Err(format!("Backup not found: {}", file_name));
+        }
+
+        // Release the file handle before touching the database files
+        *self = Self::in_memory("Restoring database from backup".to_string())?;
+
+        let db_path = data_dir.join(DB_FILE_NAME);
+        let restored = (|| {
+            for suffix in ["-wal", "-shm"] {
+                let sidecar = data_dir.join(format!("{}{}", DB_FILE_NAME, suffix));
+                if sidecar.exists() {
+                    std::fs::remove_file(&sidecar).map_err(|e| e.to_string())?;
+                }
+            }
+            std::fs::copy(&backup_path, &db_path)
+                .map_err(|e| format!("Failed to restore backup: {}", e))?;
+            Self::new()
+        })();
+
+        match restored {
+            Ok(db) => {
+                *self = db;
+                Ok(())
+            }
+            Err(e) => {
+                self.open_error = Some(e.clone());
+                Err(e)
+            }
+        }
     }
 
     pub fn save_profile(&self, profile: &ConfigProfile) -> Result<(), String> {
@@ -103,8 +406,8 @@ impl Database {
         let env_vars_json = serde_json::to_string(&profile.env_vars)
             .map_err(|e| format!("Failed to serialize env_vars: {}", e))?;
         self.conn.execute(
-            "INSERT OR REPLACE INTO profiles (id, name, description, working_directory, claude_args, env_vars, is_default)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            "INSERT OR REPLACE INTO profiles (id, name, description, working_directory, claude_args, env_vars, is_default, permission_policy_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
             params![
                 profile.id,
                 profile.name,
@@ -113,6 +416,7 @@ impl Database {
                 claude_args_json,
                 env_vars_json,
                 profile.is_default as i32,
+                profile.permission_policy_id,
             ],
         ).map_err(|e| e.to_string())?;
         Ok(())
@@ -120,7 +424,7 @@ impl Database {
 
     pub fn get_profiles(&self) -> Result<Vec<ConfigProfile>, String> {
         let mut stmt = self.conn
-            .prepare("SELECT id, name, description, working_directory, claude_args, env_vars, is_default FROM profiles")
+            .prepare("SELECT id, name, description, working_directory, claude_args, env_vars, is_default, permission_policy_id FROM profiles")
             .map_err(|e| e.to_string())?;
 
         let profiles = stmt.query_map([], |row| {
@@ -132,6 +436,7 @@ impl Database {
                 claude_args: serde_json::from_str(&row.get::<_, String>(4)?).unwrap_or_default(),
                 env_vars: serde_json::from_str(&row.get::<_, String>(5)?).unwrap_or_default(),
                 is_default: row.get::<_, i32>(6)? != 0,
+                permission_policy_id: row.get(7)?,
             })
         }).map_err(|e| e.to_string())?;
 
@@ -150,13 +455,83 @@ impl Database {
             return Err("Workspace name must be 1-255 characters".to_string());
         }
         let terminals_json = serde_json::to_string(terminals).map_err(|e| e.to_string())?;
+        let now = chrono::Utc::now().to_rfc3339();
+
+        // Internal workspaces (like the last session) are overwritten constantly
+        // and are not worth keeping history for
+        if name.starts_with("__") {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO workspaces (name, terminals, created_at) VALUES (?1, ?2, ?3)",
+                params![name, terminals_json, now],
+            ).map_err(|e| e.to_string())?;
+            return Ok(());
+        }
+
+        // Joins the caller's transaction when there is one, as in an import
+        let tx = if self.conn.is_autocommit() {
+            Some(self.conn.unchecked_transaction().map_err(|e| e.to_string())?)
+        } else {
+            None
+        };
+
+        // Workspaces saved before revisions existed get their current layout
+        // recorded first so the save below doesn't lose it
+        let has_revisions: bool = self.conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM workspace_revisions WHERE workspace_name = ?1)",
+                params![name],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if !has_revisions {
+            self.conn.execute(
+                "INSERT INTO workspace_revisions (workspace_name, revision, terminals, created_at)
+                 SELECT name, 1, terminals, created_at FROM workspaces WHERE name = ?1",
+                params![name],
+            ).map_err(|e| e.to_string())?;
+        }
+
         self.conn.execute(
             "INSERT OR REPLACE INTO workspaces (name, terminals, created_at) VALUES (?1, ?2, ?3)",
-            params![name, terminals_json, chrono::Utc::now().to_rfc3339()],
+            params![name, terminals_json, now],
+        ).map_err(|e| e.to_string())?;
+        self.conn.execute(
+            "INSERT INTO workspace_revisions (workspace_name, revision, terminals, created_at)
+             SELECT ?1, COALESCE(MAX(revision), 0) + 1, ?2, ?3 FROM workspace_revisions WHERE workspace_name = ?1",
+            params![name, terminals_json, now],
+        ).map_err(|e| e.to_string())?;
+        self.conn.execute(
+            "DELETE FROM workspace_revisions WHERE workspace_name = ?1 AND revision <= (
+                SELECT MAX(revision) FROM workspace_revisions WHERE workspace_name = ?1
+             ) - ?2",
+            params![name, MAX_WORKSPACE_REVISIONS],
         ).map_err(|e| e.to_string())?;
+
+        if let Some(tx) = tx {
+            tx.commit().map_err(|e| e.to_string())?;
+        }
         Ok(())
     }
 
+    /// Writes the records of an import in one transaction, so a failure
+    /// leaves nothing half imported
+    pub fn apply_import(&self, plan: &ImportPlan) -> Result<(), String> {
+        let tx = self.conn.unchecked_transaction().map_err(|e| e.to_string())?;
+        for policy in &plan.policies {
+            self.save_permission_policy(policy)?;
+        }
+        for profile in &plan.profiles {
+            self.save_profile(profile)?;
+        }
+        for snippet in &plan.snippets {
+            self.save_snippet(snippet)?;
+        }
+        for workspace in &plan.workspaces {
+            self.save_workspace(&workspace.name, &workspace.terminals)?;
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
     pub fn get_workspaces(&self) -> Result<Vec<WorkspaceInfo>, String> {
         let mut stmt = self.conn
             .prepare("SELECT name, terminals, created_at FROM workspaces WHERE name != '__last_session__' ORDER BY created_at DESC")
@@ -181,9 +556,44 @@ impl Database {
         }
         self.conn.execute("DELETE FROM workspaces WHERE name = ?1", params![name])
             .map_err(|e| e.to_string())?;
+        self.conn.execute("DELETE FROM workspace_revisions WHERE workspace_name = ?1", params![name])
+            .map_err(|e| e.to_string())?;
         Ok(())
     }
 
+    pub fn get_workspace_revisions(&self, name: &str) -> Result<Vec<WorkspaceRevision>, String> {
+        let mut stmt = self.conn
+            .prepare("SELECT revision, terminals, created_at FROM workspace_revisions WHERE workspace_name = ?1 ORDER BY revision DESC")
+            .map_err(|e| e.to_string())?;
+
+        let revisions = stmt.query_map(params![name], |row| {
+            let revision: i64 = row.get(0)?;
+            let terminals_json: String = row.get(1)?;
+            let created_at: String = row.get(2)?;
+            let terminal_count = serde_json::from_str::<Vec<serde_json::Value>>(&terminals_json)
+                .map(|v| v.len())
+                .unwrap_or(0);
+            Ok(WorkspaceRevision { revision, terminal_count, created_at })
+        }).map_err(|e| e.to_string())?;
+
+        revisions.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
+    pub fn load_workspace_revision(&self, name: &str, revision: i64) -> Result<Vec<TerminalConfig>, String> {
+        let result: Result<String, _> = self.conn.query_row(
+            "SELECT terminals FROM workspace_revisions WHERE workspace_name = ?1 AND revision = ?2",
+            params![name, revision],
+            |row| row.get(0),
+        );
+        match result {
+            Ok(terminals_json) => serde_json::from_str(&terminals_json).map_err(|e| e.to_string()),
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
+                Err(format!("Revision {} of workspace \"{}\" not found", revision, name))
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
     pub fn load_workspace(&self, name: &str) -> Result<Vec<TerminalConfig>, String> {
         let terminals_json: String = self.conn
             .query_row("SELECT terminals FROM workspaces WHERE name = ?1", params![name], |row| row.get(0))
@@ -271,11 +681,137 @@ impl Database {
     }
 
     pub fn delete_session_history_entry(&self, id: i64) -> Result<(), String> {
+        self.conn.execute("DELETE FROM session_file_changes WHERE session_id = ?1", params![id])
+            .map_err(|e| e.to_string())?;
         self.conn.execute("DELETE FROM session_history WHERE id = ?1", params![id])
             .map_err(|e| e.to_string())?;
         Ok(())
     }
 
+    // File change attribution methods
+
+    /// Stores changes against each terminal's open session; changes for
+    /// terminals without one are dropped
+    pub fn insert_file_changes(&mut self, changes: &[AttributedChange]) -> Result<(), String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        {
+            let mut session = tx
+                .prepare(
+                    "SELECT id FROM session_history WHERE terminal_id = ?1 AND ended_at IS NULL
+                     ORDER BY started_at DESC LIMIT 1",
+                )
+                .map_err(|e| e.to_string())?;
+            let mut insert = tx
+                .prepare(
+                    "INSERT INTO session_file_changes (session_id, path, change, changed_at, shared)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                )
+                .map_err(|e| e.to_string())?;
+            for change in changes {
+                let session_id: i64 = match session.query_row(params![change.terminal_id], |row| row.get(0)) {
+                    Ok(id) => id,
+                    Err(rusqlite::Error::QueryReturnedNoRows) => continue,
+                    Err(e) => return Err(e.to_string()),
+                };
+                insert
+                    .execute(params![
+                        session_id,
+                        change.path,
+                        change.change.as_str(),
+                        change.changed_at,
+                        change.shared,
+                    ])
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    /// A session's file changes in the order they happened
+    pub fn get_session_timeline(&self, session_id: i64) -> Result<Vec<FileChangeEntry>, String> {
+        let mut stmt = self.conn
+            .prepare(
+                "SELECT id, session_id, path, change, changed_at, shared FROM session_file_changes
+                 WHERE session_id = ?1 ORDER BY changed_at, id",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let entries = stmt.query_map(params![session_id], |row| {
+            let change: String = row.get(3)?;
+            Ok(FileChangeEntry {
+                id: row.get(0)?,
+                session_id: row.get(1)?,
+                path: row.get(2)?,
+                change: FileChangeKind::parse(&change),
+                changed_at: row.get(4)?,
+                shared: row.get(5)?,
+            })
+        }).map_err(|e| e.to_string())?;
+
+        entries.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
+    /// Files touched by a session, most recently changed first
+    pub fn get_session_files(&self, session_id: i64) -> Result<Vec<SessionFile>, String> {
+        let mut stmt = self.conn
+            .prepare(
+                "SELECT path, COUNT(*), MIN(changed_at), MAX(changed_at),
+                        (SELECT change FROM session_file_changes l
+                         WHERE l.session_id = c.session_id AND l.path = c.path
+                         ORDER BY l.changed_at DESC, l.id DESC LIMIT 1),
+                        MAX(shared)
+                 FROM session_file_changes c
+                 WHERE session_id = ?1
+                 GROUP BY path
+                 ORDER BY MAX(changed_at) DESC",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let files = stmt.query_map(params![session_id], |row| {
+            let last_change: String = row.get(4)?;
+            Ok(SessionFile {
+                path: row.get(0)?,
+                change_count: row.get(1)?,
+                first_changed_at: row.get(2)?,
+                last_changed_at: row.get(3)?,
+                last_change: FileChangeKind::parse(&last_change),
+                shared: row.get(5)?,
+            })
+        }).map_err(|e| e.to_string())?;
+
+        files.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
+    /// Sessions that touched a file, most recent first
+    pub fn get_file_sessions(&self, path: &str) -> Result<Vec<FileSession>, String> {
+        let mut stmt = self.conn
+            .prepare(
+                "SELECT h.id, h.terminal_id, h.label, h.started_at, h.ended_at,
+                        COUNT(*), MAX(c.changed_at), MAX(c.shared)
+                 FROM session_file_changes c
+                 JOIN session_history h ON h.id = c.session_id
+                 WHERE c.path = ?1
+                 GROUP BY h.id
+                 ORDER BY MAX(c.changed_at) DESC",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let sessions = stmt.query_map(params![path], |row| {
+            Ok(FileSession {
+                session_id: row.get(0)?,
+                terminal_id: row.get(1)?,
+                label: row.get(2)?,
+                started_at: row.get(3)?,
+                ended_at: row.get(4)?,
+                change_count: row.get(5)?,
+                last_changed_at: row.get(6)?,
+                shared: row.get(7)?,
+            })
+        }).map_err(|e| e.to_string())?;
+
+        sessions.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
     // Snippet methods
 
     pub fn save_snippet(&self, snippet: &Snippet) -> Result<(), String> {
@@ -312,4 +848,217 @@ impl Database {
             .map_err(|e| e.to_string())?;
         Ok(())
     }
+
+    // Disabled MCP server methods
+
+    pub fn get_disabled_mcp_servers(&self) -> Result<Vec<(String, serde_json::Value)>, String> {
+        let mut stmt = self.conn
+            .prepare("SELECT name, config FROM disabled_mcp_servers ORDER BY name")
+            .map_err(|e| e.to_string())?;
+
+        let servers = stmt.query_map([], |row| {
+            let name: String = row.get(0)?;
+            let config: String = row.get(1)?;
+            Ok((name, serde_json::from_str(&config).unwrap_or_default()))
+        }).map_err(|e| e.to_string())?;
+
+        servers.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
+    pub fn save_disabled_mcp_server(&self, name: &str, config: &serde_json::Value) -> Result<(), String> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO disabled_mcp_servers (name, config, disabled_at) VALUES (?1, ?2, ?3)",
+            params![name, config.to_string(), chrono::Utc::now().to_rfc3339()],
+        ).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn delete_disabled_mcp_server(&self, name: &str) -> Result<(), String> {
+        self.conn.execute("DELETE FROM disabled_mcp_servers WHERE name = ?1", params![name])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    // Project file trust methods
+
+    /// Whether the user trusted the project file at `path` with exactly this content
+    pub fn is_project_file_trusted(&self, path: &str, content: &str) -> Result<bool, String> {
+        self.conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM trusted_project_files WHERE path = ?1 AND content = ?2)",
+                params![path, content],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn trust_project_file(&self, path: &str, content: &str) -> Result<(), String> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO trusted_project_files (path, content, trusted_at) VALUES (?1, ?2, ?3)",
+            params![path, content, chrono::Utc::now().to_rfc3339()],
+        ).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn save_permission_policy(&self, policy: &PermissionPolicy) -> Result<(), String> {
+        let policy_json = serde_json::to_string(policy).map_err(|e| e.to_string())?;
+        self.conn.execute(
+            "INSERT OR REPLACE INTO permission_policies (id, name, policy, updated_at) VALUES (?1, ?2, ?3, ?4)",
+            params![policy.id, policy.name, policy_json, chrono::Utc::now().to_rfc3339()],
+        ).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// All readable policies. Rows that no longer parse are logged and left
+    /// out; loading one by id reports the problem instead.
+    pub fn get_permission_policies(&self) -> Result<Vec<PermissionPolicy>, String> {
+        let mut stmt = self.conn
+            .prepare("SELECT id, name, policy FROM permission_policies ORDER BY name COLLATE NOCASE")
+            .map_err(|e| e.to_string())?;
+
+        let policies = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        }).map_err(|e| e.to_string())?;
+
+        Ok(policies
+            .flatten()
+            .filter_map(|(id, name, policy)| match parse_policy(id, name, &policy) {
+                Ok(policy) => Some(policy),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Fails when the policy exists but can't be read, so a terminal bound to
+    /// it isn't launched without its restrictions
+    pub fn get_permission_policy(&self, id: &str) -> Result<Option<PermissionPolicy>, String> {
+        let mut stmt = self.conn
+            .prepare("SELECT name, policy FROM permission_policies WHERE id = ?1")
+            .map_err(|e| e.to_string())?;
+        let mut rows = stmt
+            .query_map(params![id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| e.to_string())?;
+        match rows.next() {
+            Some(row) => {
+                let (name, policy) = row.map_err(|e| e.to_string())?;
+                parse_policy(id.to_string(), name, &policy).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_auto_approve_rules(&self) -> Result<Vec<AutoApproveRule>, String> {
+        let mut stmt = self.conn
+            .prepare("SELECT id, rule FROM auto_approve_rules ORDER BY created_at")
+            .map_err(|e| e.to_string())?;
+
+        let rules = stmt.query_map([], |row| {
+            let id: String = row.get(0)?;
+            let rule: String = row.get(1)?;
+            Ok((id, rule))
+        }).map_err(|e| e.to_string())?;
+
+        // Rows that no longer parse are skipped rather than failing every prompt
+        Ok(rules
+            .flatten()
+            .filter_map(|(id, rule)| {
+                let mut rule: AutoApproveRule = serde_json::from_str(&rule).ok()?;
+                rule.id = id;
+                Some(rule)
+            })
+            .collect())
+    }
+
+    pub fn save_auto_approve_rule(&self, rule: &AutoApproveRule) -> Result<(), String> {
+        let rule_json = serde_json::to_string(rule).map_err(|e| e.to_string())?;
+        // Keep the original created_at so rule order is stable across edits
+        self.conn.execute(
+            "INSERT INTO auto_approve_rules (id, rule, created_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(id) DO UPDATE SET rule = excluded.rule",
+            params![rule.id, rule_json, chrono::Utc::now().to_rfc3339()],
+        ).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn delete_auto_approve_rule(&self, id: &str) -> Result<(), String> {
+        self.conn.execute("DELETE FROM auto_approve_rules WHERE id = ?1", params![id])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn insert_auto_approve_audit(
+        &self,
+        terminal_id: &str,
+        prompt_text: &str,
+        rule: &RuleMatch,
+    ) -> Result<(), String> {
+        let decision = serde_json::to_value(rule.decision).map_err(|e| e.to_string())?;
+        self.conn.execute(
+            "INSERT INTO auto_approve_audit (terminal_id, prompt_text, rule_id, rule_name, decision, decided_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                terminal_id,
+                prompt_text,
+                rule.rule_id,
+                rule.rule_name,
+                decision.as_str().unwrap_or_default(),
+                chrono::Utc::now().to_rfc3339(),
+            ],
+        ).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Most recent automatic decisions first, optionally for one terminal
+    pub fn get_auto_approve_audit(
+        &self,
+        terminal_id: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<AutoApproveAuditEntry>, String> {
+        let mut stmt = self.conn
+            .prepare(
+                "SELECT id, terminal_id, prompt_text, rule_id, rule_name, decision, decided_at
+                 FROM auto_approve_audit
+                 WHERE ?1 IS NULL OR terminal_id = ?1
+                 ORDER BY id DESC LIMIT ?2",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let entries = stmt.query_map(params![terminal_id, limit], |row| {
+            let decision: String = row.get(5)?;
+            Ok(AutoApproveAuditEntry {
+                id: row.get(0)?,
+                terminal_id: row.get(1)?,
+                prompt_text: row.get(2)?,
+                rule_id: row.get(3)?,
+                rule_name: row.get(4)?,
+                decision: if decision == "deny" { Decision::Deny } else { Decision::Approve },
+                decided_at: row.get(6)?,
+            })
+        }).map_err(|e| e.to_string())?;
+
+        entries.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
+    /// Deletes a policy and detaches it from any profile using it
+    pub fn delete_permission_policy(&self, id: &str) -> Result<(), String> {
+        let tx = self.conn.unchecked_transaction().map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM permission_policies WHERE id = ?1", params![id])
+            .map_err(|e| e.to_string())?;
+        tx.execute(
+            "UPDATE profiles SET permission_policy_id = NULL WHERE permission_policy_id = ?1",
+            params![id],
+        ).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    }
+}
+
+fn parse_policy(id: String, name: String, policy: &str) -> Result<PermissionPolicy, String> {
+    let mut policy: PermissionPolicy = serde_json::from_str(policy)
+        .map_err(|e| format!("Permission policy \"{}\" can't be read: {}", name, e))?;
+    policy.id = id;
+    policy.name = name;
+    Ok(policy)
 }
@@ -0,0 +1,272 @@
+use crate::changes::{self, ChangesOptions, FileChangesResult};
+use crate::conflicts::ConflictMonitor;
+use crate::git;
+use crate::repo::RepoInfo;
+use crate::terminal::TerminalManager;
+use crate::watch::{self, WatchedTree};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// Quiet period after a filesystem event before the change set is rebuilt
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Longest a change set waits while events keep coming
+const WATCH_MAX_DELAY: Duration = Duration::from_secs(3);
+
+/// Hunk lines per file included in change events; the full diff is still
+/// available from `get_terminal_changes`
+const EVENT_MAX_LINES_PER_FILE: usize = 200;
+
+/// Files in the git directory whose changes can move HEAD or start or end a
+/// merge, rebase or similar
+const GIT_STATE_FILES: &[&str] = &[
+    "index",
+    "HEAD",
+    "packed-refs",
+    "MERGE_HEAD",
+    "CHERRY_PICK_HEAD",
+    "REVERT_HEAD",
+    "BISECT_LOG",
+    "rebase-merge",
+    "rebase-apply",
+];
+
+enum Message {
+    Fs(notify::Result<notify::Event>),
+    Stop,
+}
+
+/// Watches a terminal's repository and emits `terminal-files-changed` with
+/// the new change set whenever it differs from the last one. Also keeps the
+/// terminal's `git` details current, emitting `terminal-git-changed`, and
+/// `terminal-branch-changed` when another branch is checked out, and reports
+/// its changes to the conflict monitor, emitting `terminal-conflict` when they
+/// start overlapping another terminal's. Stops when dropped.
+pub struct FilesWatcher {
+    tx: mpsc::Sender<Message>,
+}
+
+impl FilesWatcher {
+    pub fn start(
+        app: AppHandle,
+        terminals: Arc<tokio::sync::Mutex<TerminalManager>>,
+        conflicts: ConflictMonitor,
+        terminal_id: String,
+        working_directory: String,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let events = tx.clone();
+        std::thread::spawn(move || {
+            run(&app, &terminals, &conflicts, &terminal_id, &working_directory, events, rx);
+            conflicts.remove(&terminal_id);
+        });
+        FilesWatcher { tx }
+    }
+}
+
+impl Drop for FilesWatcher {
+    fn drop(&mut self) {
+        let _ = self.tx.send(Message::Stop);
+    }
+}
+
+fn run(
+    app: &AppHandle,
+    terminals: &tokio::sync::Mutex<TerminalManager>,
+    conflicts: &ConflictMonitor,
+    terminal_id: &str,
+    working_directory: &str,
+    events: mpsc::Sender<Message>,
+    rx: mpsc::Receiver<Message>,
+) {
+    // Outside git there is no change set to report
+    let Some(mut tree) = RepoTree::new(Path::new(working_directory)) else {
+        return;
+    };
+    let mut watcher = match notify::recommended_watcher(move |event| {
+        let _ = events.send(Message::Fs(event));
+    }) {
+        Ok(w) => w,
+        Err(e) => {
+            eprintln!("Failed to start file watcher for terminal {}: {}", terminal_id, e);
+            return;
+        }
+    };
+    tree.watch_git_dir(&mut watcher);
+    let root = tree.files.root.clone();
+    tree.files.watch(&mut watcher, &root);
+
+    // Hunks are read in full so overlaps further down a file are seen too
+    let options = ChangesOptions {
+        include_diff: true,
+        ..ChangesOptions::default()
+    };
+    let collect = |tree: &RepoTree| {
+        let mut result =
+            changes::terminal_result(terminal_id.to_string(), working_directory.to_string(), &options);
+        check_conflicts(app, terminals, conflicts, terminal_id, tree, &result);
+        result.cap_lines(EVENT_MAX_LINES_PER_FILE);
+        result
+    };
+    let mut last: FileChangesResult = collect(&tree);
+    update_git(app, terminals, terminal_id, last.repo.clone());
+
+    loop {
+        let (mut relevant, mut stopped) = (false, false);
+        let open = watch::recv_burst(&rx, WATCH_DEBOUNCE, WATCH_MAX_DELAY, |message| match message {
+            Message::Fs(event) => relevant |= tree.handle(&mut watcher, event),
+            Message::Stop => stopped = true,
+        });
+        if !open || stopped {
+            return;
+        }
+        if !relevant {
+            continue;
+        }
+        let next = collect(&tree);
+        if next != last {
+            if let Err(e) = app.emit("terminal-files-changed", &next) {
+                eprintln!("Failed to emit terminal-files-changed: {}", e);
+            }
+            if next.repo != last.repo {
+                update_git(app, terminals, terminal_id, next.repo.clone());
+            }
+            last = next;
+        }
+    }
+}
+
+/// Hands the terminal's changes to the conflict monitor and announces each
+/// conflict that gained files
+fn check_conflicts(
+    app: &AppHandle,
+    terminals: &tokio::sync::Mutex<TerminalManager>,
+    conflicts: &ConflictMonitor,
+    terminal_id: &str,
+    tree: &RepoTree,
+    result: &FileChangesResult,
+) {
+    if result.error.is_some() {
+        return;
+    }
+    let label = {
+        let terminals = terminals.blocking_lock();
+        let Some(config) = terminals.get_all_configs().into_iter().find(|c| c.id == terminal_id) else {
+            return;
+        };
+        config.nickname.unwrap_or(config.label)
+    };
+    let root = tree.files.root.clone();
+    let base = match &result.repo {
+        Some(repo) => repo.head.clone(),
+        None => git::run_trimmed(&root, &["rev-parse", "--verify", "--quiet", "HEAD"]).ok(),
+    };
+    let repository = tree.common_dir.clone();
+    for conflict in conflicts.update(terminal_id, label, repository, root, base, &result.changes) {
+        if let Err(e) = app.emit("terminal-conflict", &conflict) {
+            eprintln!("Failed to emit terminal-conflict: {}", e);
+        }
+    }
+}
+
+/// Stores a terminal's repository details and announces what changed
+fn update_git(
+    app: &AppHandle,
+    terminals: &tokio::sync::Mutex<TerminalManager>,
+    terminal_id: &str,
+    git: Option<RepoInfo>,
+) {
+    // The terminal may have closed in the meantime
+    let Ok(previous) = terminals.blocking_lock().set_git(terminal_id, git.clone()) else {
+        return;
+    };
+    if previous == git {
+        return;
+    }
+    if let Err(e) = app.emit("terminal-git-changed", serde_json::json!({
+        "id": terminal_id,
+        "git": git,
+    })) {
+        eprintln!("Failed to emit terminal-git-changed: {}", e);
+    }
+
+    // The first lookup isn't a switch
+    let (Some(previous), Some(current)) = (previous, git) else {
+        return;
+    };
+    if previous.branch == current.branch && previous.detached == current.detached {
+        return;
+    }
+    if let Err(e) = app.emit("terminal-branch-changed", serde_json::json!({
+        "id": terminal_id,
+        "previous_branch": previous.branch,
+        "branch": current.branch,
+        "detached": current.detached,
+        "head": current.head,
+    })) {
+        eprintln!("Failed to emit terminal-branch-changed: {}", e);
+    }
+}
+
+/// A repository's working tree and the parts of its git directory that are
+/// watched
+struct RepoTree {
+    files: WatchedTree,
+    git_dir: PathBuf,
+    common_dir: PathBuf,
+}
+
+impl RepoTree {
+    fn new(dir: &Path) -> Option<Self> {
+        let root = PathBuf::from(git::toplevel(dir)?);
+        let git_dir = PathBuf::from(git::run_trimmed(dir, &["rev-parse", "--absolute-git-dir"]).ok()?);
+        let common_dir = git::run_trimmed(dir, &["rev-parse", "--git-common-dir"])
+            .map(|d| dir.join(d))
+            .unwrap_or_else(|_| git_dir.clone());
+        let common_dir = common_dir.canonicalize().unwrap_or(common_dir);
+        Some(RepoTree {
+            files: WatchedTree::new(&root, &[]),
+            git_dir,
+            common_dir,
+        })
+    }
+
+    /// Watches HEAD and the index, which change on checkout, staging and
+    /// commits, and the refs, which move on commits, fetches and stashes
+    fn watch_git_dir(&mut self, watcher: &mut RecommendedWatcher) {
+        let _ = watcher.watch(&self.git_dir, RecursiveMode::NonRecursive);
+        // A linked worktree keeps packed-refs in the main repository
+        if self.common_dir != self.git_dir {
+            let _ = watcher.watch(&self.common_dir, RecursiveMode::NonRecursive);
+        }
+        let _ = watcher.watch(&self.common_dir.join("refs"), RecursiveMode::Recursive);
+    }
+
+    /// Updates watches for an event and says whether it can affect the
+    /// change set
+    fn handle(&mut self, watcher: &mut RecommendedWatcher, event: notify::Result<notify::Event>) -> bool {
+        let Ok(event) = event else {
+            return false;
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            return false;
+        }
+        let mut relevant = false;
+        for path in &event.paths {
+            if path.starts_with(&self.git_dir) || path.starts_with(&self.common_dir) {
+                // Lock files are renamed over the real file when git is done
+                let is_lock = path.extension().is_some_and(|e| e == "lock");
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+                relevant |= !is_lock
+                    && (GIT_STATE_FILES.contains(&name) || path.starts_with(self.common_dir.join("refs")));
+            } else if path.starts_with(&self.files.root) {
+                relevant |= self.files.handle_path(watcher, &event.kind, path);
+            }
+        }
+        relevant
+    }
+}
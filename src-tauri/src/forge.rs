@@ -0,0 +1,389 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A pull request to open from `head` into `base`
+#[derive(Debug, Clone)]
+pub struct NewPullRequest {
+    pub title: String,
+    pub body: String,
+    pub head: String,
+    pub base: String,
+    pub draft: bool,
+}
+
+/// A pull request as opened on a forge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequest {
+    pub forge: String,
+    pub number: u64,
+    pub url: String,
+    pub title: String,
+    pub head: String,
+    pub base: String,
+}
+
+/// A code host pull requests can be opened on
+pub trait Forge {
+    fn name(&self) -> &'static str;
+
+    fn open_pull_request(&self, pr: &NewPullRequest) -> Result<PullRequest, String>;
+}
+
+/// Which forge a remote lives on and how to reach its API. Tokens fall back
+/// to the forge's usual environment variable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ForgeConfig {
+    Github {
+        #[serde(default)]
+        api_url: Option<String>,
+        #[serde(default)]
+        token: Option<String>,
+    },
+    Gitlab {
+        #[serde(default)]
+        api_url: Option<String>,
+        #[serde(default)]
+        token: Option<String>,
+    },
+    Gitea {
+        #[serde(default)]
+        api_url: Option<String>,
+        #[serde(default)]
+        token: Option<String>,
+    },
+    /// A bare repository on this machine, which keeps pull requests as JSON
+    /// files next to its refs
+    Local,
+}
+
+impl ForgeConfig {
+    /// Guesses the forge from a remote URL's host
+    pub fn detect(remote_url: &str) -> Option<Self> {
+        if local_path(remote_url).is_some() {
+            return Some(ForgeConfig::Local);
+        }
+        let remote = RemoteRepo::parse(remote_url)?;
+        let host = remote.host.to_lowercase();
+        if host == "github.com" || host.starts_with("github.") {
+            Some(ForgeConfig::Github { api_url: None, token: None })
+        } else if host == "gitlab.com" || host.starts_with("gitlab.") {
+            Some(ForgeConfig::Gitlab { api_url: None, token: None })
+        } else if host == "codeberg.org" || host.starts_with("gitea.") || host.starts_with("forgejo.") {
+            Some(ForgeConfig::Gitea { api_url: None, token: None })
+        } else {
+            None
+        }
+    }
+
+    /// The adapter for the repository behind `remote_url`
+    pub fn build(&self, remote_url: &str) -> Result<Box<dyn Forge>, String> {
+        let remote = || {
+            RemoteRepo::parse(remote_url)
+                .ok_or_else(|| format!("Can't tell the repository from remote URL {}", remote_url))
+        };
+        let forge: Box<dyn Forge> = match self {
+            ForgeConfig::Github { api_url, token } => {
+                let remote = remote()?;
+                let api_url_set = api_url.is_some();
+                let api_url = api_url.clone().unwrap_or_else(|| match remote.host.as_str() {
+                    "github.com" => "https://api.github.com".to_string(),
+                    host => format!("https://{}/api/v3", host),
+                });
+                let token =
+                    token_or_env(token, &remote, api_url_set, "github.com", &["GITHUB_TOKEN", "GH_TOKEN"])?;
+                Box::new(GitHub { api_url, token, remote })
+            }
+            ForgeConfig::Gitlab { api_url, token } => {
+                let remote = remote()?;
+                let api_url_set = api_url.is_some();
+                let api_url = api_url.clone().unwrap_or_else(|| format!("https://{}/api/v4", remote.host));
+                let token = token_or_env(token, &remote, api_url_set, "gitlab.com", &["GITLAB_TOKEN"])?;
+                Box::new(GitLab { api_url, token, remote })
+            }
+            ForgeConfig::Gitea { api_url, token } => {
+                let remote = remote()?;
+                let api_url_set = api_url.is_some();
+                let api_url = api_url.clone().unwrap_or_else(|| format!("https://{}/api/v1", remote.host));
+                let token = token_or_env(token, &remote, api_url_set, "codeberg.org", &["GITEA_TOKEN"])?;
+                Box::new(Gitea { api_url, token, remote })
+            }
+            ForgeConfig::Local => {
+                let path = local_path(remote_url)
+                    .ok_or_else(|| format!("{} is not a repository on this machine", remote_url))?;
+                Box::new(LocalForge { path })
+            }
+        };
+        Ok(forge)
+    }
+}
+
+/// The configured token, or one from the environment for the forge's own
+/// host. Tokens in the environment are meant for that host, so they are never
+/// sent to another one, whatever its name.
+fn token_or_env(
+    token: &Option<String>,
+    remote: &RemoteRepo,
+    api_url_set: bool,
+    canonical_host: &str,
+    vars: &[&str],
+) -> Result<String, String> {
+    if let Some(token) = token.clone().filter(|t| !t.trim().is_empty()) {
+        return Ok(token);
+    }
+    if api_url_set || !remote.host.eq_ignore_ascii_case(canonical_host) {
+        return Err(format!("No API token configured for {}; set one for this forge", remote.host));
+    }
+    vars.iter()
+        .find_map(|v| std::env::var(v).ok().filter(|t| !t.trim().is_empty()))
+        .ok_or_else(|| format!("No API token configured; set one or export {}", vars[0]))
+}
+
+/// Host and repository path of a remote, e.g. `github.com` and `owner/repo`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteRepo {
+    pub host: String,
+    pub path: String,
+}
+
+impl RemoteRepo {
+    /// Parses `https://host/owner/repo.git`, `ssh://git@host:22/owner/repo`
+    /// and scp-like `git@host:owner/repo.git` URLs
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = url.trim();
+        let (host, path) = if let Some((scheme, rest)) = url.split_once("://") {
+            if !matches!(scheme, "https" | "http" | "ssh" | "git") {
+                return None;
+            }
+            let (authority, path) = rest.split_once('/')?;
+            let host = authority.rsplit('@').next()?;
+            let host = host.split(':').next()?;
+            (host, path)
+        } else {
+            let (authority, path) = url.split_once(':')?;
+            if authority.contains('/') {
+                return None;
+            }
+            (authority.rsplit('@').next()?, path)
+        };
+        let path = path.trim_matches('/').trim_end_matches(".git").trim_end_matches('/');
+        if host.is_empty() || !path.contains('/') {
+            return None;
+        }
+        Some(RemoteRepo { host: host.to_string(), path: path.to_string() })
+    }
+}
+
+/// Directory of a remote given as a local path or `file://` URL
+fn local_path(url: &str) -> Option<PathBuf> {
+    let path = url.strip_prefix("file://").unwrap_or(url);
+    let path = Path::new(path);
+    (path.is_absolute() && path.is_dir()).then(|| path.to_path_buf())
+}
+
+/// Sends a JSON request and turns HTTP failures into the forge's message
+fn post_json(request: ureq::Request, body: serde_json::Value) -> Result<serde_json::Value, String> {
+    match request
+        .set("Accept", "application/json")
+        .set("User-Agent", "ClaudeTerminal")
+        .send_json(body)
+    {
+        Ok(response) => response.into_json().map_err(|e| format!("Invalid response from forge: {}", e)),
+        Err(ureq::Error::Status(code, response)) => {
+            let text = response.into_string().unwrap_or_default();
+            let message = serde_json::from_str::<serde_json::Value>(&text)
+                .ok()
+                .and_then(|v| error_message(&v))
+                .unwrap_or(text);
+            Err(format!("Forge returned {}: {}", code, message.trim()))
+        }
+        Err(e) => Err(format!("Failed to reach forge: {}", e)),
+    }
+}
+
+/// The human-readable part of a forge's error body
+fn error_message(value: &serde_json::Value) -> Option<String> {
+    let message = value.get("message").or_else(|| value.get("error"))?;
+    let mut text = match message {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    // GitHub puts the useful part, e.g. "A pull request already exists", here
+    if let Some(errors) = value.get("errors").and_then(|e| e.as_array()) {
+        for error in errors {
+            if let Some(m) = error.get("message").and_then(|m| m.as_str()) {
+                text.push_str(": ");
+                text.push_str(m);
+            }
+        }
+    }
+    Some(text)
+}
+
+fn opened(forge: &str, response: &serde_json::Value, number_key: &str, url_key: &str, pr: &NewPullRequest) -> Result<PullRequest, String> {
+    let number = response
+        .get(number_key)
+        .and_then(|n| n.as_u64())
+        .ok_or("Forge response has no pull request number")?;
+    Ok(PullRequest {
+        forge: forge.to_string(),
+        number,
+        url: response.get(url_key).and_then(|u| u.as_str()).unwrap_or_default().to_string(),
+        title: pr.title.clone(),
+        head: pr.head.clone(),
+        base: pr.base.clone(),
+    })
+}
+
+struct GitHub {
+    api_url: String,
+    token: String,
+    remote: RemoteRepo,
+}
+
+impl Forge for GitHub {
+    fn name(&self) -> &'static str {
+        "github"
+    }
+
+    fn open_pull_request(&self, pr: &NewPullRequest) -> Result<PullRequest, String> {
+        let url = format!("{}/repos/{}/pulls", self.api_url.trim_end_matches('/'), self.remote.path);
+        let request = ureq::post(&url)
+            .set("Authorization", &format!("Bearer {}", self.token))
+            .set("X-GitHub-Api-Version", "2022-11-28");
+        let response = post_json(
+            request,
+            serde_json::json!({
+                "title": pr.title,
+                "body": pr.body,
+                "head": pr.head,
+                "base": pr.base,
+                "draft": pr.draft,
+            }),
+        )?;
+        opened(self.name(), &response, "number", "html_url", pr)
+    }
+}
+
+struct GitLab {
+    api_url: String,
+    token: String,
+    remote: RemoteRepo,
+}
+
+impl Forge for GitLab {
+    fn name(&self) -> &'static str {
+        "gitlab"
+    }
+
+    fn open_pull_request(&self, pr: &NewPullRequest) -> Result<PullRequest, String> {
+        let url = format!(
+            "{}/projects/{}/merge_requests",
+            self.api_url.trim_end_matches('/'),
+            self.remote.path.replace('/', "%2F")
+        );
+        let title = if pr.draft { format!("Draft: {}", pr.title) } else { pr.title.clone() };
+        let response = post_json(
+            ureq::post(&url).set("PRIVATE-TOKEN", &self.token),
+            serde_json::json!({
+                "title": title,
+                "description": pr.body,
+                "source_branch": pr.head,
+                "target_branch": pr.base,
+            }),
+        )?;
+        opened(self.name(), &response, "iid", "web_url", pr)
+    }
+}
+
+struct Gitea {
+    api_url: String,
+    token: String,
+    remote: RemoteRepo,
+}
+
+impl Forge for Gitea {
+    fn name(&self) -> &'static str {
+        "gitea"
+    }
+
+    fn open_pull_request(&self, pr: &NewPullRequest) -> Result<PullRequest, String> {
+        let url = format!("{}/repos/{}/pulls", self.api_url.trim_end_matches('/'), self.remote.path);
+        // Gitea marks drafts by title prefix
+        let title = if pr.draft { format!("WIP: {}", pr.title) } else { pr.title.clone() };
+        let response = post_json(
+            ureq::post(&url).set("Authorization", &format!("token {}", self.token)),
+            serde_json::json!({
+                "title": title,
+                "body": pr.body,
+                "head": pr.head,
+                "base": pr.base,
+            }),
+        )?;
+        opened(self.name(), &response, "number", "html_url", pr)
+    }
+}
+
+/// Keeps pull requests in `<repo>/pull-requests/<number>.json`, so the whole
+/// flow can run against a bare repository without a code host
+struct LocalForge {
+    path: PathBuf,
+}
+
+/// A pull request as stored by the local forge
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LocalPullRequest {
+    pub number: u64,
+    pub title: String,
+    pub body: String,
+    pub head: String,
+    pub base: String,
+    pub draft: bool,
+    pub created_at: String,
+}
+
+impl Forge for LocalForge {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn open_pull_request(&self, pr: &NewPullRequest) -> Result<PullRequest, String> {
+        let dir = self.path.join("pull-requests");
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let mut number = 1;
+        for entry in std::fs::read_dir(&dir).map_err(|e| e.to_string())?.flatten() {
+            let path = entry.path();
+            let Ok(existing) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let Ok(existing) = serde_json::from_str::<LocalPullRequest>(&existing) else {
+                continue;
+            };
+            if existing.head == pr.head && existing.base == pr.base {
+                return Err(format!("A pull request from {} into {} already exists", pr.head, pr.base));
+            }
+            number = number.max(existing.number + 1);
+        }
+
+        let stored = LocalPullRequest {
+            number,
+            title: pr.title.clone(),
+            body: pr.body.clone(),
+            head: pr.head.clone(),
+            base: pr.base.clone(),
+            draft: pr.draft,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+        let path = dir.join(format!("{}.json", number));
+        let json = serde_json::to_string_pretty(&stored).map_err(|e| e.to_string())?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(PullRequest {
+            forge: self.name().to_string(),
+            number,
+            url: path.to_string_lossy().to_string(),
+            title: stored.title,
+            head: stored.head,
+            base: stored.base,
+        })
+    }
+}
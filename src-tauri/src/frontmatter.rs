@@ -0,0 +1,354 @@
+use crate::claude_dir::DefinitionKind;
+use crate::diagnostics::Diagnostic;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+/// Built-in claude tools accepted in `tools` and `allowed-tools`. MCP tools
+/// (`mcp__server__tool`) are accepted without being listed here.
+const KNOWN_TOOLS: &[&str] = &[
+    "AskUserQuestion",
+    "Bash",
+    "BashOutput",
+    "Edit",
+    "ExitPlanMode",
+    "Glob",
+    "Grep",
+    "KillBash",
+    "KillShell",
+    "LS",
+    "MultiEdit",
+    "NotebookEdit",
+    "NotebookRead",
+    "Read",
+    "SlashCommand",
+    "Skill",
+    "Task",
+    "TodoWrite",
+    "WebFetch",
+    "WebSearch",
+    "Write",
+];
+
+/// Model aliases claude accepts besides full `claude-*` model ids
+const MODEL_ALIASES: &[&str] = &["inherit", "sonnet", "opus", "haiku", "opusplan"];
+
+const AGENT_KEYS: &[&str] = &["name", "description", "tools", "model", "color"];
+const COMMAND_KEYS: &[&str] = &[
+    "description",
+    "allowed-tools",
+    "argument-hint",
+    "model",
+    "disable-model-invocation",
+];
+
+/// Typed view of an agent or slash command frontmatter. Fields that don't
+/// apply to the definition's kind are left empty.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DefinitionMeta {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Agent tool list; `None` means the agent inherits every tool
+    pub tools: Option<Vec<String>>,
+    pub model: Option<String>,
+    /// Command tool permissions, e.g. `Bash(git status:*)`
+    pub allowed_tools: Option<Vec<String>>,
+    pub argument_hint: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ParsedDefinition {
+    pub file_name: String,
+    pub has_frontmatter: bool,
+    pub meta: DefinitionMeta,
+    pub body: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ParsedDefinition {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+}
+
+/// Splits a markdown file into its YAML frontmatter and body. Returns `None`
+/// for the frontmatter when the file doesn't start with a `---` line.
+fn split_frontmatter(content: &str) -> Result<(Option<&str>, &str), String> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let Some(rest) = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    else {
+        return Ok((None, content));
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return Ok((Some(&rest[..offset]), &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    Err("Frontmatter is not closed with a '---' line".to_string())
+}
+
+/// Parses and lints one agent or command file
+pub fn parse(kind: DefinitionKind, file_name: &str, content: &str) -> ParsedDefinition {
+    let mut parsed = ParsedDefinition {
+        file_name: file_name.to_string(),
+        has_frontmatter: false,
+        meta: DefinitionMeta::default(),
+        body: String::new(),
+        diagnostics: Vec::new(),
+    };
+
+    let (frontmatter, body) = match split_frontmatter(content) {
+        Ok(split) => split,
+        Err(e) => {
+            parsed.body = content.to_string();
+            parsed.diagnostics.push(Diagnostic::error(e));
+            return parsed;
+        }
+    };
+    parsed.body = body.to_string();
+    parsed.has_frontmatter = frontmatter.is_some();
+
+    if !file_name.ends_with(".md") {
+        parsed.diagnostics.push(Diagnostic::error(
+            "claude only loads .md files; rename the file to end in .md",
+        ));
+    }
+
+    let raw = frontmatter.map(raw_fields).unwrap_or_default();
+    let mapping = match frontmatter.map(serde_yaml::from_str::<Value>) {
+        None => serde_yaml::Mapping::new(),
+        Some(Ok(Value::Mapping(mapping))) => mapping,
+        Some(Ok(Value::Null)) => serde_yaml::Mapping::new(),
+        Some(Ok(_)) => {
+            parsed.diagnostics.push(Diagnostic::error("Frontmatter must be a set of key: value pairs"));
+            return parsed;
+        }
+        Some(Err(e)) => {
+            // claude reads frontmatter leniently (e.g. `argument-hint: [a] [b]`
+            // isn't valid YAML), so fall back to plain `key: value` lines
+            parsed.diagnostics.push(Diagnostic::warning(format!(
+                "Frontmatter is not valid YAML, reading it line by line: {}",
+                e
+            )));
+            raw.iter()
+                .map(|(k, v)| (Value::String(k.clone()), Value::String(v.clone())))
+                .collect()
+        }
+    };
+
+    let known_keys = match kind {
+        DefinitionKind::Agent => AGENT_KEYS,
+        DefinitionKind::Command => COMMAND_KEYS,
+    };
+    for key in mapping.keys() {
+        match key.as_str() {
+            Some(key) if known_keys.contains(&key) => {}
+            Some(key) => parsed
+                .diagnostics
+                .push(Diagnostic::warning(format!("Unknown frontmatter key \"{}\"", key))),
+            None => parsed
+                .diagnostics
+                .push(Diagnostic::error("Frontmatter keys must be strings")),
+        }
+    }
+
+    let mut field = |key: &str| -> Option<String> {
+        match mapping.get(key)? {
+            Value::String(s) => Some(s.clone()),
+            Value::Null => None,
+            Value::Bool(b) => Some(b.to_string()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => {
+                parsed
+                    .diagnostics
+                    .push(Diagnostic::error(format!("\"{}\" must be a string", key)));
+                None
+            }
+        }
+    };
+    let name = field("name");
+    let description = field("description");
+    let model = field("model");
+    // Hints like `[message]` parse as YAML lists; keep the text as written
+    let argument_hint = raw
+        .iter()
+        .find(|(k, _)| k == "argument-hint")
+        .map(|(_, v)| v.clone())
+        .filter(|v| !v.is_empty());
+    parsed.meta = DefinitionMeta {
+        name,
+        description,
+        model,
+        argument_hint,
+        ..Default::default()
+    };
+    parsed.meta.tools = tool_list(&mapping, "tools", &mut parsed.diagnostics);
+    parsed.meta.allowed_tools = tool_list(&mapping, "allowed-tools", &mut parsed.diagnostics);
+
+    lint(kind, &mut parsed);
+    parsed
+}
+
+/// Reads top-level `key: value` lines as plain strings, stripping matching
+/// quotes. Indented and comment lines are skipped.
+fn raw_fields(frontmatter: &str) -> Vec<(String, String)> {
+    frontmatter
+        .lines()
+        .filter(|line| !line.starts_with([' ', '\t', '#']))
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Reads a tool list, which claude accepts either as a comma-separated string
+/// or as a YAML list
+fn tool_list(
+    mapping: &serde_yaml::Mapping,
+    key: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<Vec<String>> {
+    let tools: Vec<String> = match mapping.get(key)? {
+        Value::String(s) => split_tools(s),
+        Value::Sequence(items) => items
+            .iter()
+            .filter_map(|item| match item.as_str() {
+                Some(s) => Some(s.trim().to_string()),
+                None => {
+                    diagnostics.push(Diagnostic::error(format!("\"{}\" entries must be strings", key)));
+                    None
+                }
+            })
+            .filter(|s| !s.is_empty())
+            .collect(),
+        Value::Null => return None,
+        _ => {
+            diagnostics.push(Diagnostic::error(format!(
+                "\"{}\" must be a comma-separated string or a list",
+                key
+            )));
+            return None;
+        }
+    };
+    Some(tools)
+}
+
+/// Splits on commas outside parentheses, so `Bash(git add:*, git commit:*)`
+/// stays one entry
+fn split_tools(value: &str) -> Vec<String> {
+    let mut tools = Vec::new();
+    let mut depth = 0usize;
+    let mut current = String::new();
+    for c in value.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                tools.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    tools.push(current);
+    tools
+        .into_iter()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+fn lint(kind: DefinitionKind, parsed: &mut ParsedDefinition) {
+    let meta = &parsed.meta;
+    let mut diagnostics = Vec::new();
+
+    match kind {
+        DefinitionKind::Agent => {
+            if !parsed.has_frontmatter {
+                diagnostics.push(Diagnostic::error(
+                    "Agents need YAML frontmatter with a name and description",
+                ));
+            } else {
+                match meta.name.as_deref().map(str::trim) {
+                    None | Some("") => diagnostics.push(Diagnostic::error("Agent is missing a name")),
+                    Some(name) => {
+                        if !name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') {
+                            diagnostics.push(Diagnostic::warning(format!(
+                                "Agent name \"{}\" should use only lowercase letters, digits and hyphens",
+                                name
+                            )));
+                        }
+                    }
+                }
+                if meta.description.as_deref().is_none_or(|d| d.trim().is_empty()) {
+                    diagnostics.push(Diagnostic::error(
+                        "Agent is missing a description; claude uses it to decide when to delegate",
+                    ));
+                }
+            }
+            if parsed.body.trim().is_empty() {
+                diagnostics.push(Diagnostic::warning("Agent has an empty system prompt"));
+            }
+        }
+        DefinitionKind::Command => {
+            if meta.description.as_deref().is_none_or(|d| d.trim().is_empty()) {
+                diagnostics.push(Diagnostic::warning(
+                    "Command has no description; claude will show the first line of the prompt instead",
+                ));
+            }
+            if parsed.body.trim().is_empty() {
+                diagnostics.push(Diagnostic::error("Command has an empty prompt"));
+            }
+        }
+    }
+
+    for tool in meta.tools.iter().chain(meta.allowed_tools.iter()).flatten() {
+        // Permission rules like `Bash(git status:*)` are checked by tool name
+        let tool_name = tool.split('(').next().unwrap_or(tool).trim();
+        if !tool_name.starts_with("mcp__") && !KNOWN_TOOLS.contains(&tool_name) {
+            diagnostics.push(Diagnostic::warning(format!("Unknown tool \"{}\"", tool_name)));
+        }
+    }
+
+    if let Some(model) = meta.model.as_deref() {
+        if !MODEL_ALIASES.contains(&model) && !model.starts_with("claude-") {
+            diagnostics.push(Diagnostic::warning(format!("Unknown model \"{}\"", model)));
+        }
+    }
+
+    parsed.diagnostics.extend(diagnostics);
+}
+
+/// Flags agents in the same scope that declare the same name; claude only
+/// loads one of them
+pub fn check_duplicate_names(definitions: &mut [ParsedDefinition]) {
+    let names: Vec<(String, Option<String>)> = definitions
+        .iter()
+        .map(|d| (d.file_name.clone(), d.meta.name.as_deref().map(|n| n.trim().to_string())))
+        .collect();
+    for definition in definitions.iter_mut() {
+        let Some(name) = definition.meta.name.as_deref().map(str::trim) else {
+            continue;
+        };
+        let others: Vec<&str> = names
+            .iter()
+            .filter(|(file, other)| *file != definition.file_name && other.as_deref() == Some(name))
+            .map(|(file, _)| file.as_str())
+            .collect();
+        if !others.is_empty() {
+            let message = format!("Agent name \"{}\" is also used by {}", name, others.join(", "));
+            definition.diagnostics.push(Diagnostic::error(message));
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// git itself, spawned directly: no shell parses the arguments, so branch
+/// names and paths reach git as given, and no login shell starts per call
+fn git_command(args: &[&str]) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.args(args);
+    // Prevent a console window from flashing on Windows
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+    cmd
+}
+
+/// Runs git in `dir` and returns its stdout, or its stderr as the error
+pub fn run(dir: &Path, args: &[&str]) -> Result<String, String> {
+    run_with_env(dir, args, &[])
+}
+
+/// Like `run`, with extra environment variables such as `GIT_INDEX_FILE`
+pub fn run_with_env(dir: &Path, args: &[&str], env: &[(&str, &str)]) -> Result<String, String> {
+    let output = git_command(args)
+        .current_dir(dir)
+        .envs(env.iter().copied())
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(if stderr.is_empty() {
+            format!("git {} failed", args.first().unwrap_or(&""))
+        } else {
+            stderr
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Like `run_with_env`, killing git if it hasn't finished within `timeout`,
+/// for background work that mustn't hang on a huge worktree or a stuck
+/// filter
+pub fn run_with_timeout(
+    dir: &Path,
+    args: &[&str],
+    env: &[(&str, &str)],
+    timeout: Duration,
+) -> Result<String, String> {
+    let mut child = git_command(args)
+        .current_dir(dir)
+        .envs(env.iter().copied())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    // Read on other threads so git never blocks on a full pipe
+    let stdout = child.stdout.take().map(read_to_end);
+    let stderr = child.stderr.take().map(read_to_end);
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait().map_err(|e| format!("Failed to run git: {}", e))? {
+            Some(status) => break status,
+            None if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                // The readers are left behind: a process git started may
+                // still hold the pipes open
+                return Err(format!(
+                    "git {} took longer than {}s",
+                    args.first().unwrap_or(&""),
+                    timeout.as_secs()
+                ));
+            }
+            None => std::thread::sleep(Duration::from_millis(20)),
+        }
+    };
+    let stdout = stdout.and_then(|h| h.join().ok()).unwrap_or_default();
+    let stderr = stderr.and_then(|h| h.join().ok()).unwrap_or_default();
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr).trim().to_string();
+        return Err(if stderr.is_empty() {
+            format!("git {} failed", args.first().unwrap_or(&""))
+        } else {
+            stderr
+        });
+    }
+    Ok(String::from_utf8_lossy(&stdout).to_string())
+}
+
+fn read_to_end(mut pipe: impl Read + Send + 'static) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    })
+}
+
+/// Like `run`, feeding `input` to git's stdin, e.g. a patch or commit message
+pub fn run_with_input(dir: &Path, args: &[&str], input: &str) -> Result<String, String> {
+    use std::io::Write;
+
+    let mut child = git_command(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes()).map_err(|e| format!("Failed to write to git: {}", e))?;
+    }
+    let output = child.wait_with_output().map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Pathspec matching exactly one path relative to the repository root,
+/// whatever directory git runs in and whatever characters the path has
+pub fn literal_pathspec(path: &str) -> String {
+    format!(":(top,literal){}", path)
+}
+
+/// Like `run`, with surrounding whitespace trimmed from the output
+pub fn run_trimmed(dir: &Path, args: &[&str]) -> Result<String, String> {
+    run(dir, args).map(|out| out.trim().to_string())
+}
+
+/// Top level of the repository containing `dir`, if any
+pub fn toplevel(dir: &Path) -> Option<String> {
+    run_trimmed(dir, &["rev-parse", "--show-toplevel"]).ok()
+}
+
+/// Checked-out branch name, or None when HEAD is detached
+pub fn current_branch(dir: &Path) -> Option<String> {
+    run_trimmed(dir, &["symbolic-ref", "--quiet", "--short", "HEAD"]).ok()
+}
+
+/// Whether tracked files (and untracked ones, if asked) have changes
+pub fn is_dirty(dir: &Path, include_untracked: bool) -> Result<bool, String> {
+    let untracked = if include_untracked { "--untracked-files=normal" } else { "--untracked-files=no" };
+    Ok(!run(dir, &["status", "--porcelain", untracked])?.trim().is_empty())
+}
@@ -0,0 +1,358 @@
+use crate::diagnostics::Diagnostic;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Claude's default hook timeout, used when a hook doesn't set its own
+const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 60;
+
+/// Upper bound on how long a dry run may block
+const MAX_DRY_RUN_TIMEOUT_SECS: u64 = 120;
+
+/// Maximum amount of stdout/stderr returned from a dry run (64 KB each)
+const MAX_DRY_RUN_OUTPUT: usize = 64 * 1024;
+
+/// Hook events claude understands
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum HookEvent {
+    PreToolUse,
+    PostToolUse,
+    Notification,
+    UserPromptSubmit,
+    Stop,
+    SubagentStop,
+    PreCompact,
+    SessionStart,
+    SessionEnd,
+}
+
+impl HookEvent {
+    pub const ALL: &'static [HookEvent] = &[
+        HookEvent::PreToolUse,
+        HookEvent::PostToolUse,
+        HookEvent::Notification,
+        HookEvent::UserPromptSubmit,
+        HookEvent::Stop,
+        HookEvent::SubagentStop,
+        HookEvent::PreCompact,
+        HookEvent::SessionStart,
+        HookEvent::SessionEnd,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::PreToolUse => "PreToolUse",
+            HookEvent::PostToolUse => "PostToolUse",
+            HookEvent::Notification => "Notification",
+            HookEvent::UserPromptSubmit => "UserPromptSubmit",
+            HookEvent::Stop => "Stop",
+            HookEvent::SubagentStop => "SubagentStop",
+            HookEvent::PreCompact => "PreCompact",
+            HookEvent::SessionStart => "SessionStart",
+            HookEvent::SessionEnd => "SessionEnd",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|e| e.name() == name)
+    }
+
+    /// Events whose groups are filtered by a matcher. Tool events match tool
+    /// names; PreCompact and SessionStart match their trigger.
+    pub fn uses_matcher(&self) -> bool {
+        matches!(
+            self,
+            HookEvent::PreToolUse | HookEvent::PostToolUse | HookEvent::PreCompact | HookEvent::SessionStart
+        )
+    }
+
+    /// A representative payload for dry runs, shaped like what claude sends on stdin
+    pub fn sample_payload(&self, cwd: &str) -> Value {
+        let mut payload = json!({
+            "session_id": "dry-run",
+            "transcript_path": "",
+            "cwd": cwd,
+            "hook_event_name": self.name(),
+        });
+        let extra = match self {
+            HookEvent::PreToolUse => json!({
+                "tool_name": "Bash",
+                "tool_input": { "command": "echo hello", "description": "Print hello" },
+            }),
+            HookEvent::PostToolUse => json!({
+                "tool_name": "Bash",
+                "tool_input": { "command": "echo hello", "description": "Print hello" },
+                "tool_response": { "stdout": "hello\n", "stderr": "", "interrupted": false },
+            }),
+            HookEvent::Notification => json!({ "message": "Claude needs your permission to use Bash" }),
+            HookEvent::UserPromptSubmit => json!({ "prompt": "Write a function that adds two numbers" }),
+            HookEvent::Stop | HookEvent::SubagentStop => json!({ "stop_hook_active": false }),
+            HookEvent::PreCompact => json!({ "trigger": "manual", "custom_instructions": "" }),
+            HookEvent::SessionStart => json!({ "source": "startup" }),
+            HookEvent::SessionEnd => json!({ "reason": "exit" }),
+        };
+        if let (Some(payload), Value::Object(extra)) = (payload.as_object_mut(), extra) {
+            payload.extend(extra);
+        }
+        payload
+    }
+}
+
+/// One `{ "matcher": ..., "hooks": [...] }` group under an event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookMatcher {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matcher: Option<String>,
+    #[serde(default)]
+    pub hooks: Vec<HookCommand>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookCommand {
+    #[serde(rename = "type")]
+    pub hook_type: String,
+    #[serde(default)]
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HookEventEntry {
+    pub event: String,
+    pub known: bool,
+    pub matchers: Vec<HookMatcher>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HookDryRunResult {
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub timed_out: bool,
+    pub duration_ms: u64,
+    /// What claude would do with this exit code ("continue", "block" or "error")
+    pub outcome: String,
+}
+
+/// Parses the `hooks` value of a settings file into typed events. Events this
+/// app doesn't know are still returned (with `known: false`) so nothing is lost.
+pub fn parse_hooks(hooks: Option<&Value>) -> Result<Vec<HookEventEntry>, String> {
+    let Some(hooks) = hooks else {
+        return Ok(vec![]);
+    };
+    let hooks = hooks.as_object().ok_or("\"hooks\" must be an object")?;
+    hooks
+        .iter()
+        .map(|(event, groups)| {
+            let matchers: Vec<HookMatcher> = serde_json::from_value(groups.clone())
+                .map_err(|e| format!("Invalid hooks for {}: {}", event, e))?;
+            Ok(HookEventEntry {
+                event: event.clone(),
+                known: HookEvent::from_name(event).is_some(),
+                matchers,
+            })
+        })
+        .collect()
+}
+
+pub fn hooks_to_value(entries: &[HookEventEntry]) -> Result<Value, String> {
+    let mut map = Map::new();
+    for entry in entries {
+        if entry.matchers.is_empty() {
+            continue;
+        }
+        map.insert(
+            entry.event.clone(),
+            serde_json::to_value(&entry.matchers).map_err(|e| e.to_string())?,
+        );
+    }
+    Ok(Value::Object(map))
+}
+
+/// Checks hook events, matchers and commands. Paths are relative to the
+/// `hooks` key, e.g. `PreToolUse[0].hooks[1]`.
+pub fn validate_hooks(entries: &[HookEventEntry]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for entry in entries {
+        let Some(event) = HookEvent::from_name(&entry.event) else {
+            diagnostics.push(
+                Diagnostic::warning(format!("Unknown hook event \"{}\"", entry.event)).at(&entry.event),
+            );
+            continue;
+        };
+
+        for (group_index, group) in entry.matchers.iter().enumerate() {
+            let group_path = format!("{}[{}]", entry.event, group_index);
+            match group.matcher.as_deref() {
+                Some(matcher) if !matcher.is_empty() && !event.uses_matcher() => {
+                    diagnostics.push(
+                        Diagnostic::warning(format!("{} hooks ignore matchers", event.name())).at(&group_path),
+                    );
+                }
+                Some(matcher) if !matcher.is_empty() && matcher != "*" => {
+                    // Matchers are JavaScript regexes, which this only approximates
+                    if let Err(e) = regex::Regex::new(matcher) {
+                        diagnostics.push(
+                            Diagnostic::warning(format!("Matcher may not be a valid pattern: {}", e)).at(&group_path),
+                        );
+                    }
+                }
+                _ => {}
+            }
+            if group.hooks.is_empty() {
+                diagnostics.push(Diagnostic::warning("Matcher has no hooks").at(&group_path));
+            }
+            for (hook_index, hook) in group.hooks.iter().enumerate() {
+                let hook_path = format!("{}.hooks[{}]", group_path, hook_index);
+                diagnostics.extend(validate_hook_command(hook).into_iter().map(|d| d.at(&hook_path)));
+            }
+        }
+    }
+    diagnostics
+}
+
+pub fn validate_hook_command(hook: &HookCommand) -> Vec<Diagnostic> {
+    let mut problems = Vec::new();
+    if hook.hook_type != "command" {
+        problems.push(Diagnostic::warning(format!(
+            "Hook type \"{}\" is not a command hook and can't be dry-run",
+            hook.hook_type
+        )));
+    } else if hook.command.trim().is_empty() {
+        problems.push(Diagnostic::error("Hook command is empty"));
+    }
+    if hook.command.contains('\0') {
+        problems.push(Diagnostic::error("Hook command contains a NUL character"));
+    }
+    if hook.timeout == Some(0) {
+        problems.push(Diagnostic::error("Timeout must be at least 1 second"));
+    }
+    problems
+}
+
+/// Runs a hook command the way claude would: through the shell, with the event
+/// payload as JSON on stdin and `CLAUDE_PROJECT_DIR` set to the working directory.
+pub fn dry_run(hook: &HookCommand, payload: &Value, cwd: &Path) -> Result<HookDryRunResult, String> {
+    if hook.hook_type != "command" {
+        return Err("Only command hooks can be dry-run".to_string());
+    }
+    if hook.command.trim().is_empty() {
+        return Err("Hook command is empty".to_string());
+    }
+    let timeout = Duration::from_secs(
+        hook.timeout
+            .unwrap_or(DEFAULT_HOOK_TIMEOUT_SECS)
+            .clamp(1, MAX_DRY_RUN_TIMEOUT_SECS),
+    );
+
+    let mut cmd = if cfg!(target_os = "windows") {
+        let mut c = Command::new("cmd");
+        c.arg("/C").arg(&hook.command);
+        c
+    } else {
+        let mut c = Command::new("/bin/sh");
+        c.arg("-c").arg(&hook.command);
+        c
+    };
+    cmd.current_dir(cwd)
+        .env("CLAUDE_PROJECT_DIR", cwd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let started = Instant::now();
+    let mut child = cmd.spawn().map_err(|e| format!("Failed to run hook: {}", e))?;
+
+    let input = serde_json::to_vec(payload).map_err(|e| e.to_string())?;
+    let mut stdin = child.stdin.take().ok_or("Failed to open hook stdin")?;
+    // Write from a thread so a hook that never reads stdin can't block us
+    std::thread::spawn(move || {
+        let _ = stdin.write_all(&input);
+    });
+    let stdout = child.stdout.take().ok_or("Failed to open hook stdout")?;
+    let stderr = child.stderr.take().ok_or("Failed to open hook stderr")?;
+    let stdout_capture = OutputCapture::spawn(stdout);
+    let stderr_capture = OutputCapture::spawn(stderr);
+
+    let mut timed_out = false;
+    let status = loop {
+        match child.try_wait().map_err(|e| e.to_string())? {
+            Some(status) => break Some(status),
+            None if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                timed_out = true;
+                break None;
+            }
+            None => std::thread::sleep(Duration::from_millis(20)),
+        }
+    };
+
+    let exit_code = status.and_then(|s| s.code());
+    let outcome = match (timed_out, exit_code) {
+        (true, _) => "error",
+        (false, Some(0)) => "continue",
+        (false, Some(2)) => "block",
+        _ => "error",
+    };
+    Ok(HookDryRunResult {
+        exit_code,
+        stdout: stdout_capture.finish(),
+        stderr: stderr_capture.finish(),
+        timed_out,
+        duration_ms: started.elapsed().as_millis() as u64,
+        outcome: outcome.to_string(),
+    })
+}
+
+/// Collects a pipe's output in the background, keeping at most
+/// `MAX_DRY_RUN_OUTPUT` bytes while still draining the rest so the hook
+/// never blocks on a full pipe
+struct OutputCapture {
+    buffer: Arc<Mutex<Vec<u8>>>,
+    handle: std::thread::JoinHandle<()>,
+}
+
+impl OutputCapture {
+    fn spawn(mut reader: impl Read + Send + 'static) -> Self {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let sink = buffer.clone();
+        let handle = std::thread::spawn(move || {
+            let mut chunk = [0u8; 8192];
+            while let Ok(n) = reader.read(&mut chunk) {
+                if n == 0 {
+                    break;
+                }
+                let mut buf = sink.lock().unwrap_or_else(|e| e.into_inner());
+                let room = MAX_DRY_RUN_OUTPUT.saturating_sub(buf.len());
+                buf.extend_from_slice(&chunk[..n.min(room)]);
+            }
+        });
+        Self { buffer, handle }
+    }
+
+    /// Returns what was captured. Background processes started by the hook can
+    /// keep the pipe open after it exits, so this waits only briefly for EOF.
+    fn finish(self) -> String {
+        let deadline = Instant::now() + Duration::from_millis(500);
+        while !self.handle.is_finished() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let buf = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+        String::from_utf8_lossy(&buf).into_owned()
+    }
+}
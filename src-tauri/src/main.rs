@@ -4,6 +4,30 @@ mod commands;
 mod terminal;
 mod config;
 mod database;
+mod diagnostics;
+mod bundle;
+mod project_file;
+mod workspace_diff;
+mod mcp;
+mod settings_file;
+mod hooks;
+mod claude_dir;
+mod frontmatter;
+mod memory;
+mod teams;
+mod policy;
+mod auto_approve;
+mod git;
+mod changes;
+mod worktree;
+mod attribution;
+mod checkpoint;
+mod files_watcher;
+mod forge;
+mod pull_request;
+mod repo;
+mod conflicts;
+mod watch;
 
 use tauri::Manager;
 use std::sync::Arc;
@@ -12,6 +36,8 @@ use tokio::sync::Mutex;
 pub struct AppState {
     pub terminals: Arc<Mutex<terminal::TerminalManager>>,
     pub db: Arc<Mutex<database::Database>>,
+    pub file_tracker: attribution::ChangeTracker,
+    pub conflicts: conflicts::ConflictMonitor,
 }
 
 fn main() {
@@ -23,14 +49,24 @@ fn main() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .setup(|app| {
-            let db = database::Database::new()?;
+            let db = database::Database::open_or_fallback()?;
             let terminal_manager = terminal::TerminalManager::new();
 
+            let db = Arc::new(Mutex::new(db));
+            let file_tracker = attribution::ChangeTracker::start(db.clone());
+            let conflicts = conflicts::ConflictMonitor::new(file_tracker.clone());
+
             app.manage(AppState {
                 terminals: Arc::new(Mutex::new(terminal_manager)),
-                db: Arc::new(Mutex::new(db)),
+                db,
+                file_tracker,
+                conflicts,
             });
 
+            if let Ok(claude_dir) = commands::get_claude_dir() {
+                teams::start_watcher(app.handle().clone(), claude_dir);
+            }
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -39,11 +75,25 @@ fn main() {
             commands::resize_terminal,
             commands::close_terminal,
             commands::get_terminals,
+            commands::get_worktrees,
+            commands::finish_worktree,
             commands::update_terminal_label,
+            commands::update_terminal_color_tag,
             commands::update_terminal_nickname,
             commands::save_profile,
             commands::get_profiles,
             commands::delete_profile,
+            commands::get_permission_policies,
+            commands::save_permission_policy,
+            commands::delete_permission_policy,
+            commands::preview_permission_policy,
+            commands::get_auto_approve_rules,
+            commands::save_auto_approve_rule,
+            commands::delete_auto_approve_rule,
+            commands::get_auto_approve_audit,
+            commands::test_auto_approve_rules,
+            commands::export_profiles,
+            commands::import_profiles,
             commands::get_claude_version,
             commands::check_claude_update,
             commands::update_claude_code,
@@ -52,6 +102,12 @@ fn main() {
             commands::delete_workspace,
             commands::save_workspace,
             commands::load_workspace,
+            commands::launch_workspace,
+            commands::list_workspace_revisions,
+            commands::diff_workspace_revisions,
+            commands::restore_workspace_revision,
+            commands::load_project_workspace,
+            commands::trust_project_file,
             commands::save_session_for_restore,
             commands::get_last_session,
             commands::clear_last_session,
@@ -60,16 +116,48 @@ fn main() {
             commands::open_external_url,
             commands::send_notification,
             commands::get_terminal_changes,
+            commands::stage_terminal_files,
+            commands::unstage_terminal_files,
+            commands::toggle_terminal_hunk,
+            commands::discard_terminal_files,
+            commands::list_discard_snapshots,
+            commands::restore_discard_snapshot,
+            commands::commit_terminal_changes,
+            commands::describe_pull_request,
+            commands::create_pull_request,
+            commands::get_terminal_conflicts,
+            commands::create_checkpoint,
+            commands::list_checkpoints,
+            commands::diff_checkpoints,
+            commands::restore_checkpoint,
+            commands::delete_checkpoints,
+            commands::get_database_status,
+            commands::retry_database_open,
+            commands::restore_database_backup,
             commands::get_session_history,
             commands::get_session_log,
             commands::read_log_file,
             commands::delete_session_history,
+            commands::get_session_files,
+            commands::get_session_timeline,
+            commands::get_file_sessions,
             commands::save_snippet,
             commands::get_snippets,
             commands::delete_snippet,
             commands::get_active_teams,
+            commands::get_team_tasks,
+            commands::join_team_terminal,
             commands::read_claude_settings,
             commands::write_claude_settings,
+            commands::validate_claude_settings,
+            commands::list_claude_settings_backups,
+            commands::restore_claude_settings_backup,
+            commands::get_claude_hooks,
+            commands::add_claude_hook,
+            commands::update_claude_hook,
+            commands::set_claude_hook_matcher,
+            commands::delete_claude_hook,
+            commands::dry_run_claude_hook,
             commands::list_claude_agents,
             commands::read_claude_agent,
             commands::write_claude_agent,
@@ -78,6 +166,22 @@ fn main() {
             commands::read_claude_command,
             commands::write_claude_command,
             commands::delete_claude_command,
+            commands::lint_claude_definition,
+            commands::lint_claude_definitions,
+            commands::list_claude_definitions,
+            commands::diff_claude_definition,
+            commands::copy_claude_definition,
+            commands::promote_claude_definition,
+            commands::get_memory_preview,
+            commands::read_memory_file,
+            commands::write_memory_file,
+            commands::list_mcp_servers,
+            commands::validate_mcp_server,
+            commands::add_mcp_server,
+            commands::update_mcp_server,
+            commands::remove_mcp_server,
+            commands::set_mcp_server_enabled,
+            commands::test_mcp_server,
         ])
         .on_window_event(|window, event| {
             if let tauri::WindowEvent::CloseRequested { .. } = event {
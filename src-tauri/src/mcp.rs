@@ -0,0 +1,521 @@
+use crate::diagnostics::Diagnostic;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// How long a test launch may take end to end before the server is killed
+const TEST_LAUNCH_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Maximum amount of server stderr kept for error reports (16 KB)
+const MAX_STDERR_CAPTURE: usize = 16 * 1024;
+
+/// MCP protocol version sent in the initialize request
+const MCP_PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// Where an MCP server definition lives
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum McpScope {
+    /// `mcpServers` in the user's `~/.claude.json`
+    User,
+    /// `mcpServers` in `<path>/.mcp.json`, shared with the repository
+    Project { path: String },
+}
+
+/// A server definition as claude stores it. Keys this app doesn't know about
+/// are kept in `extra` so they survive an edit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub server_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl McpServerConfig {
+    pub fn is_stdio(&self) -> bool {
+        matches!(self.server_type.as_deref(), None | Some("stdio"))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct McpServerEntry {
+    pub name: String,
+    pub scope: McpScope,
+    pub enabled: bool,
+    pub config: McpServerConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct McpTool {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct McpTestResult {
+    pub server_name: Option<String>,
+    pub server_version: Option<String>,
+    pub protocol_version: Option<String>,
+    pub tools: Vec<McpTool>,
+    pub stderr: String,
+    pub duration_ms: u64,
+}
+
+// JSON config files
+
+/// Reads a JSON file that must contain an object. A missing file is treated as
+/// empty; an unparseable one is an error so it never gets overwritten.
+pub fn read_json_object(path: &Path) -> Result<Map<String, Value>, String> {
+    if !path.exists() {
+        return Ok(Map::new());
+    }
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if content.trim().is_empty() {
+        return Ok(Map::new());
+    }
+    match serde_json::from_str::<Value>(&content) {
+        Ok(Value::Object(map)) => Ok(map),
+        Ok(_) => Err(format!("{} does not contain a JSON object", path.display())),
+        Err(e) => Err(format!("Failed to parse {}: {}", path.display(), e)),
+    }
+}
+
+pub fn write_json_object(path: &Path, map: &Map<String, Value>) -> Result<(), String> {
+    let mut content = serde_json::to_string_pretty(map).map_err(|e| e.to_string())?;
+    content.push('\n');
+    crate::settings_file::write_atomic(path, &content)
+}
+
+/// File holding the server definitions for a scope
+pub fn servers_file(scope: &McpScope, home: &Path) -> PathBuf {
+    match scope {
+        McpScope::User => home.join(".claude.json"),
+        McpScope::Project { path } => Path::new(path).join(".mcp.json"),
+    }
+}
+
+/// Project-local settings where claude records which `.mcp.json` servers are
+/// enabled or disabled. This file is not meant to be committed.
+pub fn project_local_settings(path: &str) -> PathBuf {
+    Path::new(path).join(".claude").join("settings.local.json")
+}
+
+pub fn read_servers(path: &Path) -> Result<Vec<(String, McpServerConfig)>, String> {
+    let root = read_json_object(path)?;
+    let Some(servers) = root.get("mcpServers") else {
+        return Ok(vec![]);
+    };
+    let servers = servers
+        .as_object()
+        .ok_or_else(|| format!("mcpServers in {} is not an object", path.display()))?;
+    servers
+        .iter()
+        .map(|(name, value)| {
+            serde_json::from_value::<McpServerConfig>(value.clone())
+                .map(|config| (name.clone(), config))
+                .map_err(|e| format!("Invalid MCP server \"{}\": {}", name, e))
+        })
+        .collect()
+}
+
+/// Applies `update` to the `mcpServers` object of a config file, leaving every
+/// other key untouched
+pub fn update_servers<F>(path: &Path, update: F) -> Result<(), String>
+where
+    F: FnOnce(&mut Map<String, Value>) -> Result<(), String>,
+{
+    let mut root = read_json_object(path)?;
+    let servers = root
+        .entry("mcpServers")
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .ok_or_else(|| format!("mcpServers in {} is not an object", path.display()))?;
+    update(servers)?;
+    write_json_object(path, &root)
+}
+
+/// Names listed under `key` (e.g. `disabledMcpjsonServers`) in a settings file
+pub fn read_name_list(settings: &Map<String, Value>, key: &str) -> Vec<String> {
+    settings
+        .get(key)
+        .and_then(|v| v.as_array())
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|v| v.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Marks a project `.mcp.json` server as enabled or disabled in the project's
+/// local settings, the same way claude records its own approval prompts
+pub fn set_project_server_enabled(project: &str, name: &str, enabled: bool) -> Result<(), String> {
+    let path = project_local_settings(project);
+    let mut settings = read_json_object(&path)?;
+    let (add_to, remove_from) = if enabled {
+        ("enabledMcpjsonServers", "disabledMcpjsonServers")
+    } else {
+        ("disabledMcpjsonServers", "enabledMcpjsonServers")
+    };
+
+    let mut remove = read_name_list(&settings, remove_from);
+    remove.retain(|n| n != name);
+    settings.insert(remove_from.to_string(), json!(remove));
+
+    let mut add = read_name_list(&settings, add_to);
+    if !add.iter().any(|n| n == name) {
+        add.push(name.to_string());
+    }
+    settings.insert(add_to.to_string(), json!(add));
+
+    write_json_object(&path, &settings)
+}
+
+// Validation
+
+fn is_valid_server_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn is_valid_env_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Checks a server definition. `cwd` is the directory relative commands are
+/// resolved against (the project root for `.mcp.json` servers).
+pub fn validate_server(name: &str, config: &McpServerConfig, cwd: Option<&Path>) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    if !is_valid_server_name(name) {
+        diagnostics.push(Diagnostic::error(
+            "Server name must be 1-64 characters of letters, digits, '-' or '_'",
+        ));
+    }
+
+    match config.server_type.as_deref() {
+        None | Some("stdio") => match config.command.as_deref().map(str::trim) {
+            None | Some("") => diagnostics.push(Diagnostic::error("stdio servers need a command")),
+            Some(command) => {
+                if let Some(diagnostic) = check_command(command, cwd) {
+                    diagnostics.push(diagnostic);
+                }
+            }
+        },
+        Some("http") | Some("sse") => match config.url.as_deref() {
+            Some(url) if url.starts_with("https://") || url.starts_with("http://") || url.starts_with("${") => {}
+            Some(_) => diagnostics.push(Diagnostic::error("Server URL must start with http:// or https://")),
+            None => diagnostics.push(Diagnostic::error("HTTP and SSE servers need a url")),
+        },
+        Some(other) => diagnostics.push(Diagnostic::error(format!(
+            "Unknown server type \"{}\" (expected stdio, http or sse)",
+            other
+        ))),
+    }
+
+    if config.args.iter().any(|a| a.contains('\0')) {
+        diagnostics.push(Diagnostic::error("Arguments must not contain NUL characters"));
+    }
+    for (key, value) in &config.env {
+        if !is_valid_env_key(key) {
+            diagnostics.push(Diagnostic::error(format!("Invalid environment variable name \"{}\"", key)));
+        }
+        if value.contains('\0') {
+            diagnostics.push(Diagnostic::error(format!("Environment variable \"{}\" contains a NUL character", key)));
+        }
+    }
+    for key in config.headers.keys() {
+        if key.is_empty() || key.chars().any(|c| c.is_whitespace() || c == ':') {
+            diagnostics.push(Diagnostic::error(format!("Invalid header name \"{}\"", key)));
+        }
+    }
+
+    diagnostics
+}
+
+fn check_command(command: &str, cwd: Option<&Path>) -> Option<Diagnostic> {
+    if command.contains('\0') {
+        return Some(Diagnostic::error("Command must not contain NUL characters"));
+    }
+    // Commands built from environment variables can only be checked at launch
+    if command.contains("${") {
+        return None;
+    }
+    if command.contains('/') || command.contains('\\') {
+        let path = match cwd {
+            Some(dir) if Path::new(command).is_relative() => dir.join(command),
+            _ => PathBuf::from(command),
+        };
+        if !path.is_file() {
+            return Some(Diagnostic::error(format!("Command not found: {}", path.display())));
+        }
+        return None;
+    }
+    if find_on_path(command).is_none() {
+        // The app's PATH can be narrower than a login shell's, so this is only a hint
+        return Some(Diagnostic::warning(format!(
+            "\"{}\" was not found on PATH; it may still resolve from claude's shell",
+            command
+        )));
+    }
+    None
+}
+
+fn find_on_path(command: &str) -> Option<PathBuf> {
+    let path_var = std::env::var_os("PATH")?;
+    let extensions: Vec<String> = if cfg!(target_os = "windows") {
+        std::env::var("PATHEXT")
+            .unwrap_or_else(|_| ".EXE;.CMD;.BAT;.COM".to_string())
+            .split(';')
+            .map(|e| e.to_string())
+            .chain(std::iter::once(String::new()))
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+    std::env::split_paths(&path_var).find_map(|dir| {
+        extensions
+            .iter()
+            .map(|ext| dir.join(format!("{}{}", command, ext)))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+/// Expands `${VAR}` and `${VAR:-default}` the way claude does for MCP configs
+pub fn expand_env_vars(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find("${") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            output.push_str(&rest[start..]);
+            return output;
+        };
+        let expr = &after[..end];
+        let (var, default) = match expr.split_once(":-") {
+            Some((var, default)) => (var, Some(default)),
+            None => (expr, None),
+        };
+        match std::env::var(var) {
+            Ok(value) => output.push_str(&value),
+            Err(_) => output.push_str(default.unwrap_or("")),
+        }
+        rest = &after[end + 1..];
+    }
+    output.push_str(rest);
+    output
+}
+
+// Test launch
+
+/// Kills the server process when the test launch ends, however it ends
+struct ChildGuard(Child);
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Spawns a stdio server, performs the MCP initialize handshake and lists its
+/// tools. The process is always killed afterwards.
+pub fn test_launch(config: &McpServerConfig, cwd: Option<&Path>) -> Result<McpTestResult, String> {
+    if !config.is_stdio() {
+        return Err("Only stdio servers can be test-launched".to_string());
+    }
+    let command = config
+        .command
+        .as_deref()
+        .map(expand_env_vars)
+        .filter(|c| !c.trim().is_empty())
+        .ok_or("Server has no command")?;
+    let args: Vec<String> = config.args.iter().map(|a| expand_env_vars(a)).collect();
+
+    let mut cmd = if cfg!(target_os = "windows") {
+        // Resolve .cmd/.bat shims such as npx.cmd
+        let mut c = Command::new("cmd");
+        c.arg("/C").arg(&command);
+        c
+    } else {
+        Command::new(&command)
+    };
+    cmd.args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    for (key, value) in &config.env {
+        cmd.env(key, expand_env_vars(value));
+    }
+    if let Some(dir) = cwd {
+        cmd.current_dir(dir);
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    }
+
+    let started = Instant::now();
+    let deadline = started + TEST_LAUNCH_TIMEOUT;
+    let mut child = ChildGuard(
+        cmd.spawn()
+            .map_err(|e| format!("Failed to start \"{}\": {}", command, e))?,
+    );
+    let mut stdin = child.0.stdin.take().ok_or("Failed to open server stdin")?;
+    let stdout = child.0.stdout.take().ok_or("Failed to open server stdout")?;
+    let stderr = child.0.stderr.take().ok_or("Failed to open server stderr")?;
+
+    let (tx, rx) = mpsc::channel::<String>();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            match line {
+                Ok(line) => {
+                    if tx.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+    let stderr_handle = std::thread::spawn(move || {
+        let mut captured = Vec::new();
+        let _ = stderr.take(MAX_STDERR_CAPTURE as u64).read_to_end(&mut captured);
+        String::from_utf8_lossy(&captured).into_owned()
+    });
+
+    let handshake = (|| {
+        send_message(&mut stdin, &json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": MCP_PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": { "name": "ClaudeTerminal", "version": env!("CARGO_PKG_VERSION") },
+            },
+        }))?;
+        let init = wait_for_response(&rx, 1, deadline)?;
+        send_message(&mut stdin, &json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/initialized",
+        }))?;
+
+        let tools = if init.get("capabilities").and_then(|c| c.get("tools")).is_some() {
+            send_message(&mut stdin, &json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "tools/list",
+            }))?;
+            let list = wait_for_response(&rx, 2, deadline)?;
+            list.get("tools")
+                .and_then(|t| t.as_array())
+                .map(|tools| {
+                    tools
+                        .iter()
+                        .filter_map(|t| {
+                            Some(McpTool {
+                                name: t.get("name")?.as_str()?.to_string(),
+                                description: t.get("description").and_then(|d| d.as_str()).map(String::from),
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        } else {
+            vec![]
+        };
+        Ok::<_, String>((init, tools))
+    })();
+
+    drop(stdin);
+    drop(child);
+    let stderr = stderr_handle.join().unwrap_or_default();
+
+    let (init, tools) = match handshake {
+        Ok(result) => result,
+        Err(e) if stderr.trim().is_empty() => return Err(e),
+        Err(e) => return Err(format!("{}\n\nServer stderr:\n{}", e, stderr.trim())),
+    };
+    let server_info = init.get("serverInfo");
+    Ok(McpTestResult {
+        server_name: server_info
+            .and_then(|i| i.get("name"))
+            .and_then(|v| v.as_str())
+            .map(String::from),
+        server_version: server_info
+            .and_then(|i| i.get("version"))
+            .and_then(|v| v.as_str())
+            .map(String::from),
+        protocol_version: init
+            .get("protocolVersion")
+            .and_then(|v| v.as_str())
+            .map(String::from),
+        tools,
+        stderr,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+fn send_message(stdin: &mut impl Write, message: &Value) -> Result<(), String> {
+    let mut line = serde_json::to_string(message).map_err(|e| e.to_string())?;
+    line.push('\n');
+    stdin
+        .write_all(line.as_bytes())
+        .and_then(|_| stdin.flush())
+        .map_err(|e| format!("Failed to write to server: {}", e))
+}
+
+/// Waits for the JSON-RPC response with the given id, skipping notifications,
+/// requests from the server and any non-JSON output
+fn wait_for_response(rx: &mpsc::Receiver<String>, id: u64, deadline: Instant) -> Result<Value, String> {
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let line = match rx.recv_timeout(remaining) {
+            Ok(line) => line,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                return Err("Timed out waiting for the server to respond".to_string())
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err("Server exited before completing the handshake".to_string())
+            }
+        };
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        if message.get("id").and_then(|v| v.as_u64()) != Some(id) || message.get("method").is_some() {
+            continue;
+        }
+        if let Some(error) = message.get("error") {
+            let text = error
+                .get("message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("unknown error");
+            return Err(format!("Server returned an error: {}", text));
+        }
+        return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+    }
+}
@@ -0,0 +1,431 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// claude follows `@path` imports at most this many hops deep
+const MAX_IMPORT_DEPTH: usize = 5;
+
+/// Directory levels searched below the working directory for nested CLAUDE.md files
+const MAX_NESTED_DEPTH: usize = 6;
+
+/// Upper bound on directories visited while looking for nested files, so huge
+/// trees don't stall the scan
+const MAX_NESTED_DIRS: usize = 5000;
+
+/// Largest memory or imported file read (1 MB)
+const MAX_MEMORY_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Directories never searched for nested memory files
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "vendor", "dist", "build", "__pycache__"];
+
+/// File names claude loads as memory
+const MEMORY_FILE_NAMES: &[&str] = &["CLAUDE.md", "CLAUDE.local.md"];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryScope {
+    /// Organization-wide policy file
+    Enterprise,
+    /// `~/.claude/CLAUDE.md`
+    User,
+    /// `CLAUDE.md` or `.claude/CLAUDE.md` in the working directory or a parent
+    Project,
+    /// `CLAUDE.local.md` in the working directory or a parent
+    Local,
+    /// `CLAUDE.md` below the working directory, loaded only when claude reads
+    /// files in that subtree
+    Nested,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryImport {
+    /// The import as written, without the leading `@`
+    pub raw: String,
+    /// Resolved path, even when the file doesn't exist
+    pub path: String,
+    /// File containing the import
+    pub from: String,
+    pub found: bool,
+    /// 1 for imports written directly in a memory file
+    pub depth: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryFile {
+    pub path: String,
+    pub scope: MemoryScope,
+    /// Whether claude loads this file when it starts in the directory
+    pub loaded_at_startup: bool,
+    pub size: u64,
+    pub modified_at: Option<String>,
+    pub imports: Vec<MemoryImport>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryPreview {
+    pub directory: String,
+    pub files: Vec<MemoryFile>,
+    /// Everything loaded at startup, in load order, with imports inlined
+    pub merged: String,
+    pub diagnostics: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MemoryFileContent {
+    pub path: String,
+    pub content: String,
+    /// Pass back to `write` to detect edits made elsewhere in the meantime
+    pub modified_at: Option<String>,
+}
+
+fn enterprise_path() -> PathBuf {
+    if cfg!(target_os = "windows") {
+        PathBuf::from(r"C:\ProgramData\ClaudeCode\CLAUDE.md")
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from("/Library/Application Support/ClaudeCode/CLAUDE.md")
+    } else {
+        PathBuf::from("/etc/claude-code/CLAUDE.md")
+    }
+}
+
+fn modified_at(path: &Path) -> Option<String> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(chrono::DateTime::<chrono::Utc>::from(modified).to_rfc3339())
+}
+
+/// Lists the memory files that apply to `directory` in the order claude loads
+/// them: enterprise, user, then project and local files from the outermost
+/// parent down, followed by nested files that load on demand.
+fn discover_paths(directory: &Path, home: &Path) -> Vec<(PathBuf, MemoryScope)> {
+    let mut found = Vec::new();
+    let enterprise = enterprise_path();
+    if enterprise.is_file() {
+        found.push((enterprise, MemoryScope::Enterprise));
+    }
+    let user = home.join(".claude").join("CLAUDE.md");
+    if user.is_file() {
+        found.push((user, MemoryScope::User));
+    }
+
+    // claude walks up to, but not including, the filesystem root
+    let ancestors: Vec<&Path> = directory.ancestors().filter(|dir| dir.parent().is_some()).collect();
+    for dir in ancestors.into_iter().rev() {
+        for (path, scope) in [
+            (dir.join("CLAUDE.md"), MemoryScope::Project),
+            (dir.join(".claude").join("CLAUDE.md"), MemoryScope::Project),
+            (dir.join("CLAUDE.local.md"), MemoryScope::Local),
+        ] {
+            if path.is_file() {
+                found.push((path, scope));
+            }
+        }
+    }
+
+    let mut visited = 0;
+    find_nested(directory, 0, &mut visited, &mut found);
+    found
+}
+
+fn find_nested(dir: &Path, depth: usize, visited: &mut usize, found: &mut Vec<(PathBuf, MemoryScope)>) {
+    if depth >= MAX_NESTED_DEPTH {
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut subdirs: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .filter(|e| {
+            let name = e.file_name();
+            let name = name.to_string_lossy();
+            !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref())
+        })
+        .map(|e| e.path())
+        .collect();
+    subdirs.sort();
+    for subdir in subdirs {
+        *visited += 1;
+        if *visited > MAX_NESTED_DIRS {
+            return;
+        }
+        for name in MEMORY_FILE_NAMES {
+            let path = subdir.join(name);
+            if path.is_file() {
+                found.push((path, MemoryScope::Nested));
+            }
+        }
+        find_nested(&subdir, depth + 1, visited, found);
+    }
+}
+
+fn read_limited(path: &Path) -> Result<String, String> {
+    let size = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if size > MAX_MEMORY_FILE_SIZE {
+        return Err(format!("{} is too large ({} bytes)", path.display(), size));
+    }
+    std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+/// Resolves an import path relative to the file that contains it
+fn resolve_import(raw: &str, file: &Path, home: &Path) -> PathBuf {
+    if let Some(rest) = raw.strip_prefix("~/") {
+        return home.join(rest);
+    }
+    let path = Path::new(raw);
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    file.parent().unwrap_or(Path::new("")).join(path)
+}
+
+struct Expander<'a> {
+    home: &'a Path,
+    pattern: Regex,
+    imports: Vec<MemoryImport>,
+    diagnostics: Vec<String>,
+}
+
+impl Expander<'_> {
+    /// Returns `content` with every `@path` import replaced by the imported
+    /// file (recursively). Imports inside code spans and fenced blocks are
+    /// left alone, as claude does.
+    fn expand(&mut self, file: &Path, content: &str, stack: &mut Vec<PathBuf>) -> String {
+        let mut out = String::with_capacity(content.len());
+        let mut in_fence = false;
+        for line in content.split_inclusive('\n') {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+            }
+            if in_fence || !line.contains('@') {
+                out.push_str(line);
+                continue;
+            }
+            // Odd segments between backticks are inline code
+            for (i, segment) in line.split('`').enumerate() {
+                if i > 0 {
+                    out.push('`');
+                }
+                if i % 2 == 1 {
+                    out.push_str(segment);
+                } else {
+                    out.push_str(&self.expand_segment(file, segment, stack));
+                }
+            }
+        }
+        out
+    }
+
+    fn expand_segment(&mut self, file: &Path, segment: &str, stack: &mut Vec<PathBuf>) -> String {
+        let mut out = String::new();
+        let mut last = 0;
+        let matches: Vec<(usize, usize, String)> = self
+            .pattern
+            .captures_iter(segment)
+            .filter_map(|c| {
+                let m = c.get(1)?;
+                let mut raw = m.as_str();
+                // Allow sentence punctuation after an import, e.g. "see @README.md."
+                let trimmed = raw.trim_end_matches(['.', ',', ';', ':', ')', '!', '?']);
+                if trimmed != raw && !trimmed.is_empty() && !resolve_import(raw, file, self.home).is_file() {
+                    raw = trimmed;
+                }
+                Some((m.start() - 1, m.start() + raw.len(), raw.to_string()))
+            })
+            .collect();
+        for (start, end, raw) in matches {
+            out.push_str(&segment[last..start]);
+            last = end;
+            match self.import(file, &raw, stack) {
+                Some(expanded) => out.push_str(&expanded),
+                None => out.push_str(&segment[start..end]),
+            }
+        }
+        out.push_str(&segment[last..]);
+        out
+    }
+
+    fn import(&mut self, file: &Path, raw: &str, stack: &mut Vec<PathBuf>) -> Option<String> {
+        let path = resolve_import(raw, file, self.home);
+        let depth = stack.len();
+        let found = path.is_file();
+        self.imports.push(MemoryImport {
+            raw: raw.to_string(),
+            path: path.to_string_lossy().to_string(),
+            from: file.to_string_lossy().to_string(),
+            found,
+            depth,
+        });
+        if !found {
+            self.diagnostics.push(format!("{}: import @{} not found", file.display(), raw));
+            return None;
+        }
+        if depth > MAX_IMPORT_DEPTH {
+            self.diagnostics.push(format!(
+                "{}: import @{} is more than {} levels deep and is not loaded",
+                file.display(),
+                raw,
+                MAX_IMPORT_DEPTH
+            ));
+            return None;
+        }
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if stack.contains(&canonical) {
+            self.diagnostics.push(format!("{}: import @{} is circular", file.display(), raw));
+            return None;
+        }
+        let content = match read_limited(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                self.diagnostics.push(e);
+                return None;
+            }
+        };
+        stack.push(canonical);
+        let expanded = self.expand(&path, &content, stack);
+        stack.pop();
+        // The import is replaced in place, so the line's own newline follows
+        Some(expanded.trim_end_matches(['\n', '\r']).to_string())
+    }
+}
+
+/// Discovers the memory files for a directory and builds the merged context
+/// claude would load there
+pub fn preview(directory: &Path, home: &Path) -> Result<MemoryPreview, String> {
+    let directory = directory
+        .canonicalize()
+        .map_err(|e| format!("Invalid directory: {}", e))?;
+    let mut expander = Expander {
+        home,
+        // `@` must start the text or follow whitespace, so e-mail addresses
+        // aren't treated as imports
+        pattern: Regex::new(r"(?:^|\s)@([^\s`]+)").map_err(|e| e.to_string())?,
+        imports: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+
+    let mut files = Vec::new();
+    let mut merged = String::new();
+    for (path, scope) in discover_paths(&directory, home) {
+        let loaded_at_startup = scope != MemoryScope::Nested;
+        let content = match read_limited(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                expander.diagnostics.push(e);
+                continue;
+            }
+        };
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        let expanded = expander.expand(&path, &content, &mut vec![canonical]);
+        if loaded_at_startup {
+            if !merged.is_empty() {
+                merged.push('\n');
+            }
+            merged.push_str(&format!("<!-- {} ({:?}) -->\n", path.display(), scope));
+            merged.push_str(&expanded);
+            if !expanded.ends_with('\n') {
+                merged.push('\n');
+            }
+        }
+        files.push(MemoryFile {
+            path: path.to_string_lossy().to_string(),
+            scope,
+            loaded_at_startup,
+            size: content.len() as u64,
+            modified_at: modified_at(&path),
+            imports: std::mem::take(&mut expander.imports),
+        });
+    }
+
+    Ok(MemoryPreview {
+        directory: directory.to_string_lossy().to_string(),
+        files,
+        merged,
+        diagnostics: expander.diagnostics,
+    })
+}
+
+/// Resolves a path given to the memory commands, following symlinks. Only
+/// files named like memory files, or markdown files under `~/.claude` or a
+/// project's `.claude` directory, may be read or written.
+fn check_memory_path(path: &Path, home: &Path) -> Result<PathBuf, String> {
+    if !path.is_absolute() {
+        return Err("Memory file paths must be absolute".to_string());
+    }
+    let resolved = match path.canonicalize() {
+        Ok(resolved) => resolved,
+        // A file that doesn't exist yet: its directory must
+        Err(_) => {
+            let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+                return Err(format!("Invalid memory file path: {}", path.display()));
+            };
+            parent
+                .canonicalize()
+                .map_err(|e| format!("Failed to resolve {}: {}", parent.display(), e))?
+                .join(name)
+        }
+    };
+    if resolved.extension().and_then(|e| e.to_str()) != Some("md") {
+        return Err("Only markdown (.md) memory files can be edited".to_string());
+    }
+    let name = resolved.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let claude_dir = home.join(".claude");
+    let claude_dir = claude_dir.canonicalize().unwrap_or(claude_dir);
+    let in_claude_dir = resolved.starts_with(&claude_dir)
+        || resolved
+            .parent()
+            .is_some_and(|dir| dir.ancestors().any(|d| d.file_name().is_some_and(|n| n == ".claude")));
+    if !MEMORY_FILE_NAMES.contains(&name) && !in_claude_dir {
+        return Err(format!(
+            "{} is not a memory file; only {} or files in a .claude directory can be edited",
+            path.display(),
+            MEMORY_FILE_NAMES.join(" and ")
+        ));
+    }
+    Ok(resolved)
+}
+
+pub fn read(path: &Path, home: &Path) -> Result<MemoryFileContent, String> {
+    let path = &check_memory_path(path, home)?;
+    Ok(MemoryFileContent {
+        path: path.to_string_lossy().to_string(),
+        content: read_limited(path)?,
+        modified_at: modified_at(path),
+    })
+}
+
+/// Writes a memory file atomically. New files may only be created under a
+/// memory file name; with `expected_modified_at` the write is refused if the
+/// file changed since it was read.
+pub fn write(
+    path: &Path,
+    home: &Path,
+    content: &str,
+    expected_modified_at: Option<&str>,
+) -> Result<MemoryFileContent, String> {
+    let path = &check_memory_path(path, home)?;
+    if path.exists() {
+        if let Some(expected) = expected_modified_at {
+            if modified_at(path).as_deref() != Some(expected) {
+                return Err(format!(
+                    "{} was changed outside the editor; reload it before saving",
+                    path.display()
+                ));
+            }
+        }
+    } else {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if !MEMORY_FILE_NAMES.contains(&name) {
+            return Err(format!("New memory files must be named {}", MEMORY_FILE_NAMES.join(" or ")));
+        }
+    }
+    if content.len() as u64 > MAX_MEMORY_FILE_SIZE {
+        return Err("Memory file is too large".to_string());
+    }
+    crate::settings_file::write_atomic(path, content)?;
+    Ok(MemoryFileContent {
+        path: path.to_string_lossy().to_string(),
+        content: content.to_string(),
+        modified_at: modified_at(path),
+    })
+}
@@ -0,0 +1,213 @@
+use crate::terminal::TerminalManager;
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Permission modes claude accepts for `defaultMode`
+const DEFAULT_MODES: &[&str] = &["default", "acceptEdits", "plan", "bypassPermissions"];
+
+/// How a policy is handed to claude at launch
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyOutput {
+    /// Command-line flags when every rule passes the argument checks,
+    /// otherwise a generated settings file
+    #[default]
+    Auto,
+    /// `--allowed-tools` / `--disallowed-tools` flags only
+    Args,
+    /// A generated settings file passed with `--settings`
+    Settings,
+}
+
+/// A named set of permission rules that can be attached to profiles
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PermissionPolicy {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// Tools allowed without prompting, e.g. `Read` or `mcp__github`
+    pub allowed_tools: Vec<String>,
+    pub denied_tools: Vec<String>,
+    /// Bash command patterns, e.g. `npm test` or `git diff:*`
+    pub allowed_commands: Vec<String>,
+    pub denied_commands: Vec<String>,
+    /// Path patterns claude may read and edit, e.g. `src/**`
+    pub allowed_paths: Vec<String>,
+    /// Path patterns claude may neither read nor edit, e.g. `~/.ssh/**`
+    pub denied_paths: Vec<String>,
+    /// Directories outside the working directory claude may access
+    pub additional_directories: Vec<String>,
+    pub default_mode: Option<String>,
+    pub output: PolicyOutput,
+}
+
+/// Launch arguments generated from a policy
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MaterializedPolicy {
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+    pub args: Vec<String>,
+    /// Path of the generated settings file, when one was needed
+    pub settings_file: Option<String>,
+}
+
+impl PermissionPolicy {
+    /// Checks the policy's fields before it is saved
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() || self.name.len() > 255 {
+            return Err("Policy name must be 1-255 characters".to_string());
+        }
+        let lists = [
+            ("Allowed tools", &self.allowed_tools),
+            ("Denied tools", &self.denied_tools),
+            ("Allowed commands", &self.allowed_commands),
+            ("Denied commands", &self.denied_commands),
+            ("Allowed paths", &self.allowed_paths),
+            ("Denied paths", &self.denied_paths),
+            ("Additional directories", &self.additional_directories),
+        ];
+        for (label, entries) in lists {
+            for entry in entries {
+                if entry.trim().is_empty() {
+                    return Err(format!("{} can't contain empty entries", label));
+                }
+                if entry.contains(['\n', '\r', '\0']) {
+                    return Err(format!("{} entry \"{}\" contains a line break", label, entry));
+                }
+            }
+        }
+        for tool in self.allowed_tools.iter().chain(&self.denied_tools) {
+            if tool.contains(char::is_whitespace) && !tool.contains('(') {
+                return Err(format!("\"{}\" is not a tool name", tool));
+            }
+        }
+        for pattern in self
+            .allowed_commands
+            .iter()
+            .chain(&self.denied_commands)
+            .chain(&self.allowed_paths)
+            .chain(&self.denied_paths)
+        {
+            if pattern.contains([')', '(']) {
+                return Err(format!("\"{}\" can't contain parentheses", pattern));
+            }
+        }
+        if let Some(mode) = &self.default_mode {
+            if !DEFAULT_MODES.contains(&mode.as_str()) {
+                return Err(format!("Unknown permission mode \"{}\"", mode));
+            }
+        }
+        Ok(())
+    }
+
+    /// Expands the policy into claude permission rules (allow, deny)
+    pub fn rules(&self) -> (Vec<String>, Vec<String>) {
+        let mut allow = self.allowed_tools.clone();
+        allow.extend(self.allowed_commands.iter().map(|c| format!("Bash({})", c.trim())));
+        for path in &self.allowed_paths {
+            allow.push(format!("Read({})", path.trim()));
+            allow.push(format!("Edit({})", path.trim()));
+        }
+
+        let mut deny = self.denied_tools.clone();
+        deny.extend(self.denied_commands.iter().map(|c| format!("Bash({})", c.trim())));
+        for path in &self.denied_paths {
+            deny.push(format!("Read({})", path.trim()));
+            deny.push(format!("Edit({})", path.trim()));
+        }
+        (allow, deny)
+    }
+
+    /// The `permissions` settings object equivalent to this policy
+    fn settings(&self) -> serde_json::Value {
+        let (allow, deny) = self.rules();
+        let mut permissions = serde_json::json!({ "allow": allow, "deny": deny });
+        if !self.additional_directories.is_empty() {
+            permissions["additionalDirectories"] = serde_json::json!(self.additional_directories);
+        }
+        if let Some(mode) = &self.default_mode {
+            permissions["defaultMode"] = serde_json::json!(mode);
+        }
+        serde_json::json!({ "permissions": permissions })
+    }
+
+    /// Flag form of the policy. Fails with the first argument that wouldn't
+    /// pass the terminal's metacharacter check.
+    fn flag_args(&self) -> Result<Vec<String>, String> {
+        let (allow, deny) = self.rules();
+        let mut args = Vec::new();
+        if !allow.is_empty() {
+            args.push("--allowed-tools".to_string());
+            args.extend(allow);
+        }
+        if !deny.is_empty() {
+            args.push("--disallowed-tools".to_string());
+            args.extend(deny);
+        }
+        for dir in &self.additional_directories {
+            args.push("--add-dir".to_string());
+            args.push(dir.clone());
+        }
+        if let Some(mode) = &self.default_mode {
+            args.push("--permission-mode".to_string());
+            args.push(mode.clone());
+        }
+        for arg in &args {
+            TerminalManager::validate_arg(arg)?;
+        }
+        Ok(args)
+    }
+
+    /// Produces the launch arguments for this policy. When flags can't carry
+    /// the rules a settings file under the app data dir is used instead; it is
+    /// only written when `write_settings` is set, so previews have no effect.
+    pub fn materialize(&self, write_settings: bool) -> Result<MaterializedPolicy, String> {
+        let (allow, deny) = self.rules();
+        let flags = match self.output {
+            PolicyOutput::Settings => None,
+            PolicyOutput::Args => Some(self.flag_args()?),
+            PolicyOutput::Auto => self.flag_args().ok(),
+        };
+        if let Some(args) = flags {
+            return Ok(MaterializedPolicy { allow, deny, args, settings_file: None });
+        }
+
+        let file_name = if self.id.is_empty() { "unsaved" } else { self.id.as_str() };
+        let path = settings_dir()?.join(format!("{}.json", file_name));
+        let mut content = serde_json::to_string_pretty(&self.settings()).map_err(|e| e.to_string())?;
+        content.push('\n');
+        let diagnostics = crate::settings_file::validate_settings(&content)?;
+        if let Some(d) = diagnostics.iter().find(|d| d.is_error()) {
+            let path = d.path.as_deref().unwrap_or("(root)");
+            return Err(format!("Policy produced invalid settings: {}: {}", path, d.message));
+        }
+        if write_settings {
+            crate::settings_file::write_atomic(&path, &content)?;
+        }
+
+        // Forward slashes keep Windows paths clear of the backslash check
+        let path = path.to_string_lossy().replace('\\', "/");
+        let args = vec!["--settings".to_string(), path.clone()];
+        for arg in &args {
+            TerminalManager::validate_arg(arg)
+                .map_err(|e| format!("The policy settings path can't be passed to claude: {}", e))?;
+        }
+        Ok(MaterializedPolicy { allow, deny, args, settings_file: Some(path) })
+    }
+}
+
+fn settings_dir() -> Result<PathBuf, String> {
+    Ok(ProjectDirs::from("com", "claudeterminal", "ClaudeTerminal")
+        .ok_or("Failed to get project directories")?
+        .data_dir()
+        .join("policies"))
+}
+
+/// Removes a policy's generated settings file, if any
+pub fn remove_settings_file(id: &str) {
+    if let Ok(dir) = settings_dir() {
+        let _ = std::fs::remove_file(dir.join(format!("{}.json", id)));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::git;
+use crate::terminal::{TerminalConfig, TerminalKind, TerminalStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Name of the workspace file a repository can commit at its root
+pub const PROJECT_FILE_NAME: &str = ".claudeterminal.toml";
+
+/// Project files are small hand-written configs; anything larger is rejected
+const MAX_PROJECT_FILE_SIZE: u64 = 256 * 1024;
+
+/// Contents of a `.claudeterminal.toml` file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectFile {
+    pub name: Option<String>,
+    #[serde(default)]
+    pub terminals: Vec<ProjectTerminal>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectTerminal {
+    pub label: String,
+    pub nickname: Option<String>,
+    /// Relative to the directory containing the project file. Defaults to that directory.
+    pub working_directory: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub color_tag: Option<String>,
+    #[serde(default)]
+    pub kind: TerminalKind,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProjectWorkspace {
+    pub name: String,
+    pub root: String,
+    pub file_path: String,
+    /// The file as read, for the user to review before trusting it
+    pub content: String,
+    /// Whether the user trusted this exact content. Until they do, terminals
+    /// come without the file's args and env vars, which would otherwise reach
+    /// claude straight from whoever committed the file.
+    pub trusted: bool,
+    pub terminals: Vec<TerminalConfig>,
+}
+
+/// Looks for a project file in `start` and each of its parent directories up
+/// to the repository root, returning the first one found. Outside a
+/// repository only `start` itself is searched.
+pub fn discover(start: &Path) -> Option<PathBuf> {
+    let top = git::toplevel(start)
+        .map(PathBuf::from)
+        .and_then(|top| top.canonicalize().ok())
+        .unwrap_or_else(|| start.to_path_buf());
+    start
+        .ancestors()
+        .take_while(|dir| dir.starts_with(&top))
+        .map(|dir| dir.join(PROJECT_FILE_NAME))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Parses a project file and resolves its terminals against the directory it
+/// lives in. `is_trusted` is asked about the content read.
+pub fn load(path: &Path, is_trusted: impl FnOnce(&str) -> bool) -> Result<ProjectWorkspace, String> {
+    let metadata = std::fs::metadata(path)
+        .map_err(|e| format!("Failed to read {}: {}", PROJECT_FILE_NAME, e))?;
+    if metadata.len() > MAX_PROJECT_FILE_SIZE {
+        return Err(format!("{} is too large", PROJECT_FILE_NAME));
+    }
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", PROJECT_FILE_NAME, e))?;
+    let file: ProjectFile = toml::from_str(&content)
+        .map_err(|e| format!("Invalid {}: {}", PROJECT_FILE_NAME, e))?;
+
+    let root = path
+        .parent()
+        .ok_or("Project file has no parent directory")?
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve project root: {}", e))?;
+
+    let trusted = is_trusted(&content);
+    let mut terminals = Vec::with_capacity(file.terminals.len());
+    for mut terminal in file.terminals {
+        if !trusted {
+            terminal.args.clear();
+            terminal.env.clear();
+        }
+        terminals.push(resolve_terminal(terminal, &root)?);
+    }
+
+    let name = file.name.unwrap_or_else(|| {
+        root.file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| root.to_string_lossy().to_string())
+    });
+
+    Ok(ProjectWorkspace {
+        name,
+        root: root.to_string_lossy().to_string(),
+        file_path: path.to_string_lossy().to_string(),
+        content,
+        trusted,
+        terminals,
+    })
+}
+
+fn resolve_terminal(terminal: ProjectTerminal, root: &Path) -> Result<TerminalConfig, String> {
+    if terminal.label.trim().is_empty() || terminal.label.len() > 255 {
+        return Err("Terminal label must be 1-255 characters".to_string());
+    }
+
+    let working_directory = match terminal.working_directory.as_deref() {
+        None | Some("") | Some(".") => root.to_path_buf(),
+        Some(dir) => {
+            if Path::new(dir).is_absolute() {
+                return Err(format!(
+                    "Working directory \"{}\" must be relative to the project root",
+                    dir
+                ));
+            }
+            let resolved = root
+                .join(dir)
+                .canonicalize()
+                .map_err(|e| format!("Invalid working directory \"{}\": {}", dir, e))?;
+            // Keep shared project files from pointing terminals outside the repository
+            if !resolved.starts_with(root) {
+                return Err(format!(
+                    "Working directory \"{}\" is outside the project root",
+                    dir
+                ));
+            }
+            resolved
+        }
+    };
+
+    Ok(TerminalConfig {
+        id: uuid::Uuid::new_v4().to_string(),
+        label: terminal.label,
+        nickname: terminal.nickname,
+        profile_id: None,
+        working_directory: working_directory.to_string_lossy().to_string(),
+        claude_args: terminal.args,
+        env_vars: terminal.env,
+        created_at: chrono::Utc::now(),
+        status: TerminalStatus::Idle,
+        color_tag: terminal.color_tag,
+        kind: terminal.kind,
+        permission_policy_id: None,
+        worktree: None,
+        git: None,
+    })
+}
@@ -0,0 +1,296 @@
+use crate::changes;
+use crate::forge::{ForgeConfig, NewPullRequest, PullRequest};
+use crate::git;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const DEFAULT_REMOTE: &str = "origin";
+
+/// Generated titles are cut to fit a commit subject line
+const MAX_TITLE_CHARS: usize = 72;
+
+/// Cap on the reply quoted in a generated body
+const MAX_SUMMARY_CHARS: usize = 4000;
+
+/// Commits listed in a generated body
+const MAX_LISTED_COMMITS: usize = 50;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PullRequestOptions {
+    /// Remote to push to, `origin` when unset
+    pub remote: Option<String>,
+    /// Forge hosting the remote, detected from its URL when unset
+    pub forge: Option<ForgeConfig>,
+    /// Branch to merge into, the remote's default branch when unset
+    pub base: Option<String>,
+    /// Name for the branch created when on the base branch
+    pub branch: Option<String>,
+    pub title: Option<String>,
+    pub body: Option<String>,
+    /// Message for pending changes, the title when unset
+    pub commit_message: Option<String>,
+    pub draft: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequestDescription {
+    pub title: String,
+    pub body: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PullRequestResult {
+    pub branch: String,
+    /// The branch was created because the terminal was on the base branch
+    pub created_branch: bool,
+    /// Commit made from pending changes, if there were any
+    pub commit: Option<String>,
+    pub remote: String,
+    pub pull_request: PullRequest,
+}
+
+/// Commits a terminal's pending changes, pushes its branch and opens a pull
+/// request into the remote's default branch. `label` and `transcript` come
+/// from the terminal's session and fill in the title and body when not given.
+pub fn create(
+    dir: &Path,
+    label: &str,
+    transcript: &str,
+    options: &PullRequestOptions,
+) -> Result<PullRequestResult, String> {
+    let root = git::toplevel(dir).ok_or_else(|| format!("{} is not inside a git repository", dir.display()))?;
+    let repo = Path::new(&root);
+    let remote = non_empty(&options.remote).unwrap_or(DEFAULT_REMOTE).to_string();
+    let remote_url = git::run_trimmed(repo, &["remote", "get-url", &remote])
+        .map_err(|_| format!("No remote named {}", remote))?;
+    // Settle the forge before anything is committed or pushed
+    let forge_config = match &options.forge {
+        Some(config) => config.clone(),
+        None => ForgeConfig::detect(&remote_url)
+            .ok_or_else(|| format!("Can't tell which forge hosts {}; choose one", remote_url))?,
+    };
+    let forge = forge_config.build(&remote_url)?;
+
+    let base = match non_empty(&options.base) {
+        Some(base) => base.to_string(),
+        None => default_branch(repo, &remote)?,
+    };
+    let remote_base = format!("refs/remotes/{}/{}", remote, base);
+    let has_remote_base = git::run(repo, &["rev-parse", "--verify", "--quiet", &remote_base]).is_ok();
+    let dirty = git::is_dirty(repo, true)?;
+    if !dirty && has_remote_base {
+        let ahead = git::run_trimmed(repo, &["rev-list", "--count", &format!("{}..HEAD", remote_base)])?;
+        if ahead == "0" {
+            return Err(format!("There are no changes to open a pull request for against {}", base));
+        }
+    }
+
+    let generated = describe(transcript, label);
+    let title = non_empty(&options.title).map(str::to_string).unwrap_or(generated.title);
+
+    let (branch, created_branch) = match git::current_branch(repo) {
+        Some(current) if current != base => (current, false),
+        _ => {
+            let branch = match non_empty(&options.branch) {
+                Some(branch) => branch.to_string(),
+                None => new_branch_name(&title),
+            };
+            if branch.starts_with('-') {
+                return Err(format!("Invalid branch name: {}", branch));
+            }
+            git::run(repo, &["check-ref-format", "--branch", &branch])
+                .map_err(|_| format!("Invalid branch name: {}", branch))?;
+            git::run(repo, &["switch", "-c", &branch])?;
+            (branch, true)
+        }
+    };
+
+    let commit = if dirty {
+        git::run(repo, &["add", "-A"])?;
+        let message = non_empty(&options.commit_message).unwrap_or(&title);
+        Some(changes::commit(repo, message)?)
+    } else {
+        None
+    };
+
+    // Never wait on a credential prompt nobody can see
+    git::run_with_env(
+        repo,
+        &["push", "--set-upstream", &remote, &branch],
+        &[("GIT_TERMINAL_PROMPT", "0")],
+    )
+    .map_err(|e| format!("Failed to push {} to {}: {}", branch, remote, e))?;
+
+    let body = match non_empty(&options.body) {
+        Some(body) => body.to_string(),
+        None => with_commits(repo, &generated.body, has_remote_base.then_some(remote_base.as_str())),
+    };
+    let pull_request = forge.open_pull_request(&NewPullRequest {
+        title,
+        body,
+        head: branch.clone(),
+        base,
+        draft: options.draft,
+    })?;
+
+    Ok(PullRequestResult {
+        branch,
+        created_branch,
+        commit,
+        remote,
+        pull_request,
+    })
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// The branch a remote's HEAD points at, from the remote-tracking ref or by
+/// asking the remote
+fn default_branch(repo: &Path, remote: &str) -> Result<String, String> {
+    let head = format!("refs/remotes/{}/HEAD", remote);
+    if let Ok(target) = git::run_trimmed(repo, &["symbolic-ref", "--quiet", &head]) {
+        if let Some(branch) = target.strip_prefix(&format!("refs/remotes/{}/", remote)) {
+            return Ok(branch.to_string());
+        }
+    }
+    let output = git::run_with_env(
+        repo,
+        &["ls-remote", "--symref", remote, "HEAD"],
+        &[("GIT_TERMINAL_PROMPT", "0")],
+    )?;
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix("ref: refs/heads/")?.split_once('\t').map(|(b, _)| b.to_string()))
+        .ok_or_else(|| format!("Can't tell the default branch of {}; choose a base branch", remote))
+}
+
+fn new_branch_name(title: &str) -> String {
+    let stamp = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+    match crate::worktree::slug(title) {
+        s if s.is_empty() => format!("claude/{}", stamp),
+        s => format!("claude/{}-{}", s, stamp),
+    }
+}
+
+/// Title and body for a session's pull request: the title from the first
+/// request typed into claude, the body from its last reply
+pub fn describe(transcript: &str, label: &str) -> PullRequestDescription {
+    let title = first_prompt(transcript)
+        .map(|prompt| shorten(&prompt, MAX_TITLE_CHARS))
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| format!("Changes from {}", label));
+    let mut title = title;
+    if let Some(first) = title.chars().next() {
+        title.replace_range(..first.len_utf8(), &first.to_uppercase().to_string());
+    }
+
+    let body = match last_reply(transcript) {
+        Some(reply) => format!("## Summary\n\n{}\n", shorten(&reply, MAX_SUMMARY_CHARS)),
+        None => format!("## Summary\n\nChanges made in the {} session.\n", label),
+    };
+    PullRequestDescription { title, body }
+}
+
+/// Appends the commits the pull request brings in
+fn with_commits(repo: &Path, body: &str, remote_base: Option<&str>) -> String {
+    let max = format!("--max-count={}", MAX_LISTED_COMMITS);
+    let range = remote_base.map(|base| format!("{}..HEAD", base));
+    let mut args = vec!["log", "--format=%s", &max];
+    // Without the base branch locally, only the new commit is certain
+    match &range {
+        Some(range) => args.push(range),
+        None => args.push("-1"),
+    }
+    let subjects = git::run(repo, &args).unwrap_or_default();
+    let commits: Vec<&str> = subjects.lines().filter(|s| !s.trim().is_empty()).collect();
+    if commits.is_empty() {
+        return body.to_string();
+    }
+    let mut body = format!("{}\n## Commits\n\n", body);
+    for subject in commits.iter().rev() {
+        body.push_str(&format!("- {}\n", subject));
+    }
+    body
+}
+
+/// Cuts `text` to at most `max` characters at a word boundary
+fn shorten(text: &str, max: usize) -> String {
+    let text = text.trim();
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max.saturating_sub(1)).collect();
+    let cut = match cut.rfind(char::is_whitespace) {
+        Some(i) if i > max / 2 => &cut[..i],
+        _ => &cut,
+    };
+    format!("{}…", cut.trim_end())
+}
+
+/// Text of a line in claude's prompt box, e.g. `│ > fix the build │`
+fn prompt_text(line: &str) -> Option<&str> {
+    let line = line.trim_matches(|c: char| c == '│' || c.is_whitespace());
+    let text = line.strip_prefix('>')?.trim();
+    // The placeholder shown in an empty prompt box
+    if text.is_empty() || text.starts_with("Try \"") {
+        return None;
+    }
+    Some(text)
+}
+
+/// The first request submitted to claude. The log holds every redraw of the
+/// prompt box while it was typed, so this is the first text that wasn't
+/// later extended.
+fn first_prompt(transcript: &str) -> Option<String> {
+    let mut prompts: Vec<&str> = transcript.lines().filter_map(prompt_text).collect();
+    prompts.dedup();
+    prompts
+        .iter()
+        .enumerate()
+        .find(|(i, prompt)| prompts.get(i + 1).is_none_or(|next| !next.starts_with(**prompt)))
+        .map(|(_, prompt)| prompt.to_string())
+}
+
+/// Whether a `⏺` line is a tool call such as `Bash(cargo test)` rather than
+/// a reply
+fn is_tool_call(text: &str) -> bool {
+    text.split_once('(')
+        .is_some_and(|(name, _)| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// The last reply claude printed: a `⏺` line that isn't a tool call and the
+/// indented lines under it
+fn last_reply(transcript: &str) -> Option<String> {
+    let mut last: Option<Vec<&str>> = None;
+    let mut current: Option<Vec<&str>> = None;
+    for line in transcript.lines() {
+        let line = line.trim_end();
+        let trimmed = line.trim_start();
+        if let Some(text) = trimmed.strip_prefix("⏺") {
+            let text = text.trim();
+            if let Some(block) = current.take() {
+                last = Some(block);
+            }
+            if !is_tool_call(text) && !text.is_empty() {
+                current = Some(vec![text]);
+            }
+            continue;
+        }
+        let Some(block) = current.as_mut() else {
+            continue;
+        };
+        let continues = line.is_empty()
+            || (line.starts_with("  ") && !trimmed.starts_with(['⎿', '│', '╭', '╰', '>', '✻', '·']));
+        if continues {
+            block.push(trimmed);
+        } else {
+            last = current.take();
+        }
+    }
+    let block = current.or(last)?;
+    let reply = block.join("\n").trim().to_string();
+    (!reply.is_empty()).then_some(reply)
+}
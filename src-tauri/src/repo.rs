@@ -0,0 +1,171 @@
+use git2::{BranchType, ErrorCode, Repository, RepositoryState};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Where a repository stands: what is checked out, how it compares with its
+/// upstream, and what is stashed
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RepoInfo {
+    pub root: String,
+    /// Checked-out branch, also when it has no commits yet; None when HEAD
+    /// is detached
+    pub branch: Option<String>,
+    pub detached: bool,
+    /// Merge, rebase or similar waiting to be finished or aborted
+    pub operation: Option<RepoOperation>,
+    /// Commit HEAD points at; None on an unborn branch
+    pub head: Option<String>,
+    /// Upstream of the branch, e.g. `origin/main`
+    pub upstream: Option<String>,
+    /// Commits on the branch that aren't on its upstream
+    pub ahead: u32,
+    /// Commits on the upstream that aren't on the branch
+    pub behind: u32,
+    pub stashes: Vec<StashEntry>,
+    pub last_commit: Option<CommitSummary>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RepoOperation {
+    Merge,
+    Rebase,
+    CherryPick,
+    Revert,
+    Bisect,
+    /// `git am` applying patches from a mailbox
+    ApplyMailbox,
+}
+
+impl RepoOperation {
+    fn from_state(state: RepositoryState) -> Option<Self> {
+        match state {
+            RepositoryState::Clean => None,
+            RepositoryState::Merge => Some(RepoOperation::Merge),
+            RepositoryState::Revert | RepositoryState::RevertSequence => Some(RepoOperation::Revert),
+            RepositoryState::CherryPick | RepositoryState::CherryPickSequence => Some(RepoOperation::CherryPick),
+            RepositoryState::Bisect => Some(RepoOperation::Bisect),
+            RepositoryState::Rebase | RepositoryState::RebaseInteractive | RepositoryState::RebaseMerge => {
+                Some(RepoOperation::Rebase)
+            }
+            RepositoryState::ApplyMailbox => Some(RepoOperation::ApplyMailbox),
+            RepositoryState::ApplyMailboxOrRebase => Some(RepoOperation::Rebase),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StashEntry {
+    /// Position in `git stash list`, 0 being the newest
+    pub index: usize,
+    pub message: String,
+    pub commit: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CommitSummary {
+    pub id: String,
+    pub summary: String,
+    pub author: String,
+    pub time: String,
+}
+
+/// Opens the repository containing `dir`. Ok(None) when there is none; an
+/// error when libgit2 can't read it, e.g. because of a repository format
+/// extension it doesn't support, and git itself should be asked instead.
+pub fn open(dir: &Path) -> Result<Option<Repository>, String> {
+    match Repository::discover(dir) {
+        Ok(repo) => Ok(Some(repo)),
+        Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(e.message().to_string()),
+    }
+}
+
+/// Whether the repository configures content filters such as Git LFS.
+/// libgit2 can't run them, so it would see every filtered file as modified.
+pub fn has_external_filters(repo: &Repository) -> bool {
+    let Ok(config) = repo.config() else {
+        return false;
+    };
+    let Ok(entries) = config.entries(Some(r"^filter\..*\.(clean|process)$")) else {
+        return false;
+    };
+    let mut found = false;
+    let _ = entries.for_each(|_| found = true);
+    found
+}
+
+/// Reads branch, upstream, stash and last commit details. Parts that can't
+/// be read are left empty rather than failing the whole lookup.
+pub fn info(repo: &mut Repository) -> RepoInfo {
+    let mut info = RepoInfo {
+        root: repo
+            .workdir()
+            .unwrap_or_else(|| repo.path())
+            .to_string_lossy()
+            .trim_end_matches('/')
+            .to_string(),
+        detached: repo.head_detached().unwrap_or(false),
+        operation: RepoOperation::from_state(repo.state()),
+        ..Default::default()
+    };
+
+    match repo.head() {
+        Ok(head) => {
+            if head.is_branch() {
+                info.branch = head.shorthand().map(str::to_string);
+            }
+            if let Ok(commit) = head.peel_to_commit() {
+                info.head = Some(commit.id().to_string());
+                info.last_commit = Some(CommitSummary {
+                    id: commit.id().to_string(),
+                    summary: commit.summary().unwrap_or_default().to_string(),
+                    author: commit.author().name().unwrap_or_default().to_string(),
+                    time: commit_time(&commit.time()),
+                });
+            }
+        }
+        // HEAD names a branch that has no commits yet
+        Err(_) => {
+            info.branch = repo
+                .find_reference("HEAD")
+                .ok()
+                .and_then(|head| head.symbolic_target().map(str::to_string))
+                .and_then(|target| target.strip_prefix("refs/heads/").map(str::to_string));
+        }
+    }
+
+    if let (Some(name), Some(head)) = (&info.branch, &info.head) {
+        let upstream = repo
+            .find_branch(name, BranchType::Local)
+            .and_then(|branch| branch.upstream());
+        if let Ok(upstream) = upstream {
+            info.upstream = upstream.name().ok().flatten().map(str::to_string);
+            if let (Ok(local), Some(remote)) = (git2::Oid::from_str(head), upstream.get().target()) {
+                if let Ok((ahead, behind)) = repo.graph_ahead_behind(local, remote) {
+                    info.ahead = ahead as u32;
+                    info.behind = behind as u32;
+                }
+            }
+        }
+    }
+
+    let _ = repo.stash_foreach(|index, message, id| {
+        info.stashes.push(StashEntry {
+            index,
+            message: message.to_string(),
+            commit: id.to_string(),
+        });
+        true
+    });
+    info
+}
+
+/// A commit's time as RFC 3339 in the author's timezone
+fn commit_time(time: &git2::Time) -> String {
+    let offset = chrono::FixedOffset::east_opt(time.offset_minutes() * 60)
+        .unwrap_or_else(|| chrono::FixedOffset::east_opt(0).unwrap());
+    chrono::DateTime::from_timestamp(time.seconds(), 0)
+        .map(|t| t.with_timezone(&offset).to_rfc3339())
+        .unwrap_or_default()
+}
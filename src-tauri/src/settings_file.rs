@@ -0,0 +1,406 @@
+use crate::diagnostics::{Diagnostic, Severity};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Schema for the settings.json keys this app understands. Unknown keys are
+/// allowed so newer claude settings are never rejected.
+const SETTINGS_SCHEMA: &str = include_str!("../schemas/claude-settings.schema.json");
+
+/// Number of settings.json backups kept; older ones are pruned on each write
+const MAX_SETTINGS_BACKUPS: usize = 20;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SettingsBackup {
+    pub file_name: String,
+    pub size: u64,
+    pub modified_at: Option<String>,
+}
+
+/// Checks a settings document against the bundled schema and the hook rules.
+/// Returns an error only when the content is not JSON at all. Warnings are
+/// values claude may still accept, like an enum value newer than the schema.
+pub fn validate_settings(content: &str) -> Result<Vec<Diagnostic>, String> {
+    let value: Value = serde_json::from_str(content).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let schema: Value = serde_json::from_str(SETTINGS_SCHEMA).map_err(|e| e.to_string())?;
+    let mut diagnostics = Vec::new();
+    check_value(&value, &schema, "", &mut diagnostics);
+
+    // The schema only covers the shape of hooks; matcher syntax and the like
+    // are checked by the hooks module
+    let in_hooks = |d: &Diagnostic| d.path.as_deref().is_some_and(|p| p == "hooks" || p.starts_with("hooks."));
+    if !diagnostics.iter().any(in_hooks) {
+        if let Ok(events) = crate::hooks::parse_hooks(value.get("hooks")) {
+            for d in crate::hooks::validate_hooks(&events) {
+                let path = format!("hooks.{}", d.path.as_deref().unwrap_or_default());
+                diagnostics.push(d.at(path));
+            }
+        }
+    }
+    Ok(diagnostics)
+}
+
+fn check_value(value: &Value, schema: &Value, path: &str, out: &mut Vec<Diagnostic>) {
+    let mut report = |severity: Severity, message: String| {
+        out.push(Diagnostic {
+            severity,
+            message,
+            path: Some(if path.is_empty() { "(root)".to_string() } else { path.to_string() }),
+        })
+    };
+
+    if let Some(expected) = schema.get("type").and_then(Value::as_str) {
+        if !matches_type(value, expected) {
+            report(Severity::Error, format!("Expected {}, found {}", expected, type_name(value)));
+            return;
+        }
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            let allowed: Vec<String> = allowed.iter().map(|v| v.to_string()).collect();
+            report(Severity::Warning, format!("Must be one of {}", allowed.join(", ")));
+        }
+    }
+    if let (Some(minimum), Some(n)) = (schema.get("minimum").and_then(Value::as_f64), value.as_f64()) {
+        if n < minimum {
+            report(Severity::Error, format!("Must be at least {}", minimum));
+        }
+    }
+
+    match value {
+        Value::Object(map) => {
+            for key in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+                if let Some(key) = key.as_str() {
+                    if !map.contains_key(key) {
+                        report(Severity::Error, format!("Missing required key \"{}\"", key));
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (key, child) in map {
+                let child_path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                match properties.and_then(|p| p.get(key)) {
+                    Some(child_schema) => check_value(child, child_schema, &child_path, out),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => out.push(Diagnostic::error("Unknown key").at(child_path)),
+                        Some(child_schema @ Value::Object(_)) => {
+                            check_value(child, child_schema, &child_path, out)
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check_value(item, item_schema, &format!("{}[{}]", path, i), out);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn matches_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Writes a file by writing a sibling temp file and renaming it over the
+/// target, so readers (including a running claude) never see a partial file.
+/// Symlinked targets are followed so dotfile-managed settings stay linked.
+pub fn write_atomic(path: &Path, content: &str) -> Result<(), String> {
+    let target = match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_symlink() => {
+            std::fs::canonicalize(path).map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))?
+        }
+        _ => path.to_path_buf(),
+    };
+    let parent = target.parent().ok_or("Invalid settings path")?;
+    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+
+    let file_name = target.file_name().and_then(|n| n.to_str()).unwrap_or("settings");
+    let tmp_path = parent.join(format!(".{}.{}.tmp", file_name, uuid::Uuid::new_v4()));
+    let result = (|| {
+        let mut file = std::fs::File::create(&tmp_path).map_err(|e| e.to_string())?;
+        file.write_all(content.as_bytes()).map_err(|e| e.to_string())?;
+        file.sync_all().map_err(|e| e.to_string())?;
+        if let Ok(meta) = std::fs::metadata(&target) {
+            let _ = std::fs::set_permissions(&tmp_path, meta.permissions());
+        }
+        std::fs::rename(&tmp_path, &target).map_err(|e| e.to_string())
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result.map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Backs up the current settings file and writes the new content atomically.
+/// Refuses content that isn't JSON; schema findings are left to
+/// `validate_settings`, as the bundled schema can lag behind claude.
+pub fn save_settings(path: &Path, content: &str) -> Result<(), String> {
+    serde_json::from_str::<Value>(content).map_err(|e| format!("settings.json is not valid JSON: {}", e))?;
+    write_with_backup(path, content)
+}
+
+/// Backs up the current settings file (if any) and writes `content` atomically
+pub fn write_with_backup(path: &Path, content: &str) -> Result<(), String> {
+    backup_settings(path)?;
+    write_atomic(path, content)
+}
+
+fn backups_dir() -> Result<PathBuf, String> {
+    Ok(ProjectDirs::from("com", "claudeterminal", "ClaudeTerminal")
+        .ok_or("Failed to get project directories")?
+        .data_dir()
+        .join("settings-backups"))
+}
+
+/// Copies the current settings file into the backups directory and prunes
+/// the oldest backups. Does nothing when the file doesn't exist yet.
+fn backup_settings(path: &Path) -> Result<(), String> {
+    if !path.is_file() {
+        return Ok(());
+    }
+    let dir = backups_dir()?;
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S_%3f");
+    std::fs::copy(path, dir.join(format!("settings-{}.json", timestamp)))
+        .map_err(|e| format!("Failed to back up settings.json: {}", e))?;
+
+    for old in list_backups().iter().skip(MAX_SETTINGS_BACKUPS) {
+        let _ = std::fs::remove_file(dir.join(&old.file_name));
+    }
+    Ok(())
+}
+
+/// Lists settings.json backups, newest first
+pub fn list_backups() -> Vec<SettingsBackup> {
+    let Ok(entries) = backups_dir().and_then(|dir| std::fs::read_dir(dir).map_err(|e| e.to_string())) else {
+        return vec![];
+    };
+    let mut backups: Vec<SettingsBackup> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_str()?.to_string();
+            if !file_name.starts_with("settings-") || !file_name.ends_with(".json") {
+                return None;
+            }
+            let metadata = entry.metadata().ok()?;
+            let modified_at = metadata
+                .modified()
+                .ok()
+                .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339());
+            Some(SettingsBackup { file_name, size: metadata.len(), modified_at })
+        })
+        .collect();
+    backups.sort_by(|a, b| b.file_name.cmp(&a.file_name));
+    backups
+}
+
+/// Restores a backup over the settings file. The file being replaced is backed
+/// up first, so a restore can itself be undone. Returns the restored content.
+pub fn restore_backup(path: &Path, file_name: &str) -> Result<String, String> {
+    if file_name.contains(['/', '\\', '\0']) || file_name.contains("..")
+        || !file_name.starts_with("settings-") || !file_name.ends_with(".json")
+    {
+        return Err("Invalid backup file name".to_string());
+    }
+    let backup_path = backups_dir()?.join(file_name);
+    let content = std::fs::read_to_string(&backup_path)
+        .map_err(|_| format!("Backup not found: {}", file_name))?;
+    serde_json::from_str::<Value>(&content)
+        .map_err(|e| format!("Backup {} is not valid JSON: {}", file_name, e))?;
+    write_with_backup(path, &content)?;
+    Ok(content)
+}
+
+/// Byte ranges of one top-level `"key": value` pair in a JSON object
+struct TopLevelEntry {
+    key: String,
+    value_start: usize,
+    value_end: usize,
+}
+
+/// Replaces (or inserts) a single top-level key of a JSON object document,
+/// leaving every other byte of the document as it was. This keeps the user's
+/// formatting and key order in files like `settings.json` intact.
+pub fn replace_top_level_key(content: &str, key: &str, value: &Value) -> Result<String, String> {
+    // Only operate on well-formed documents; the scanner below relies on it
+    match serde_json::from_str::<Value>(content) {
+        Ok(Value::Object(_)) => {}
+        Ok(_) => return Err("Settings file does not contain a JSON object".to_string()),
+        Err(e) => return Err(format!("Invalid JSON: {}", e)),
+    }
+
+    let (open, close, entries) = scan_top_level(content)?;
+    let indent = detect_indent(content, &entries);
+    let rendered = render_value(value, &indent)?;
+
+    if let Some(entry) = entries.iter().find(|e| e.key == key) {
+        let mut out = String::with_capacity(content.len() + rendered.len());
+        out.push_str(&content[..entry.value_start]);
+        out.push_str(&rendered);
+        out.push_str(&content[entry.value_end..]);
+        return Ok(out);
+    }
+
+    let key_json = serde_json::to_string(key).map_err(|e| e.to_string())?;
+    let mut out = String::with_capacity(content.len() + rendered.len() + key_json.len() + 8);
+    match entries.last() {
+        Some(last) => {
+            out.push_str(&content[..last.value_end]);
+            out.push_str(&format!(",\n{}{}: {}", indent, key_json, rendered));
+            out.push_str(&content[last.value_end..]);
+        }
+        None => {
+            out.push_str(&content[..open]);
+            out.push_str(&format!("{{\n{}{}: {}\n}}", indent, key_json, rendered));
+            out.push_str(&content[close + 1..]);
+        }
+    }
+    Ok(out)
+}
+
+fn render_value(value: &Value, indent: &str) -> Result<String, String> {
+    use serde::Serialize;
+    let mut buf = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut buf, formatter);
+    value.serialize(&mut serializer).map_err(|e| e.to_string())?;
+    let rendered = String::from_utf8(buf).map_err(|e| e.to_string())?;
+    // Nested lines sit one level deeper than the top-level key
+    Ok(rendered.replace('\n', &format!("\n{}", indent)))
+}
+
+/// Uses the indentation of the first top-level key, defaulting to two spaces
+fn detect_indent(content: &str, entries: &[TopLevelEntry]) -> String {
+    entries
+        .first()
+        .and_then(|entry| {
+            let line_start = content[..entry.value_start].rfind('\n')? + 1;
+            let indent: String = content[line_start..]
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect();
+            (!indent.is_empty()).then_some(indent)
+        })
+        .unwrap_or_else(|| "  ".to_string())
+}
+
+/// Finds the top-level object braces and its key/value spans. Assumes the
+/// document has already been validated as JSON.
+fn scan_top_level(content: &str) -> Result<(usize, usize, Vec<TopLevelEntry>), String> {
+    let bytes = content.as_bytes();
+    let mut pos = skip_whitespace(bytes, 0);
+    if bytes.get(pos) != Some(&b'{') {
+        return Err("Settings file does not contain a JSON object".to_string());
+    }
+    let open = pos;
+    pos += 1;
+
+    let mut entries = Vec::new();
+    loop {
+        pos = skip_whitespace(bytes, pos);
+        match bytes.get(pos) {
+            Some(b'}') => return Ok((open, pos, entries)),
+            Some(b',') => {
+                pos += 1;
+                continue;
+            }
+            Some(b'"') => {}
+            _ => return Err("Unexpected content in settings file".to_string()),
+        }
+
+        let key_end = skip_string(bytes, pos)?;
+        let key: String = serde_json::from_str(&content[pos..key_end]).map_err(|e| e.to_string())?;
+        pos = skip_whitespace(bytes, key_end);
+        if bytes.get(pos) != Some(&b':') {
+            return Err("Expected ':' in settings file".to_string());
+        }
+        let value_start = skip_whitespace(bytes, pos + 1);
+        let value_end = skip_value(bytes, value_start)?;
+        entries.push(TopLevelEntry { key, value_start, value_end });
+        pos = value_end;
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], mut pos: usize) -> usize {
+    while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+        pos += 1;
+    }
+    pos
+}
+
+/// Returns the index just past the closing quote of the string starting at `pos`
+fn skip_string(bytes: &[u8], pos: usize) -> Result<usize, String> {
+    let mut i = pos + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Ok(i + 1),
+            _ => i += 1,
+        }
+    }
+    Err("Unterminated string in settings file".to_string())
+}
+
+/// Returns the index just past the value starting at `pos`
+fn skip_value(bytes: &[u8], pos: usize) -> Result<usize, String> {
+    match bytes.get(pos) {
+        Some(b'"') => skip_string(bytes, pos),
+        Some(b'{') | Some(b'[') => {
+            let mut depth = 0usize;
+            let mut i = pos;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'"' => {
+                        i = skip_string(bytes, i)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Ok(i + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            Err("Unbalanced brackets in settings file".to_string())
+        }
+        Some(_) => {
+            let mut i = pos;
+            while i < bytes.len() && !matches!(bytes[i], b',' | b'}' | b']') && !bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            Ok(i)
+        }
+        None => Err("Unexpected end of settings file".to_string()),
+    }
+}
@@ -0,0 +1,532 @@
+use crate::watch;
+use notify::event::{ModifyKind, RenameMode};
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// Quiet period after a filesystem event before rescanning, since claude
+/// rewrites several task files in quick succession
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Longest a refresh waits while events keep coming
+const WATCH_MAX_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamMember {
+    pub agent_id: String,
+    pub name: String,
+    pub agent_type: String,
+    pub model: Option<String>,
+    pub joined_at: Option<u64>,
+    pub cwd: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamConfig {
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: Option<u64>,
+    pub lead_agent_id: Option<String>,
+    pub members: Vec<TeamMember>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamInfo {
+    pub dir_name: String,
+    pub config: TeamConfig,
+    pub task_count: Option<u32>,
+    /// Terminals the team's members are running in, where one could be found
+    #[serde(default)]
+    pub member_terminals: Vec<MemberTerminal>,
+}
+
+/// How a team member was matched to a terminal
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchReason {
+    /// A claude process below the terminal's shell carries the member's agent id
+    Process,
+    /// The terminal runs in the member's working directory and was started
+    /// before the member joined
+    Cwd,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberTerminal {
+    pub agent_id: String,
+    pub terminal_id: String,
+    pub matched_by: MatchReason,
+}
+
+/// What the member mapping needs to know about a live terminal
+pub struct TerminalProcess {
+    pub terminal_id: String,
+    pub working_directory: String,
+    pub created_at_ms: i64,
+    pub pid: Option<u32>,
+}
+
+/// One task file from `~/.claude/tasks/<team>/`. Unknown keys are kept in
+/// `extra` so newer claude versions don't break parsing.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamTask {
+    pub id: String,
+    #[serde(default)]
+    pub subject: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub active_form: Option<String>,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// Ids of tasks that can't start until this one completes
+    #[serde(default)]
+    pub blocks: Vec<String>,
+    /// Ids of tasks this one waits on
+    #[serde(default)]
+    pub blocked_by: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamTaskList {
+    pub team: String,
+    pub tasks: Vec<TeamTask>,
+    pub high_watermark: Option<u32>,
+}
+
+/// Reads every team under `~/.claude/teams`, skipping unreadable configs
+pub fn read_teams(claude_dir: &Path) -> Result<Vec<TeamInfo>, String> {
+    let teams_dir = claude_dir.join("teams");
+    if !teams_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let entries = std::fs::read_dir(&teams_dir).map_err(|e| e.to_string())?;
+    let mut teams = Vec::new();
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        let config_str = match std::fs::read_to_string(path.join("config.json")) {
+            Ok(s) => s,
+            Err(_) => continue,
+        };
+
+        let config: TeamConfig = match serde_json::from_str(&config_str) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+
+        let dir_name = entry.file_name().to_string_lossy().to_string();
+        let task_count = read_high_watermark(claude_dir, &dir_name);
+
+        teams.push(TeamInfo {
+            dir_name,
+            config,
+            task_count,
+            member_terminals: Vec::new(),
+        });
+    }
+
+    teams.sort_by(|a, b| a.dir_name.cmp(&b.dir_name));
+    Ok(teams)
+}
+
+fn read_high_watermark(claude_dir: &Path, team: &str) -> Option<u32> {
+    std::fs::read_to_string(claude_dir.join("tasks").join(team).join(".highwatermark"))
+        .ok()
+        .and_then(|s| s.trim().parse::<u32>().ok())
+}
+
+/// Reads the task files of a team, ordered by numeric id where possible
+pub fn read_tasks(claude_dir: &Path, team: &str) -> Result<TeamTaskList, String> {
+    if team.is_empty() || team.contains(['/', '\\', '\0']) || team.contains("..") {
+        return Err("Invalid team name".to_string());
+    }
+    let tasks_dir = claude_dir.join("tasks").join(team);
+    let mut tasks = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(&tasks_dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            // Task files are rewritten in place; a half-written one is picked
+            // up on the next change event
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            if let Ok(task) = serde_json::from_str::<TeamTask>(&content) {
+                tasks.push(task);
+            }
+        }
+    }
+    tasks.sort_by(|a, b| match (a.id.parse::<u64>(), b.id.parse::<u64>()) {
+        (Ok(x), Ok(y)) => x.cmp(&y),
+        _ => a.id.cmp(&b.id),
+    });
+    Ok(TeamTaskList {
+        team: team.to_string(),
+        tasks,
+        high_watermark: read_high_watermark(claude_dir, team),
+    })
+}
+
+/// Links team members to the terminals they run in. A member whose agent id
+/// shows up on the command line of a process below a terminal is matched to
+/// that terminal; otherwise the terminal in the member's cwd started most
+/// recently before the member joined is used.
+pub fn map_members(teams: &mut [TeamInfo], terminals: &[TerminalProcess]) {
+    let processes = process_table();
+    let descendants: Vec<(&TerminalProcess, Vec<&str>)> = terminals
+        .iter()
+        .map(|t| (t, t.pid.map(|pid| descendant_commands(&processes, pid)).unwrap_or_default()))
+        .collect();
+    let terminal_dirs: Vec<Option<PathBuf>> = terminals
+        .iter()
+        .map(|t| Path::new(&t.working_directory).canonicalize().ok())
+        .collect();
+
+    for team in teams.iter_mut() {
+        team.member_terminals = team
+            .config
+            .members
+            .iter()
+            .filter_map(|member| {
+                let by_process = descendants.iter().find(|(_, commands)| {
+                    commands.iter().any(|command| has_agent_id(command, &member.agent_id))
+                });
+                if let Some((terminal, _)) = by_process {
+                    return Some(MemberTerminal {
+                        agent_id: member.agent_id.clone(),
+                        terminal_id: terminal.terminal_id.clone(),
+                        matched_by: MatchReason::Process,
+                    });
+                }
+
+                let cwd = Path::new(member.cwd.as_deref()?).canonicalize().ok()?;
+                // joinedAt is in milliseconds; tolerate seconds from older files
+                let joined_ms = member
+                    .joined_at
+                    .map(|t| if t < 100_000_000_000 { t * 1000 } else { t } as i64);
+                terminals
+                    .iter()
+                    .zip(&terminal_dirs)
+                    .filter(|(_, dir)| dir.as_ref() == Some(&cwd))
+                    .map(|(t, _)| t)
+                    .filter(|t| joined_ms.is_none_or(|joined| t.created_at_ms <= joined))
+                    .max_by_key(|t| t.created_at_ms)
+                    .map(|terminal| MemberTerminal {
+                        agent_id: member.agent_id.clone(),
+                        terminal_id: terminal.terminal_id.clone(),
+                        matched_by: MatchReason::Cwd,
+                    })
+            })
+            .collect();
+    }
+}
+
+fn has_agent_id(command: &str, agent_id: &str) -> bool {
+    let mut tokens = command.split_whitespace();
+    while let Some(token) = tokens.next() {
+        if token == format!("--agent-id={}", agent_id) {
+            return true;
+        }
+        if token == "--agent-id" && tokens.next() == Some(agent_id) {
+            return true;
+        }
+    }
+    false
+}
+
+/// (pid, parent pid, command line) of every process visible to the user
+#[cfg(not(target_os = "windows"))]
+fn process_table() -> Vec<(u32, u32, String)> {
+    let Ok(output) = std::process::Command::new("ps")
+        .args(["-axo", "pid=,ppid=,command="])
+        .output()
+    else {
+        return vec![];
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let pid = parts.next()?.parse().ok()?;
+            let ppid = parts.next()?.parse().ok()?;
+            Some((pid, ppid, parts.collect::<Vec<_>>().join(" ")))
+        })
+        .collect()
+}
+
+/// Process trees aren't inspected on Windows; members are matched by cwd only
+#[cfg(target_os = "windows")]
+fn process_table() -> Vec<(u32, u32, String)> {
+    vec![]
+}
+
+/// Command lines of `root` and every process below it
+fn descendant_commands(processes: &[(u32, u32, String)], root: u32) -> Vec<&str> {
+    let mut pids = vec![root];
+    let mut commands = Vec::new();
+    while let Some(pid) = pids.pop() {
+        for (child, parent, command) in processes {
+            if *child == pid {
+                commands.push(command.as_str());
+            }
+            if *parent == pid && *child != pid {
+                pids.push(*child);
+            }
+        }
+    }
+    commands
+}
+
+/// Arguments that make a new claude session join `team` as `name`. The agent
+/// id follows claude's `<name>@<team>` convention.
+pub fn member_args(team: &str, name: &str, agent_type: Option<&str>, model: Option<&str>) -> Vec<String> {
+    let mut args = vec![
+        "--agent-id".to_string(),
+        format!("{}@{}", name, team),
+        "--agent-name".to_string(),
+        name.to_string(),
+        "--team-name".to_string(),
+        team.to_string(),
+    ];
+    if let Some(agent_type) = agent_type {
+        args.push("--agent-type".to_string());
+        args.push(agent_type.to_string());
+    }
+    if let Some(model) = model {
+        args.push("--model".to_string());
+        args.push(model.to_string());
+    }
+    args
+}
+
+/// Last seen state used to turn filesystem events into team events
+#[derive(Default)]
+struct Snapshot {
+    members: HashMap<String, Vec<String>>,
+    tasks: HashMap<String, HashMap<String, TeamTask>>,
+}
+
+impl Snapshot {
+    fn capture(claude_dir: &Path) -> (Self, Vec<TeamInfo>) {
+        let teams = read_teams(claude_dir).unwrap_or_default();
+        let mut snapshot = Snapshot::default();
+        for team in &teams {
+            snapshot.members.insert(
+                team.dir_name.clone(),
+                team.config.members.iter().map(|m| m.agent_id.clone()).collect(),
+            );
+        }
+        // Task lists can exist before (or without) a team config
+        if let Ok(entries) = std::fs::read_dir(claude_dir.join("tasks")) {
+            for entry in entries.flatten().filter(|e| e.path().is_dir()) {
+                let team = entry.file_name().to_string_lossy().to_string();
+                if let Ok(list) = read_tasks(claude_dir, &team) {
+                    let tasks = list.tasks.into_iter().map(|t| (t.id.clone(), t)).collect();
+                    snapshot.tasks.insert(team, tasks);
+                }
+            }
+        }
+        (snapshot, teams)
+    }
+}
+
+/// Emits events for everything that differs between two snapshots. Returns
+/// whether anything changed.
+fn emit_changes(app: &AppHandle, before: &Snapshot, after: &Snapshot, teams: &[TeamInfo]) -> bool {
+    let mut changed = false;
+
+    for team in teams {
+        let known = before.members.get(&team.dir_name);
+        for member in &team.config.members {
+            if known.is_some_and(|ids| ids.contains(&member.agent_id)) {
+                continue;
+            }
+            changed = true;
+            let _ = app.emit(
+                "team-member-joined",
+                serde_json::json!({ "team": team.dir_name, "member": member }),
+            );
+        }
+    }
+    if before.members.len() != after.members.len()
+        || before.members.iter().any(|(team, ids)| after.members.get(team) != Some(ids))
+    {
+        changed = true;
+    }
+
+    for (team, tasks) in &after.tasks {
+        let previous = before.tasks.get(team);
+        for (id, task) in tasks {
+            match previous.and_then(|p| p.get(id)) {
+                None => {
+                    changed = true;
+                    let _ = app.emit("team-task-created", serde_json::json!({ "team": team, "task": task }));
+                }
+                Some(old) if old.status != task.status => {
+                    changed = true;
+                    let _ = app.emit(
+                        "team-task-status-changed",
+                        serde_json::json!({
+                            "team": team,
+                            "task": task,
+                            "previousStatus": old.status,
+                        }),
+                    );
+                }
+                Some(old) if old != task => changed = true,
+                Some(_) => {}
+            }
+        }
+    }
+    if before.tasks.iter().any(|(team, tasks)| {
+        after.tasks.get(team).is_none_or(|now| tasks.keys().any(|id| !now.contains_key(id)))
+    }) {
+        changed = true;
+    }
+
+    if changed {
+        let _ = app.emit("teams-changed", serde_json::json!({ "teams": teams }));
+    }
+    changed
+}
+
+/// The team and task directories and the watches that stand in for them
+/// until claude creates them
+struct TeamWatches {
+    claude_dir: PathBuf,
+    roots: [PathBuf; 2],
+    watched: [bool; 2],
+    /// `~/.claude` while a root is missing, or its parent while `~/.claude`
+    /// itself is
+    parent: Option<PathBuf>,
+}
+
+impl TeamWatches {
+    fn new(claude_dir: PathBuf) -> Self {
+        let roots = [claude_dir.join("teams"), claude_dir.join("tasks")];
+        Self { claude_dir, roots, watched: [false, false], parent: None }
+    }
+
+    /// Watches whichever roots exist, and the nearest existing ancestor of
+    /// any that don't
+    fn ensure(&mut self, watcher: &mut notify::RecommendedWatcher) {
+        for (root, watched) in self.roots.iter().zip(self.watched.iter_mut()) {
+            if *watched && !root.is_dir() {
+                let _ = watcher.unwatch(root);
+                *watched = false;
+            }
+            if !*watched && root.is_dir() {
+                *watched = watcher.watch(root, RecursiveMode::Recursive).is_ok();
+            }
+        }
+
+        let wanted = if self.watched.iter().all(|w| *w) {
+            None
+        } else if self.claude_dir.is_dir() {
+            Some(self.claude_dir.clone())
+        } else {
+            self.claude_dir.parent().filter(|p| p.is_dir()).map(Path::to_path_buf)
+        };
+        if self.parent != wanted {
+            if let Some(old) = self.parent.take() {
+                let _ = watcher.unwatch(&old);
+            }
+            if let Some(dir) = wanted {
+                if watcher.watch(&dir, RecursiveMode::NonRecursive).is_ok() {
+                    self.parent = Some(dir);
+                }
+            }
+        }
+    }
+
+    /// Notes roots removed or renamed away, directly or with `~/.claude`, so
+    /// the next `ensure` watches them again once they're back
+    fn forget_removed(&mut self, event: &notify::Event) {
+        let gone = matches!(
+            event.kind,
+            EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From | RenameMode::Any))
+        );
+        if !gone {
+            return;
+        }
+        for (root, watched) in self.roots.iter().zip(self.watched.iter_mut()) {
+            if event.paths.iter().any(|p| root.starts_with(p)) {
+                *watched = false;
+            }
+        }
+    }
+
+    /// Whether an event touches the roots or an ancestor of them, such as
+    /// `~/.claude` being created
+    fn is_relevant(&self, event: &notify::Result<notify::Event>) -> bool {
+        match event {
+            Ok(event) => event.paths.iter().any(|p| {
+                self.roots.iter().any(|root| p.starts_with(root) || root.starts_with(p))
+            }),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Starts watching `~/.claude/teams` and `~/.claude/tasks` on a background
+/// thread. Directories that don't exist yet, `~/.claude` included, are picked
+/// up once claude creates them, and again if they're removed and recreated.
+pub fn start_watcher(app: AppHandle, claude_dir: PathBuf) {
+    std::thread::spawn(move || {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = match notify::recommended_watcher(tx) {
+            Ok(w) => w,
+            Err(e) => {
+                eprintln!("Failed to start team watcher: {}", e);
+                return;
+            }
+        };
+
+        let mut watches = TeamWatches::new(claude_dir.clone());
+        watches.ensure(&mut watcher);
+
+        let (mut snapshot, _) = Snapshot::capture(&claude_dir);
+        loop {
+            let mut relevant = false;
+            let open = watch::recv_burst(&rx, WATCH_DEBOUNCE, WATCH_MAX_DELAY, |event| {
+                if let Ok(event) = &event {
+                    watches.forget_removed(event);
+                }
+                relevant |= watches.is_relevant(&event);
+            });
+            if !open {
+                return;
+            }
+            if !relevant {
+                continue;
+            }
+            watches.ensure(&mut watcher);
+            let (next, teams) = Snapshot::capture(&claude_dir);
+            emit_changes(&app, &snapshot, &next, &teams);
+            snapshot = next;
+        }
+    });
+}
@@ -18,6 +18,30 @@ pub struct TerminalConfig {
     pub created_at: DateTime<Utc>,
     pub status: TerminalStatus,
     pub color_tag: Option<String>,
+    #[serde(default)]
+    pub kind: TerminalKind,
+    /// Permission policy applied on launch; its generated arguments are not
+    /// part of `claude_args`
+    #[serde(default)]
+    pub permission_policy_id: Option<String>,
+    /// Worktree created for this terminal, if it runs isolated from the
+    /// repository's main checkout
+    #[serde(default)]
+    pub worktree: Option<crate::worktree::WorktreeInfo>,
+    /// Branch, upstream and in-progress operation of the repository the
+    /// terminal runs in, kept current while it runs. Never read back from
+    /// saved sessions or workspaces, where it would be stale.
+    #[serde(skip_deserializing)]
+    pub git: Option<crate::repo::RepoInfo>,
+}
+
+/// What a terminal runs: claude itself, or a plain login shell next to it
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TerminalKind {
+    #[default]
+    Claude,
+    Shell,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -33,6 +57,8 @@ pub struct Terminal {
     #[allow(dead_code)]
     pub pty_pair: PtyPair,
     pub writer: Box<dyn Write + Send>,
+    /// Process id of the spawned shell, used to find claude processes below it
+    pub pid: Option<u32>,
 }
 
 pub struct TerminalManager {
@@ -61,6 +87,17 @@ impl TerminalManager {
         "HOME", "USERPROFILE", "HOMEDRIVE", "HOMEPATH",
     ];
 
+    /// Rejects a claude argument containing shell metacharacters
+    pub fn validate_arg(arg: &str) -> Result<(), String> {
+        if arg.contains(Self::SHELL_METACHARACTERS) {
+            return Err(format!(
+                "Invalid character in argument: \"{}\". Shell metacharacters are not allowed.",
+                arg
+            ));
+        }
+        Ok(())
+    }
+
     pub fn create_terminal(
         &mut self,
         label: String,
@@ -69,17 +106,13 @@ impl TerminalManager {
         env_vars: HashMap<String, String>,
         color_tag: Option<String>,
         nickname: Option<String>,
+        kind: TerminalKind,
         tx: mpsc::Sender<(String, Vec<u8>)>,
         log_file_path: Option<String>,
     ) -> Result<TerminalConfig, String> {
         // Validate claude_args: reject any argument containing shell metacharacters
         for arg in &claude_args {
-            if arg.contains(Self::SHELL_METACHARACTERS) {
-                return Err(format!(
-                    "Invalid character in argument: \"{}\". Shell metacharacters are not allowed.",
-                    arg
-                ));
-            }
+            Self::validate_arg(arg)?;
         }
 
         // Filter out blocked environment variables
@@ -103,14 +136,17 @@ impl TerminalManager {
             .map_err(|e| format!("Failed to open pty: {}", e))?;
 
         // Spawn claude directly so the process exits when claude finishes,
-        // allowing the terminal-finished event to fire for notifications
+        // allowing the terminal-finished event to fire for notifications.
+        // Shell terminals get an interactive login shell instead.
         #[cfg(target_os = "windows")]
         let mut cmd = {
             let mut c = CommandBuilder::new("cmd.exe");
-            c.arg("/C");
-            c.arg("claude");
-            for arg in &claude_args {
-                c.arg(arg);
+            if kind == TerminalKind::Claude {
+                c.arg("/C");
+                c.arg("claude");
+                for arg in &claude_args {
+                    c.arg(arg);
+                }
             }
             c
         };
@@ -149,8 +185,12 @@ impl TerminalManager {
                 }
                 full_cmd.push('\'');
             }
-            c.arg("-lc");
-            c.arg(&full_cmd);
+            if kind == TerminalKind::Claude {
+                c.arg("-lc");
+                c.arg(&full_cmd);
+            } else {
+                c.arg("-l");
+            }
             c
         };
 
@@ -165,8 +205,9 @@ impl TerminalManager {
         }
 
         // Spawn the command
-        let _child = pty_pair.slave.spawn_command(cmd)
+        let child = pty_pair.slave.spawn_command(cmd)
             .map_err(|e| format!("Failed to spawn command: {}", e))?;
+        let pid = child.process_id();
 
         let id = Uuid::new_v4().to_string();
         let config = TerminalConfig {
@@ -180,6 +221,10 @@ impl TerminalManager {
             created_at: Utc::now(),
             status: TerminalStatus::Running,
             color_tag,
+            kind,
+            permission_policy_id: None,
+            worktree: None,
+            git: None,
         };
 
         let mut reader = pty_pair.master.try_clone_reader()
@@ -228,6 +273,7 @@ impl TerminalManager {
                 config: config.clone(),
                 pty_pair,
                 writer,
+                pid,
             },
         );
 
@@ -278,6 +324,11 @@ impl TerminalManager {
         self.terminals.values().map(|t| t.config.clone()).collect()
     }
 
+    /// Configs of all terminals together with their root process ids
+    pub fn get_processes(&self) -> Vec<(TerminalConfig, Option<u32>)> {
+        self.terminals.values().map(|t| (t.config.clone(), t.pid)).collect()
+    }
+
     pub fn update_label(&mut self, id: &str, label: String) -> Result<(), String> {
         if let Some(terminal) = self.terminals.get_mut(id) {
             terminal.config.label = label;
@@ -287,6 +338,41 @@ impl TerminalManager {
         }
     }
 
+    pub fn update_color_tag(&mut self, id: &str, color_tag: Option<String>) -> Result<(), String> {
+        let terminal = self.terminals.get_mut(id).ok_or("Terminal not found")?;
+        terminal.config.color_tag = color_tag;
+        Ok(())
+    }
+
+    /// Records what a terminal was launched with after spawning, e.g. the
+    /// profile, policy and worktree, without the arguments generated from them
+    pub fn set_launch_details(
+        &mut self,
+        id: &str,
+        profile_id: Option<String>,
+        permission_policy_id: Option<String>,
+        worktree: Option<crate::worktree::WorktreeInfo>,
+        claude_args: Vec<String>,
+    ) -> Result<TerminalConfig, String> {
+        let terminal = self.terminals.get_mut(id).ok_or("Terminal not found")?;
+        terminal.config.profile_id = profile_id;
+        terminal.config.permission_policy_id = permission_policy_id;
+        terminal.config.worktree = worktree;
+        terminal.config.claude_args = claude_args;
+        Ok(terminal.config.clone())
+    }
+
+    /// Records the latest state of a terminal's repository and returns the
+    /// previous one
+    pub fn set_git(
+        &mut self,
+        id: &str,
+        git: Option<crate::repo::RepoInfo>,
+    ) -> Result<Option<crate::repo::RepoInfo>, String> {
+        let terminal = self.terminals.get_mut(id).ok_or("Terminal not found")?;
+        Ok(std::mem::replace(&mut terminal.config.git, git))
+    }
+
     pub fn update_nickname(&mut self, id: &str, nickname: String) -> Result<(), String> {
         if let Some(terminal) = self.terminals.get_mut(id) {
             terminal.config.nickname = Some(nickname);
@@ -0,0 +1,156 @@
+use crate::git;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::{Match, WalkBuilder};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// Directories watched per tree; deeper trees are only partly watched
+const MAX_WATCHED_DIRS: usize = 10_000;
+
+/// Waits for an event, then takes the burst that usually follows it: events
+/// until `quiet` passes without one, or `max_wait` after the first, so a
+/// steady stream (a build, say) still ends bursts regularly. `on_event` sees
+/// each event as it arrives. Returns false once the channel is closed.
+pub fn recv_burst<T>(rx: &Receiver<T>, quiet: Duration, max_wait: Duration, mut on_event: impl FnMut(T)) -> bool {
+    let Ok(first) = rx.recv() else {
+        return false;
+    };
+    let started = Instant::now();
+    on_event(first);
+    loop {
+        let left = max_wait.saturating_sub(started.elapsed());
+        if left.is_zero() {
+            return true;
+        }
+        match rx.recv_timeout(quiet.min(left)) {
+            Ok(event) => on_event(event),
+            Err(RecvTimeoutError::Timeout) => return true,
+            Err(RecvTimeoutError::Disconnected) => return false,
+        }
+    }
+}
+
+/// A directory tree watched one directory at a time, skipping what git
+/// ignores so build output and dependencies don't use up watches
+pub struct WatchedTree {
+    pub root: PathBuf,
+    /// Directory names never watched, whatever the ignore files say
+    skipped: &'static [&'static str],
+    /// `.gitignore` matchers, deepest directory first
+    ignores: Vec<(PathBuf, Gitignore)>,
+    /// `.git/info/exclude` and the user's global excludes file
+    excludes: Vec<Gitignore>,
+    watched_dirs: usize,
+}
+
+impl WatchedTree {
+    /// Ignore rules for `root`, including the `.gitignore` files above it in
+    /// its repository. Nothing is watched until `watch` is called.
+    pub fn new(root: &Path, skipped: &'static [&'static str]) -> Self {
+        let mut tree = WatchedTree {
+            root: root.to_path_buf(),
+            skipped,
+            ignores: vec![],
+            excludes: vec![Gitignore::global().0],
+            watched_dirs: 0,
+        };
+        let Some(toplevel) = git::toplevel(root).map(PathBuf::from) else {
+            return tree;
+        };
+        if let Ok(common_dir) = git::run_trimmed(root, &["rev-parse", "--git-common-dir"]) {
+            let mut builder = GitignoreBuilder::new(&toplevel);
+            builder.add(root.join(common_dir).join("info").join("exclude"));
+            if let Ok(exclude) = builder.build() {
+                tree.excludes.push(exclude);
+            }
+        }
+        // The walk picks up the root's own .gitignore and those below it
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let toplevel = toplevel.canonicalize().unwrap_or(toplevel);
+        for dir in root.ancestors().skip(1).take_while(|d| d.starts_with(&toplevel)) {
+            let gitignore = dir.join(".gitignore");
+            if gitignore.is_file() {
+                tree.load_gitignore(&gitignore);
+            }
+        }
+        tree
+    }
+
+    /// Watches `dir` and every directory below it that isn't ignored
+    pub fn watch(&mut self, watcher: &mut RecommendedWatcher, dir: &Path) {
+        let skipped = self.skipped;
+        let walk = WalkBuilder::new(dir)
+            .hidden(false)
+            .require_git(false)
+            .filter_entry(move |entry| {
+                let name = entry.file_name();
+                name != ".git" && !skipped.iter().any(|s| name == *s)
+            })
+            .build();
+        for entry in walk.flatten() {
+            let path = entry.path();
+            if entry.file_type().is_some_and(|t| t.is_dir()) {
+                if self.watched_dirs >= MAX_WATCHED_DIRS {
+                    continue;
+                }
+                if watcher.watch(path, RecursiveMode::NonRecursive).is_ok() {
+                    self.watched_dirs += 1;
+                }
+            } else if entry.file_name() == ".gitignore" {
+                self.load_gitignore(path);
+            }
+        }
+    }
+
+    fn load_gitignore(&mut self, path: &Path) {
+        let Some(dir) = path.parent() else {
+            return;
+        };
+        self.ignores.retain(|(d, _)| d != dir);
+        let (gitignore, _) = Gitignore::new(path);
+        self.ignores.push((dir.to_path_buf(), gitignore));
+        self.ignores.sort_by_key(|(d, _)| std::cmp::Reverse(d.components().count()));
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let skipped = |rel: &Path| {
+            rel.components().any(|c| {
+                let name = c.as_os_str();
+                name == ".git" || self.skipped.iter().any(|s| name == *s)
+            })
+        };
+        if path.strip_prefix(&self.root).is_ok_and(skipped) {
+            return true;
+        }
+        for (dir, gitignore) in &self.ignores {
+            if !path.starts_with(dir) {
+                continue;
+            }
+            match gitignore.matched_path_or_any_parents(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        self.excludes
+            .iter()
+            .any(|exclude| exclude.matched(path, is_dir).is_ignore())
+    }
+
+    /// Updates watches for an event on `path`, a path under the root, and
+    /// says whether it matters, i.e. isn't ignored
+    pub fn handle_path(&mut self, watcher: &mut RecommendedWatcher, kind: &EventKind, path: &Path) -> bool {
+        let is_dir = path.is_dir();
+        if self.is_ignored(path, is_dir) {
+            return false;
+        }
+        if is_dir && matches!(kind, EventKind::Create(_) | EventKind::Modify(notify::event::ModifyKind::Name(_))) {
+            self.watch(watcher, path);
+        } else if path.file_name().is_some_and(|n| n == ".gitignore") {
+            self.load_gitignore(path);
+        }
+        true
+    }
+}
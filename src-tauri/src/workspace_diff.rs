@@ -0,0 +1,115 @@
+use crate::terminal::TerminalConfig;
+use serde::{Deserialize, Serialize};
+
+/// Fields compared between revisions. Runtime-only fields (id, created_at,
+/// status) change on every save and are deliberately left out.
+const COMPARED_FIELDS: &[&str] = &[
+    "label",
+    "nickname",
+    "profile_id",
+    "working_directory",
+    "claude_args",
+    "env_vars",
+    "color_tag",
+    "kind",
+    "permission_policy_id",
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: serde_json::Value,
+    pub after: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TerminalChange {
+    pub label: String,
+    pub fields: Vec<FieldChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkspaceDiff {
+    pub from_revision: i64,
+    pub to_revision: i64,
+    pub added: Vec<TerminalConfig>,
+    pub removed: Vec<TerminalConfig>,
+    pub changed: Vec<TerminalChange>,
+}
+
+/// Compares two workspace layouts. Terminals are paired by id first and then by
+/// label, since ids are regenerated whenever a workspace is relaunched and saved.
+pub fn diff_terminals(
+    from_revision: i64,
+    before: Vec<TerminalConfig>,
+    to_revision: i64,
+    after: Vec<TerminalConfig>,
+) -> WorkspaceDiff {
+    let mut unmatched: Vec<Option<TerminalConfig>> = before.into_iter().map(Some).collect();
+    let mut pairs = Vec::new();
+    let mut added = Vec::new();
+
+    let mut remaining = Vec::new();
+    for new in after {
+        match unmatched
+            .iter()
+            .position(|old| old.as_ref().is_some_and(|o| o.id == new.id))
+        {
+            Some(i) => pairs.push((unmatched[i].take().unwrap(), new)),
+            None => remaining.push(new),
+        }
+    }
+    for new in remaining {
+        match unmatched
+            .iter()
+            .position(|old| old.as_ref().is_some_and(|o| o.label == new.label))
+        {
+            Some(i) => pairs.push((unmatched[i].take().unwrap(), new)),
+            None => added.push(new),
+        }
+    }
+
+    let changed = pairs
+        .into_iter()
+        .filter_map(|(old, new)| {
+            let fields = diff_fields(&old, &new);
+            if fields.is_empty() {
+                None
+            } else {
+                Some(TerminalChange {
+                    label: new.label,
+                    fields,
+                })
+            }
+        })
+        .collect();
+
+    WorkspaceDiff {
+        from_revision,
+        to_revision,
+        added,
+        removed: unmatched.into_iter().flatten().collect(),
+        changed,
+    }
+}
+
+fn diff_fields(old: &TerminalConfig, new: &TerminalConfig) -> Vec<FieldChange> {
+    let old = serde_json::to_value(old).unwrap_or_default();
+    let new = serde_json::to_value(new).unwrap_or_default();
+    COMPARED_FIELDS
+        .iter()
+        .filter_map(|field| {
+            let before = old.get(field).cloned().unwrap_or_default();
+            let after = new.get(field).cloned().unwrap_or_default();
+            if before == after {
+                None
+            } else {
+                Some(FieldChange {
+                    field: field.to_string(),
+                    before,
+                    after,
+                })
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,223 @@
+use crate::git;
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A git worktree created for a single terminal
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WorktreeInfo {
+    pub id: String,
+    /// Root of the worktree checkout
+    pub path: String,
+    pub branch: String,
+    /// Top level of the repository the worktree was created from
+    pub repo_path: String,
+    /// Branch that was checked out in the repository at creation; merges go
+    /// back into it. None when HEAD was detached.
+    pub base_branch: Option<String>,
+    pub base_commit: String,
+    pub created_at: String,
+}
+
+/// A managed worktree with its current state
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorktreeStatus {
+    #[serde(flatten)]
+    pub info: WorktreeInfo,
+    pub exists: bool,
+    /// Uncommitted changes, including untracked files
+    pub dirty: bool,
+    /// Commits on the worktree branch that the base branch doesn't have
+    pub ahead: u32,
+    pub terminal_id: Option<String>,
+}
+
+/// What to do with a terminal's worktree when it is closed
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WorktreeAction {
+    /// Leave the worktree and branch in place
+    Keep,
+    /// Merge the branch into the base branch, then remove both
+    Merge,
+    /// Remove the worktree and delete its branch, discarding its work
+    Remove,
+}
+
+/// Directory holding managed worktrees and their metadata
+fn worktrees_dir() -> Result<PathBuf, String> {
+    Ok(ProjectDirs::from("com", "claudeterminal", "ClaudeTerminal")
+        .ok_or("Failed to get project directories")?
+        .data_dir()
+        .join("worktrees"))
+}
+
+fn metadata_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.json", id))
+}
+
+/// Lowercase, dash-separated form of a label for use in branch names
+pub fn slug(label: &str) -> String {
+    let slug = label
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    slug.chars().take(40).collect::<String>().trim_end_matches('-').to_string()
+}
+
+/// Creates a worktree on a new branch for a terminal about to start in
+/// `working_directory`. Returns the worktree and the directory the terminal
+/// should start in, which keeps any subdirectory of the repository.
+pub fn create(
+    working_directory: &str,
+    branch: Option<&str>,
+    label: &str,
+) -> Result<(WorktreeInfo, String), String> {
+    let cwd = Path::new(working_directory);
+    let repo_path = git::toplevel(cwd)
+        .ok_or_else(|| format!("{} is not inside a git repository", working_directory))?;
+    let repo = Path::new(&repo_path);
+    let prefix = git::run_trimmed(cwd, &["rev-parse", "--show-prefix"])?;
+    let base_commit = git::run_trimmed(repo, &["rev-parse", "--verify", "HEAD"])
+        .map_err(|_| "The repository has no commits yet".to_string())?;
+    let base_branch = git::current_branch(repo);
+
+    let id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+    let branch = match branch.map(str::trim).filter(|b| !b.is_empty()) {
+        Some(branch) => branch.to_string(),
+        None => match slug(label) {
+            s if s.is_empty() => format!("claude/{}", id),
+            s => format!("claude/{}-{}", s, id),
+        },
+    };
+    if branch.starts_with('-') {
+        return Err(format!("Invalid branch name: {}", branch));
+    }
+    git::run(repo, &["check-ref-format", "--branch", &branch])
+        .map_err(|_| format!("Invalid branch name: {}", branch))?;
+    if git::run(repo, &["rev-parse", "--verify", "--quiet", &format!("refs/heads/{}", branch)]).is_ok() {
+        return Err(format!("Branch {} already exists", branch));
+    }
+
+    let dir = worktrees_dir()?;
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let path = dir.join(&id);
+    let path_str = path.to_string_lossy().to_string();
+    git::run(repo, &["worktree", "add", "-b", &branch, &path_str, &base_commit])?;
+
+    let info = WorktreeInfo {
+        id: id.clone(),
+        path: path_str,
+        branch,
+        repo_path,
+        base_branch,
+        base_commit,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+    let json = serde_json::to_string_pretty(&info).map_err(|e| e.to_string())?;
+    if let Err(e) = std::fs::write(metadata_path(&dir, &id), json) {
+        let _ = remove(&info);
+        return Err(format!("Failed to record worktree: {}", e));
+    }
+
+    let start_dir = path.join(prefix.trim_end_matches('/'));
+    let start_dir = if start_dir.is_dir() { start_dir } else { path };
+    Ok((info, start_dir.to_string_lossy().to_string()))
+}
+
+/// The managed worktree a directory belongs to, e.g. when a saved session
+/// is restored into an existing worktree
+pub fn find(working_directory: &str) -> Option<WorktreeInfo> {
+    let dir = worktrees_dir().ok()?;
+    let rest = Path::new(working_directory).strip_prefix(&dir).ok()?;
+    let id = rest.components().next()?.as_os_str().to_str()?;
+    load(id).ok()
+}
+
+pub fn load(id: &str) -> Result<WorktreeInfo, String> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("Invalid worktree id".to_string());
+    }
+    let content = std::fs::read_to_string(metadata_path(&worktrees_dir()?, id))
+        .map_err(|_| format!("Worktree not found: {}", id))?;
+    serde_json::from_str(&content).map_err(|e| e.to_string())
+}
+
+/// All managed worktrees, oldest first
+pub fn list() -> Result<Vec<WorktreeInfo>, String> {
+    let dir = worktrees_dir()?;
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Ok(vec![]);
+    };
+    let mut worktrees: Vec<WorktreeInfo> = entries
+        .flatten()
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|e| serde_json::from_str(&std::fs::read_to_string(e.path()).ok()?).ok())
+        .collect();
+    worktrees.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    Ok(worktrees)
+}
+
+pub fn status(info: WorktreeInfo, terminal_id: Option<String>) -> WorktreeStatus {
+    let path = Path::new(&info.path);
+    let exists = path.is_dir();
+    let dirty = exists && git::is_dirty(path, true).unwrap_or(false);
+    let base = info.base_branch.as_deref().unwrap_or(&info.base_commit);
+    let ahead = git::run_trimmed(
+        Path::new(&info.repo_path),
+        &["rev-list", "--count", &format!("{}..{}", base, info.branch)],
+    )
+    .ok()
+    .and_then(|n| n.parse().ok())
+    .unwrap_or(0);
+    WorktreeStatus { info, exists, dirty, ahead, terminal_id }
+}
+
+/// Checks that `merge` can run before the terminal using the worktree is
+/// closed, so a refused merge leaves everything as it was
+pub fn check_merge(info: &WorktreeInfo) -> Result<(), String> {
+    let base = info
+        .base_branch
+        .as_deref()
+        .ok_or("The worktree was created from a detached HEAD; there is no branch to merge into")?;
+    if Path::new(&info.path).is_dir() && git::is_dirty(Path::new(&info.path), true)? {
+        return Err("The worktree has uncommitted changes; commit or discard them before merging".to_string());
+    }
+    let repo = Path::new(&info.repo_path);
+    if git::current_branch(repo).as_deref() != Some(base) {
+        return Err(format!("Check out {} in {} to merge the worktree back", base, info.repo_path));
+    }
+    if git::is_dirty(repo, false)? {
+        return Err(format!("{} has uncommitted changes; commit or stash them before merging", info.repo_path));
+    }
+    Ok(())
+}
+
+/// Merges the worktree branch into its base branch and removes the worktree.
+/// A conflicting merge is aborted and the worktree is kept.
+pub fn merge(info: &WorktreeInfo) -> Result<(), String> {
+    check_merge(info)?;
+    let repo = Path::new(&info.repo_path);
+    let message = format!("Merge branch '{}'", info.branch);
+    if let Err(e) = git::run(repo, &["merge", "--no-ff", "-m", &message, &info.branch]) {
+        let _ = git::run(repo, &["merge", "--abort"]);
+        return Err(format!("Merge failed and was aborted; the worktree was kept: {}", e));
+    }
+    remove(info)
+}
+
+/// Removes the worktree, deletes its branch and forgets it
+pub fn remove(info: &WorktreeInfo) -> Result<(), String> {
+    let repo = Path::new(&info.repo_path);
+    if Path::new(&info.path).exists() {
+        git::run(repo, &["worktree", "remove", "--force", &info.path])?;
+    } else {
+        let _ = git::run(repo, &["worktree", "prune"]);
+    }
+    // The branch may already be gone if it was deleted by hand
+    let _ = git::run(repo, &["branch", "-D", &info.branch]);
+    let _ = std::fs::remove_file(metadata_path(&worktrees_dir()?, &info.id));
+    Ok(())
+}
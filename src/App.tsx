@@ -20,6 +20,7 @@ import { ClaudeConfigModal } from './components/ClaudeConfigModal';
 import { OrchestrationPanel } from './components/OrchestrationPanel';
 import { useAppStore } from './store/appStore';
 import { useTerminalStore } from './store/terminalStore';
+import type { RepoInfo, TerminalConflict } from './store/terminalStore';
 import { useKeyboardShortcuts } from './hooks/useKeyboardShortcuts';
 import { useNotification } from './hooks/useNotification';
 import { listen } from '@tauri-apps/api/event';
@@ -67,6 +68,11 @@ interface SystemStatus {
   claude_version: string | null;
 }
 
+interface DatabaseStatus {
+  persistent: boolean;
+  error: string | null;
+}
+
 interface SavedTerminalConfig {
   id: string;
   label: string;
@@ -79,8 +85,9 @@ interface SavedTerminalConfig {
 
 function App() {
   const { sidebarOpen, hintsOpen, changesOpen, orchestrationOpen, settingsOpen, profileModalOpen, newTerminalModalOpen, workspaceModalOpen, sessionHistoryOpen, snippetsModalOpen, commandPaletteOpen, whatsNewOpen, claudeConfigOpen, notifyOnFinish, restoreSession, triggerChangesRefresh, showRestoreBanner, pendingRestoreConfigs, setShowRestoreBanner, setPendingRestoreConfigs, lastSeenVersion, setLastSeenVersion, openWhatsNew } = useAppStore();
-  const { handleTerminalOutput, updateTerminalStatus, createTerminal } = useTerminalStore();
+  const { handleTerminalOutput, updateTerminalStatus, updateTerminalGit, handleBranchChange, createTerminal } = useTerminalStore();
   const [showSetup, setShowSetup] = useState<boolean | null>(null);
+  const [databaseError, setDatabaseError] = useState<string | null>(null);
   const { notify } = useNotification();
 
   useKeyboardShortcuts();
@@ -151,6 +158,64 @@ function App() {
     };
   }, [notifyOnFinish, notify, updateTerminalStatus]);
 
+  useEffect(() => {
+    const unlisten = listen<{ id: string; git: RepoInfo | null }>('terminal-git-changed', (event) => {
+      updateTerminalGit(event.payload.id, event.payload.git);
+    });
+
+    return () => {
+      unlisten.then(fn => fn());
+    };
+  }, [updateTerminalGit]);
+
+  useEffect(() => {
+    const unlisten = listen<BranchChange>('terminal-branch-changed', (event) => {
+      handleBranchChange(event.payload).catch((err) => {
+        console.error('Failed to follow branch change:', err);
+      });
+    });
+
+    return () => {
+      unlisten.then(fn => fn());
+    };
+  }, [handleBranchChange]);
+
+  useEffect(() => {
+    const unlisten = listen<TerminalConflict>('terminal-conflict', (event) => {
+      const { terminals, files } = event.payload;
+      const names = terminals.map(t => t.label).join(' and ');
+      const paths = files.map(f => f.overlap === 'hunk' ? `${f.path} (same lines)` : f.path).join(', ');
+      notify('Overlapping Changes', `${names} are both changing ${paths}.`);
+    });
+
+    return () => {
+      unlisten.then(fn => fn());
+    };
+  }, [notify]);
+
+  // The app falls back to an in-memory database when the real one can't be
+  // opened; nothing recorded then survives a restart
+  useEffect(() => {
+    if (showSetup !== false) return;
+
+    invoke<DatabaseStatus>('get_database_status')
+      .then((status) => {
+        if (!status.persistent) setDatabaseError(status.error ?? 'Unknown error');
+      })
+      .catch((err) => {
+        console.error('Failed to check database status:', err);
+      });
+  }, [showSetup]);
+
+  const handleRetryDatabase = async () => {
+    try {
+      const status = await invoke<DatabaseStatus>('retry_database_open');
+      setDatabaseError(status.persistent ? null : status.error ?? 'Unknown error');
+    } catch (err) {
+      setDatabaseError(String(err));
+    }
+  };
+
   // Restore previous session on startup — show banner instead of silently restoring
   useEffect(() => {
     if (showSetup !== false) return;
@@ -245,6 +310,38 @@ function App() {
         <>
           <AutoUpdater />
 
+          <AnimatePresence>
+            {databaseError && (
+              <motion.div
+                initial={{ height: 0, opacity: 0 }}
+                animate={{ height: 'auto', opacity: 1 }}
+                exit={{ height: 0, opacity: 0 }}
+                transition={{ duration: 0.2 }}
+                className="bg-error/10 border-b border-error/20 overflow-hidden"
+              >
+                <div className="flex items-center justify-between gap-4 px-4 py-2.5">
+                  <p className="text-error text-[13px] min-w-0 truncate" title={databaseError}>
+                    The database could not be opened, so history, profiles and settings changes won't be saved: {databaseError}
+                  </p>
+                  <div className="flex items-center gap-2 shrink-0">
+                    <button
+                      onClick={handleRetryDatabase}
+                      className="bg-accent-primary hover:bg-accent-secondary text-white px-3 py-1 rounded-md text-[12px] font-medium transition-colors"
+                    >
+                      Retry
+                    </button>
+                    <button
+                      onClick={() => setDatabaseError(null)}
+                      className="text-text-secondary hover:text-text-primary px-3 py-1 rounded-md text-[12px] transition-colors"
+                    >
+                      Dismiss
+                    </button>
+                  </div>
+                </div>
+              </motion.div>
+            )}
+          </AnimatePresence>
+
           {/* Restore Banner (F3) */}
           <AnimatePresence>
             {showRestoreBanner && pendingRestoreConfigs && (
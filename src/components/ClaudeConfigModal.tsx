@@ -407,9 +407,12 @@ function FileListTab({ type }: { type: 'agents' | 'commands' }) {
 }
 
 function getAgentTemplate(): string {
-  return `# Agent Name
+  return `---
+name: agent-name
+description: Description of what this agent does and when to use it.
+---
 
-Description of what this agent does.
+# Agent Name
 
 ## Instructions
 
@@ -419,9 +422,12 @@ Description of what this agent does.
 }
 
 function getCommandTemplate(): string {
-  return `# Command Name
+  return `---
+description: Description of this command.
+argument-hint: [arguments]
+---
 
-Description of this command.
+# Command Name
 
 ## Steps
 
@@ -1,12 +1,41 @@
 import { useState, useEffect } from 'react';
-import { RefreshCw, GitBranch, FilePlus, FileEdit, FileX, FileQuestion, ArrowRightLeft, FolderOpen } from 'lucide-react';
+import { RefreshCw, GitBranch, FilePlus, FileEdit, FileX, FileQuestion, ArrowRightLeft, FolderOpen, Copy, AlertTriangle } from 'lucide-react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import { useTerminalStore } from '../store/terminalStore';
+import type { RepoInfo } from '../store/terminalStore';
 import { useAppStore } from '../store/appStore';
 
+type ChangeKind = 'modified' | 'typechanged' | 'added' | 'deleted' | 'renamed' | 'copied' | 'unmerged' | 'untracked';
+
+interface DiffLine {
+  kind: 'context' | 'added' | 'removed';
+  content: string;
+  old_line: number | null;
+  new_line: number | null;
+}
+
+interface DiffHunk {
+  header: string;
+  old_start: number;
+  old_lines: number;
+  new_start: number;
+  new_lines: number;
+  staged: boolean;
+  lines: DiffLine[];
+}
+
 interface FileChange {
   path: string;
   status: string;
+  index_status: ChangeKind | null;
+  worktree_status: ChangeKind | null;
+  original_path: string | null;
+  added: number;
+  removed: number;
+  binary: boolean;
+  hunks: DiffHunk[];
+  truncated: boolean;
 }
 
 interface FileChangesResult {
@@ -15,6 +44,7 @@ interface FileChangesResult {
   changes: FileChange[];
   is_git_repo: boolean;
   branch: string | null;
+  repo: RepoInfo | null;
   error: string | null;
 }
 
@@ -23,6 +53,9 @@ const statusConfig: Record<string, { label: string; color: string; icon: React.R
   modified: { label: 'Modified', color: 'text-yellow-400', icon: <FileEdit size={14} /> },
   deleted: { label: 'Deleted', color: 'text-red-400', icon: <FileX size={14} /> },
   renamed: { label: 'Renamed', color: 'text-blue-400', icon: <ArrowRightLeft size={14} /> },
+  copied: { label: 'Copied', color: 'text-blue-400', icon: <Copy size={14} /> },
+  typechanged: { label: 'Type Changed', color: 'text-yellow-400', icon: <FileEdit size={14} /> },
+  conflicted: { label: 'Conflicted', color: 'text-red-400', icon: <AlertTriangle size={14} /> },
   untracked: { label: 'Untracked', color: 'text-text-tertiary', icon: <FileQuestion size={14} /> },
 };
 
@@ -51,6 +84,20 @@ export function FileChangesPanel() {
     fetchChanges();
   }, [activeTerminalId, changesRefreshTrigger]);
 
+  useEffect(() => {
+    if (!activeTerminalId) return;
+    const unlisten = listen<FileChangesResult>('terminal-files-changed', (event) => {
+      if (event.payload.terminal_id === activeTerminalId) {
+        setResult(event.payload);
+        setError(null);
+      }
+    });
+
+    return () => {
+      unlisten.then(fn => fn());
+    };
+  }, [activeTerminalId]);
+
   // Group changes by status
   const grouped = result?.changes.reduce<Record<string, FileChange[]>>((acc, change) => {
     const key = change.status;
@@ -59,7 +106,7 @@ export function FileChangesPanel() {
     return acc;
   }, {}) ?? {};
 
-  const statusOrder = ['new', 'modified', 'deleted', 'renamed', 'untracked'];
+  const statusOrder = ['conflicted', 'new', 'modified', 'typechanged', 'deleted', 'renamed', 'copied', 'untracked'];
 
   return (
     <div className="h-full bg-bg-secondary border-l border-border flex flex-col">
@@ -78,7 +125,15 @@ export function FileChangesPanel() {
         {result?.branch && (
           <div className="flex items-center gap-1.5 text-text-secondary">
             <GitBranch size={12} />
-            <span className="text-[11px] font-mono">{result.branch}</span>
+            <span className="text-[11px] font-mono">
+              {result.repo?.detached && result.repo.head ? `detached at ${result.repo.head.slice(0, 7)}` : result.branch}
+            </span>
+            {result.repo?.upstream && (result.repo.ahead > 0 || result.repo.behind > 0) && (
+              <span className="text-[11px] font-mono text-text-tertiary" title={result.repo.upstream}>
+                {result.repo.ahead > 0 && `↑${result.repo.ahead}`}
+                {result.repo.behind > 0 && ` ↓${result.repo.behind}`}
+              </span>
+            )}
           </div>
         )}
       </div>
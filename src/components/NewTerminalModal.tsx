@@ -3,7 +3,7 @@ import { motion } from 'framer-motion';
 import { X, FolderOpen, Terminal, Zap } from 'lucide-react';
 import { invoke } from '@tauri-apps/api/core';
 import { useAppStore } from '../store/appStore';
-import { useTerminalStore } from '../store/terminalStore';
+import { useTerminalStore, TAG_COLORS } from '../store/terminalStore';
 import { homeDir } from '@tauri-apps/api/path';
 import { open } from '@tauri-apps/plugin-dialog';
 
@@ -19,16 +19,6 @@ interface ConfigProfile {
   is_default: boolean;
 }
 
-const TAG_COLORS = [
-  'bg-red-500',
-  'bg-orange-500',
-  'bg-yellow-500',
-  'bg-green-500',
-  'bg-blue-500',
-  'bg-purple-500',
-  'bg-pink-500',
-];
-
 export function NewTerminalModal() {
   const { closeNewTerminalModal, defaultClaudeArgs } = useAppStore();
   const { terminals, createTerminal } = useTerminalStore();
@@ -1,6 +1,6 @@
 import { useState, useMemo } from 'react';
 import { AnimatePresence } from 'framer-motion';
-import { Plus, Search, MoreVertical, Copy, Trash2, Edit3, Tag, Grid3X3, FolderOpen, Clock, FileText, Settings } from 'lucide-react';
+import { Plus, Search, MoreVertical, Copy, Trash2, Edit3, Tag, Grid3X3, FolderOpen, Clock, FileText, Settings, GitBranch } from 'lucide-react';
 import { useTerminalStore } from '../store/terminalStore';
 import { useAppStore } from '../store/appStore';
 
@@ -146,6 +146,15 @@ export function Sidebar() {
                     <p className="text-text-tertiary text-[11px] truncate mt-0.5">
                       {terminal.working_directory}
                     </p>
+                    {terminal.git && (
+                      <p className="flex items-center gap-1 text-text-tertiary text-[11px] font-mono truncate">
+                        <GitBranch size={10} className="flex-shrink-0" />
+                        {terminal.git.branch ?? `detached at ${terminal.git.head?.slice(0, 7) ?? 'HEAD'}`}
+                        {terminal.git.operation && (
+                          <span className="text-warning">({terminal.git.operation})</span>
+                        )}
+                      </p>
+                    )}
                   </>
                 )}
               </div>
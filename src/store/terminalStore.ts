@@ -2,6 +2,72 @@ import { create } from 'zustand';
 import { invoke } from '@tauri-apps/api/core';
 import { Terminal } from '@xterm/xterm';
 
+export interface StashEntry {
+  index: number;
+  message: string;
+  commit: string;
+}
+
+export interface CommitSummary {
+  id: string;
+  summary: string;
+  author: string;
+  time: string;
+}
+
+export interface RepoInfo {
+  root: string;
+  branch: string | null;
+  detached: boolean;
+  operation: 'merge' | 'rebase' | 'cherrypick' | 'revert' | 'bisect' | 'applymailbox' | null;
+  head: string | null;
+  upstream: string | null;
+  ahead: number;
+  behind: number;
+  stashes: StashEntry[];
+  last_commit: CommitSummary | null;
+}
+
+export const TAG_COLORS = [
+  'bg-red-500',
+  'bg-orange-500',
+  'bg-yellow-500',
+  'bg-green-500',
+  'bg-blue-500',
+  'bg-purple-500',
+  'bg-pink-500',
+];
+
+// Same branch, same color, in every terminal
+function branchColorTag(branch: string): string {
+  let hash = 0;
+  for (const ch of branch) {
+    hash = (hash * 31 + ch.charCodeAt(0)) >>> 0;
+  }
+  return TAG_COLORS[hash % TAG_COLORS.length];
+}
+
+export interface BranchChange {
+  id: string;
+  previous_branch: string | null;
+  branch: string | null;
+  detached: boolean;
+  head: string | null;
+}
+
+export interface ConflictFile {
+  path: string;
+  overlap: 'path' | 'hunk';
+  ranges: { start: number; end: number }[];
+}
+
+export interface TerminalConflict {
+  repository: string;
+  terminals: { terminal_id: string; label: string; worktree: string }[];
+  shared_worktree: boolean;
+  files: ConflictFile[];
+}
+
 export interface TerminalConfig {
   id: string;
   label: string;
@@ -13,6 +79,7 @@ export interface TerminalConfig {
   created_at: string;
   status: 'Running' | 'Idle' | 'Error' | 'Stopped';
   color_tag: string | null;
+  git?: RepoInfo | null;
 }
 
 interface TerminalInstance {
@@ -44,6 +111,8 @@ interface TerminalState {
   setXterm: (id: string, xterm: Terminal) => void;
   handleTerminalOutput: (id: string, data: Uint8Array) => void;
   updateTerminalStatus: (id: string, status: TerminalConfig['status']) => void;
+  updateTerminalGit: (id: string, git: RepoInfo | null) => void;
+  handleBranchChange: (change: BranchChange) => Promise<void>;
   getTerminalList: () => TerminalConfig[];
   clearUnread: (id: string) => void;
   hasUnread: (id: string) => boolean;
@@ -197,6 +266,49 @@ export const useTerminalStore = create<TerminalState>((set, get) => ({
     });
   },
 
+  updateTerminalGit: (id, git) => {
+    set((state) => {
+      const newTerminals = new Map(state.terminals);
+      const instance = newTerminals.get(id);
+      if (instance) {
+        instance.config.git = git;
+      }
+      return { terminals: newTerminals };
+    });
+  },
+
+  handleBranchChange: async ({ id, previous_branch, branch }) => {
+    const instance = get().terminals.get(id);
+    if (!instance || !branch) return;
+    const { label, color_tag } = instance.config;
+
+    // Only labels naming the old branch follow it, e.g. "main" or "api (main)"
+    let nextLabel = label;
+    if (previous_branch && label === previous_branch) {
+      nextLabel = branch;
+    } else if (previous_branch && label.endsWith(` (${previous_branch})`)) {
+      nextLabel = `${label.slice(0, -previous_branch.length - 3)} (${branch})`;
+    }
+    const nextColorTag = color_tag ? branchColorTag(branch) : color_tag;
+
+    if (nextLabel !== label) {
+      await invoke('update_terminal_label', { id, label: nextLabel });
+    }
+    if (nextColorTag !== color_tag) {
+      await invoke('update_terminal_color_tag', { id, colorTag: nextColorTag });
+    }
+
+    set((state) => {
+      const newTerminals = new Map(state.terminals);
+      const inst = newTerminals.get(id);
+      if (inst) {
+        inst.config.label = nextLabel;
+        inst.config.color_tag = nextColorTag;
+      }
+      return { terminals: newTerminals };
+    });
+  },
+
   getTerminalList: () => {
     const { terminals } = get();
     return Array.from(terminals.values()).map((t) => t.config);